@@ -1,11 +1,31 @@
 use crate::{
-   data_store::DataStore,
+   chunk_repo::{preview_file_chunks, process_github_repo, remote_head_commit},
+   chunks::{Chunk, ChunkKind, changelog::normalize_version},
+   config::{ChunkPreferences, EmbeddingConfig},
+   data_store::{ChunkFilter, ChunkPayload, DataStore, Granularity},
+   embedder::{ApiKey, available_providers},
    error::BackendError,
-   github_processor::process_and_embed_github_repo,
-   query::QueryService,
+   export::{
+      allowed_export_dirs, export_jsonl, import_chunks, parse_jsonl, resolve_export_path,
+      validate_vector_dimensions,
+   },
+   git_history::GIT_HISTORY_PATH,
+   github_processor::{
+      EmbedOptions, EmbedResult, process_and_embed_file_url, process_and_embed_github_repo,
+      update_github_repo_diff, update_github_repo_files,
+   },
+   keywords::{highlight_matching_lines, top_terms},
+   metrics::Metrics,
+   prompts,
+   query::{
+      MultiRepoResults, QueryEmbeddingCache, QueryLimiter, QueryOptions, QueryService,
+      VectorSizeMismatch, record_query, search_by_vector,
+   },
+   similarity::{rank_by_similarity, relative_relevance},
    utils::{
-      extract_repo_name_from_url, gen_table_name_for_repo, parse_collection_name_to_repo,
-      parse_repository_input,
+      extract_repo_name_from_url, gen_permalink, gen_table_name_for_repo, is_repo_alias,
+      language_name, matches_repo_pattern, parse_collection_name_to_repo, parse_repository_input,
+      raw_code,
    },
 };
 use anyhow::{Context, Result};
@@ -17,21 +37,38 @@ use rmcp::{
    tool,
 };
 use serde::{Deserialize, Deserializer, Serialize};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+   collections::{BTreeMap, HashMap},
+   sync::Arc,
+   time::Duration,
+};
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 /// Custom deserializer for repository input that accepts either full URLs or owner/repo
-/// format
+/// format. Aliases are kept as given, to be resolved by `resolve_repo_alias`.
 fn deserialize_repository<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
    D: Deserializer<'de>,
 {
    let input = String::deserialize(deserializer)?;
+   if is_repo_alias(&input) {
+      return Ok(input);
+   }
    parse_repository_input(&input).map_err(serde::de::Error::custom)
 }
 
+/// Deserializes a result limit, treating zero as the default limit since a search for no
+/// results would only report that nothing was found
+fn deserialize_limit<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+   D: Deserializer<'de>,
+{
+   let limit = u64::deserialize(deserializer)?;
+   Ok(if limit == 0 { default_limit() } else { limit })
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct GenDocsRequest {
    #[schemars(description = "Crate name to generate docs for")]
@@ -45,9 +82,52 @@ pub struct GenDocsRequest {
 pub struct EmbedRequest {
    #[serde(deserialize_with = "deserialize_repository")]
    #[schemars(
-      description = "Repository to embed. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo')"
+      description = "Repository to embed. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo'), or an alias given at embed time (e.g., 'tokio')"
    )]
    pub repo_url: String,
+   #[serde(default)]
+   #[schemars(
+      description = "Names of the functions and types to embed. When given, all other chunks are \
+                     skipped, producing a small collection for a few known APIs"
+   )]
+   pub symbols: Vec<String>,
+   #[serde(default)]
+   #[schemars(
+      description = "Add the chunks to the repository's existing collection instead of replacing \
+                     it, e.g. to embed further symbols into a collection built with `symbols`. \
+                     Chunks already stored are skipped"
+   )]
+   pub append: bool,
+   #[serde(default)]
+   #[schemars(
+      description = "Append even when the existing collection was embedded with another model, \
+                     whose vectors are not comparable with the new ones"
+   )]
+   pub force: bool,
+   #[serde(default)]
+   #[schemars(
+      description = "OpenAI API key to embed the repository with instead of the server's, so \
+                     usage is billed to your account. Never logged"
+   )]
+   pub api_key: Option<ApiKey>,
+   #[serde(default)]
+   #[schemars(
+      description = "Short name to query the repository by instead of its URL (e.g., 'tokio'). \
+                     Letters, digits, '-', '_' and '.' only. Kept when the repository is \
+                     re-embedded"
+   )]
+   pub alias: Option<String>,
+   #[serde(flatten)]
+   pub preferences: ChunkPreferences,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct EmbedFileUrlRequest {
+   #[schemars(
+      description = "URL of a single file to embed: a raw.githubusercontent.com file URL, a GitHub \
+                     file page (https://github.com/owner/repo/blob/main/src/lib.rs) or a gist"
+   )]
+   pub url: String,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -56,12 +136,263 @@ pub struct QueryRequest {
    pub query: String,
    #[serde(deserialize_with = "deserialize_repository")]
    #[schemars(
-      description = "Repository to search in. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo')"
+      description = "Repository to search in. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo'), or an alias given at embed time (e.g., 'tokio')"
+   )]
+   pub repo_url: String,
+   #[serde(default = "default_limit", deserialize_with = "deserialize_limit")]
+   #[schemars(description = "Number of results to return (defaults to 10, also used for 0)")]
+   pub limit: u64,
+   #[serde(default)]
+   #[schemars(
+      description = "Drop results whose content duplicates a better match, fetching extra \
+                     candidates to fill the limit (defaults to false)"
+   )]
+   pub dedup: bool,
+   #[serde(default)]
+   #[schemars(
+      description = "Share of file recency in the ranking, from 0.0 for similarity alone to 1.0 \
+                     for recency alone, so recently changed code ranks higher. Only affects \
+                     repositories embedded with file recency (defaults to 0.0)"
+   )]
+   pub recency_weight: f32,
+   #[serde(default)]
+   #[schemars(
+      description = "How many candidates the nearest-neighbor search explores (HNSW ef), at most \
+                     512. Higher values find close matches the default search can miss, at the \
+                     cost of latency that grows with the value. Uses the server default when \
+                     omitted"
+   )]
+   pub search_ef: Option<u64>,
+   #[serde(default)]
+   #[schemars(
+      description = "Also report each result's relevance relative to the other results, from 1.0 \
+                     for the best to 0.0 for the worst, since raw scores fall in a narrow band \
+                     (defaults to false)"
+   )]
+   pub calibrate: bool,
+   #[serde(default)]
+   #[schemars(
+      description = "Also list the outline of each result's file, the kind, name and line range \
+                     of every chunk in it, to show where the result sits in the file (defaults to \
+                     false)"
+   )]
+   pub with_outline: bool,
+   #[serde(default)]
+   #[schemars(
+      description = "Only return results of this granularity: 'chunk' for single items such as \
+                     functions, 'file' for whole files or 'signature' for function signatures \
+                     alone, when those were embedded too. All are mixed when omitted"
+   )]
+   pub granularity: Option<Granularity>,
+   #[serde(default)]
+   #[schemars(
+      description = "Only return changelog and release notes entries of this version, e.g. \
+                     '1.2.3' or 'Unreleased'"
+   )]
+   pub version: Option<String>,
+   #[serde(default)]
+   #[schemars(
+      description = "Only return results whose content contains all the words of this text, e.g. \
+                     an identifier such as 'spawn_blocking'"
+   )]
+   pub contains: Option<String>,
+   #[serde(default)]
+   #[schemars(
+      description = "Also report where the query time went: embedding the query, and whether its \
+                     embedding was cached, versus searching (defaults to false)"
+   )]
+   pub debug: bool,
+   #[serde(default)]
+   #[schemars(
+      description = "Mark with ** the lines of each result that contain words of the query, to \
+                     show which part of a large chunk matched (defaults to false)"
+   )]
+   pub highlight: bool,
+   #[serde(default)]
+   #[schemars(
+      description = "Return just the code of each result, without the surrounding ``` fences and \
+                     doc comment prose, ready to paste. Markdown, text, comment and commit \
+                     results are returned as-is (defaults to false)"
+   )]
+   pub raw: bool,
+   #[serde(default)]
+   #[schemars(
+      description = "OpenAI API key to embed the query with instead of the server's, so usage is \
+                     billed to your account. Never logged"
+   )]
+   pub api_key: Option<ApiKey>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct VectorQueryRequest {
+   #[serde(deserialize_with = "deserialize_repository")]
+   #[schemars(
+      description = "Repository to search in. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo'), or an alias given at embed time (e.g., 'tokio')"
    )]
    pub repo_url: String,
-   #[serde(default = "default_limit")]
-   #[schemars(description = "Number of results to return (defaults to 10)")]
+   #[schemars(
+      description = "Precomputed query embedding, with as many dimensions as the repository was \
+                     embedded with"
+   )]
+   pub vector: Vec<f32>,
+   #[serde(default = "default_limit", deserialize_with = "deserialize_limit")]
+   #[schemars(description = "Number of results to return (defaults to 10, also used for 0)")]
+   pub limit: u64,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct MultiRepoQueryRequest {
+   #[schemars(description = "Query to search for in the embedded docs")]
+   pub query: String,
+   #[schemars(
+      description = "Pattern selecting the repositories to search, in owner/repo form. Supports \
+                     '*' wildcards (e.g., 'acme/*'); without a wildcard it matches as a prefix"
+   )]
+   pub repo_pattern: String,
+   #[serde(default = "default_limit", deserialize_with = "deserialize_limit")]
+   #[schemars(description = "Number of results to return (defaults to 10, also used for 0)")]
    pub limit: u64,
+   #[serde(default)]
+   #[schemars(
+      description = "OpenAI API key to embed the query with instead of the server's, so usage is \
+                     billed to your account. Never logged"
+   )]
+   pub api_key: Option<ApiKey>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct BestAnswerRequest {
+   #[schemars(description = "Question to answer from the embedded docs")]
+   pub query: String,
+   #[serde(deserialize_with = "deserialize_repository")]
+   #[schemars(
+      description = "Repository to search in. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo'), or an alias given at embed time (e.g., 'tokio')"
+   )]
+   pub repo_url: String,
+   #[serde(default = "default_min_score")]
+   #[schemars(
+      description = "Minimum similarity score required to return an answer (defaults to 0.35)"
+   )]
+   pub min_score: f32,
+   #[serde(default)]
+   #[schemars(
+      description = "OpenAI API key to embed the query with instead of the server's, so usage is \
+                     billed to your account. Never logged"
+   )]
+   pub api_key: Option<ApiKey>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ExportRequest {
+   #[serde(deserialize_with = "deserialize_repository")]
+   #[schemars(
+      description = "Repository whose embeddings to export. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo'), or an alias given at embed time (e.g., 'tokio')"
+   )]
+   pub repo_url: String,
+   #[serde(default)]
+   #[schemars(
+      description = "Optional file path to write the JSONL export to. Must be inside one of the \
+                     server's allowed export directories. When omitted the JSONL is returned \
+                     inline"
+   )]
+   pub path: Option<String>,
+   #[serde(default)]
+   #[schemars(
+      description = "Whether to include the embedding vectors in the export (defaults to false)"
+   )]
+   pub include_vectors: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ImportRequest {
+   #[serde(deserialize_with = "deserialize_repository")]
+   #[schemars(
+      description = "Repository to import the embeddings as. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo'), or an alias given at embed time (e.g., 'tokio')"
+   )]
+   pub repo_url: String,
+   #[serde(default)]
+   #[schemars(
+      description = "Path of a JSONL file produced by export_collection. Must be inside one of \
+                     the server's allowed export directories"
+   )]
+   pub path: Option<String>,
+   #[serde(default)]
+   #[schemars(description = "Inline JSONL content, used when no path is given")]
+   pub jsonl: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UpdateLabelsRequest {
+   #[serde(deserialize_with = "deserialize_repository")]
+   #[schemars(
+      description = "Embedded repository to relabel. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo'), or an alias given at embed time (e.g., 'tokio')"
+   )]
+   pub repo_url: String,
+   #[schemars(
+      description = "Labels to set, e.g. {\"team\": \"b\"}. Existing labels not listed are kept; \
+                     a label with an empty value is removed"
+   )]
+   pub labels: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RenameRepoRequest {
+   #[serde(deserialize_with = "deserialize_repository")]
+   #[schemars(
+      description = "Embedded repository under its previous URL. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo'), or an alias given at embed time (e.g., 'tokio')"
+   )]
+   pub old_url: String,
+   #[serde(deserialize_with = "deserialize_repository")]
+   #[schemars(
+      description = "URL the repository moved to. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo')"
+   )]
+   pub new_url: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UpdateFilesRequest {
+   #[serde(deserialize_with = "deserialize_repository")]
+   #[schemars(
+      description = "Embedded repository to update. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo'), or an alias given at embed time (e.g., 'tokio')"
+   )]
+   pub repo_url: String,
+   #[schemars(
+      description = "Paths of the changed files relative to the repository root. Files that were \
+                     deleted have their chunks removed"
+   )]
+   pub paths: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct EmbedDiffRequest {
+   #[serde(deserialize_with = "deserialize_repository")]
+   #[schemars(
+      description = "Embedded repository to update. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo'), or an alias given at embed time (e.g., 'tokio')"
+   )]
+   pub repo_url: String,
+   #[schemars(
+      description = "Branch, tag or commit SHA the embedding is up to date with (e.g., 'main')"
+   )]
+   pub base_ref: String,
+   #[schemars(
+      description = "Branch, tag or commit SHA whose changes to embed (e.g., 'feature/parser')"
+   )]
+   pub head_ref: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PreviewChunksRequest {
+   #[serde(deserialize_with = "deserialize_repository")]
+   #[schemars(
+      description = "Repository containing the file. Can be either a full GitHub URL (e.g., 'https://github.com/owner/repo') or shorthand format (e.g., 'owner/repo'), or an alias given at embed time (e.g., 'tokio')"
+   )]
+   pub repo_url: String,
+   #[serde(default, alias = "path")]
+   #[schemars(
+      description = "Path of the file relative to the repository root (e.g., 'src/lib.rs'). When \
+                     omitted, every file of the repository is previewed"
+   )]
+   pub file_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -70,113 +401,146 @@ pub struct StatusRequest {
    pub operation_id: String,
 }
 
+/// Whether the server runs read-only, read from `READ_ONLY`. Tools that embed, import or
+/// otherwise change collections are then rejected, while querying and listing keep working.
+pub fn read_only_mode() -> bool {
+   dotenvy::var("READ_ONLY").is_ok_and(|value| value == "true")
+}
+
 fn default_limit() -> u64 {
    10
 }
 
+fn default_min_score() -> f32 {
+   0.35
+}
+
+/// Embed operations by ID, shared by every session so they all see the embeds in flight
+pub type EmbedOperations = Arc<RwLock<HashMap<String, EmbedOperation>>>;
+
 #[derive(Debug, Clone)]
 pub struct EmbedOperation {
    pub status: EmbedStatus,
    pub repo_url: String,
    pub message: String,
+   /// Structured summary, set once the embedding completed, fully or partially
+   pub result: Option<EmbedResult>,
+}
+
+impl EmbedOperation {
+   /// Describes the operation for status responses, followed by the structured result as
+   /// JSON when the embedding completed
+   fn status_report(&self, operation_id: &str) -> Result<String> {
+      let status_text = match &self.status {
+         EmbedStatus::InProgress => "in_progress",
+         EmbedStatus::Completed => "completed",
+         EmbedStatus::PartiallyCompleted => "partially_completed",
+         EmbedStatus::Failed => "failed",
+      };
+
+      let mut report = format!(
+         "Embed operation {} for {}: {} - {}",
+         operation_id, self.repo_url, status_text, self.message
+      );
+      if let Some(result) = &self.result {
+         report.push('\n');
+         report.push_str(&serde_json::to_string_pretty(result)?);
+      }
+
+      Ok(report)
+   }
 }
 
 #[derive(Debug, Clone)]
 pub enum EmbedStatus {
    InProgress,
    Completed,
+   /// Some embedding batches timed out; the chunks embedded were stored and are queryable,
+   /// the others are listed in the result's `failed_ranges`
+   PartiallyCompleted,
    Failed,
 }
 
 #[derive(Clone, Default)]
 pub struct Backend {
-   embed_operations: Arc<RwLock<HashMap<String, EmbedOperation>>>,
+   embed_operations: EmbedOperations,
    cancellation_token: CancellationToken,
+   metrics: Arc<Metrics>,
+   query_cache: Arc<QueryEmbeddingCache>,
+   query_limiter: Arc<QueryLimiter>,
+   read_only: bool,
 }
 
-#[tool(tool_box)]
 impl Backend {
-   /// Provides graceful shutdown capability by allowing background operations
-   /// to be cancelled when the server needs to terminate
-   pub fn new(cancellation_token: CancellationToken) -> Self {
-      Self {
-         cancellation_token,
-         ..Default::default()
+   /// Rejects a tool that changes collections when the server is read-only
+   fn ensure_writable(&self, tool: &str) -> Result<(), McpError> {
+      if self.read_only {
+         return Err(McpError::invalid_request(
+            format!("Server is read-only: {tool} is disabled"),
+            None,
+         ));
       }
+      Ok(())
    }
 
-   #[tool(description = "Generate and embed documentation from a Git repository")]
-   async fn embed_repo(&self, #[tool(aggr)] req: EmbedRequest) -> Result<CallToolResult, McpError> {
-      tracing::info!("Starting embed_repo for repository: {}", req.repo_url);
-      // Extract a safe name from the URL for the operation ID
-      let repo_name = extract_repo_name_from_url(&req.repo_url).map_err(BackendError::Internal)?;
-      let operation_id = format!("embed_{}_{}", repo_name, Uuid::new_v4());
-      tracing::debug!("Generated operation ID: {}", operation_id);
-      let ops = self.embed_operations.clone();
-      let cancellation_token = self.cancellation_token.child_token();
-
-      // Check if this repo is already embedded
-      let table_name = gen_table_name_for_repo(&req.repo_url).map_err(|e| {
-         McpError::invalid_request(format!("Failed to generate table name: {e}"), None)
-      })?;
-      tracing::debug!("Generated table name: {}", table_name);
-
-      tracing::info!("Checking if {} is already embedded", req.repo_url);
-
-      if let Ok(qdrant_url) = dotenvy::var("QDRANT_URL")
-         && let Ok(qdrant_client) = qdrant_client::Qdrant::from_url(&qdrant_url)
-            .api_key(dotenvy::var("QDRANT_API_KEY").ok())
-            .build()
-         && let Ok(exists) = qdrant_client.collection_exists(&table_name).await
-         && exists
-      {
-         tracing::info!("Repository {} is already embedded, skipping", req.repo_url);
-         return Ok(CallToolResult::success(vec![Content::text(format!(
-            "Repository {} is already embedded",
-            req.repo_url
-         ))]));
+   /// Registers a new in-progress embed operation for the repository, unless one is already
+   /// running for it. Returns the id of the running operation in that case, so concurrent
+   /// requests for the same repository share a single background embed.
+   async fn register_operation(&self, operation_id: &str, repo_url: &str) -> Option<String> {
+      let mut ops_lock = self.embed_operations.write().await;
+      if let Some(existing_id) = in_progress_operation(&ops_lock, repo_url) {
+         return Some(existing_id);
       }
+
       tracing::info!(
-         "Repository {} not found in embeddings, proceeding with embedding",
-         req.repo_url
+         "Registering operation {} for repository {}",
+         operation_id,
+         repo_url
       );
+      ops_lock.insert(
+         operation_id.to_string(),
+         EmbedOperation {
+            status: EmbedStatus::InProgress,
+            repo_url: repo_url.to_string(),
+            message: "Starting repository processing and embedding".to_string(),
+            result: None,
+         },
+      );
+      None
+   }
 
-      {
-         tracing::debug!("Acquiring write lock for operations tracking");
-         let mut ops_lock = ops.write().await;
-         tracing::info!(
-            "Registering operation {} for repository {}",
-            operation_id,
-            req.repo_url
-         );
-         ops_lock.insert(
-            operation_id.clone(),
-            EmbedOperation {
-               status: EmbedStatus::InProgress,
-               repo_url: req.repo_url.clone(),
-               message: "Starting repository processing and embedding".to_string(),
-            },
-         );
-      }
-
-      let background_operation_id = operation_id.clone();
-      let repo_url = req.repo_url.clone();
+   /// Runs an operation registered with `register_operation` in the background and records
+   /// the outcome of `embed` once it resolves. Each operation is given a child of the server's
+   /// cancellation token, so shutting down cancels every operation while cancelling one
+   /// leaves the others running.
+   fn spawn_operation<F>(
+      &self,
+      operation_id: String,
+      repo_url: String,
+      cancellation_token: CancellationToken,
+      embed: F,
+   ) -> tokio::task::JoinHandle<()>
+   where
+      F: Future<Output = anyhow::Result<EmbedResult>> + Send + 'static,
+   {
+      let ops = self.embed_operations.clone();
+      let metrics = self.metrics.clone();
 
       tokio::spawn(async move {
          tracing::info!(
             "Spawning background task for embedding {} (operation: {})",
             repo_url,
-            background_operation_id
+            operation_id
          );
          let result = tokio::select! {
             _ = cancellation_token.cancelled() => {
-               tracing::warn!("Operation {} cancelled for repository {}", background_operation_id, repo_url);
+               tracing::warn!("Operation {} cancelled for repository {}", operation_id, repo_url);
                Err(anyhow::anyhow!("Operation cancelled"))
             }
             res = async {
                // Process GitHub repository and embed it
                tracing::info!("Starting GitHub repository processing for {}", repo_url);
-               let embed_result = process_and_embed_github_repo(&repo_url).await;
+               let embed_result = embed.await;
                match &embed_result {
                   Ok(_) => tracing::info!("Successfully processed repository for {}", repo_url),
                   Err(e) => tracing::error!("Failed to process repository for {}: {}", repo_url, e),
@@ -185,115 +549,1146 @@ impl Backend {
             } => res
          };
 
-         tracing::debug!("Updating operation status for {}", background_operation_id);
+         tracing::debug!("Updating operation status for {}", operation_id);
          let mut ops_lock = ops.write().await;
-         if let Some(op) = ops_lock.get_mut(&background_operation_id) {
+         if let Some(op) = ops_lock.get_mut(&operation_id) {
             match result {
-               Ok(_) => {
-                  op.status = EmbedStatus::Completed;
-                  op.message = format!(
-                     "Successfully processed and embedded repository {}",
-                     op.repo_url
-                  );
+               Ok(embed_result) => {
+                  metrics.embed_completed(Duration::from_secs_f64(embed_result.duration_secs));
+                  if embed_result.failed_ranges.is_empty() {
+                     op.status = EmbedStatus::Completed;
+                     op.message = format!(
+                        "Successfully processed and embedded repository {}",
+                        op.repo_url
+                     );
+                  } else {
+                     op.status = EmbedStatus::PartiallyCompleted;
+                     op.message = format!(
+                        "Embedded {} chunks of repository {}, {} ranges timed out and can be \
+                         retried with update_files",
+                        embed_result.chunk_count,
+                        op.repo_url,
+                        embed_result.failed_ranges.len()
+                     );
+                  }
+                  op.result = Some(embed_result);
                   tracing::info!(
                      "Operation {} completed successfully for {}",
-                     background_operation_id,
+                     operation_id,
                      op.repo_url
                   );
                }
                Err(e) => {
+                  metrics.embed_failed();
                   op.status = EmbedStatus::Failed;
                   op.message = format!("Failed to embed repository: {e}");
                   tracing::error!(
                      "Operation {} failed for {}: {}",
-                     background_operation_id,
+                     operation_id,
                      op.repo_url,
                      e
                   );
                }
             }
          } else {
-            tracing::warn!(
-               "Operation {} not found in tracking map",
-               background_operation_id
-            );
+            tracing::warn!("Operation {} not found in tracking map", operation_id);
          }
-      });
+      })
+   }
+}
 
-      tracing::info!(
-         "Embed operation {} started for repository {}",
-         operation_id,
-         req.repo_url
-      );
-      Ok(CallToolResult::success(vec![Content::text(format!(
-         "Started repository processing and embedding with ID: {operation_id}. Sleep for about 6 \
-          seconds and then Use \"check_embed_status\" to monitor progress --- do this until it \
-          either succeeds or fails."
-      ))]))
+/// Cites the file and lines a chunk comes from, with a permalink at the embedded `commit` for
+/// repositories on GitHub. Without a recorded commit there is nothing stable to link to, so
+/// the permalink is left out. Notebook chunks are cited by cell since their lines are relative
+/// to it, so the permalink leaves out the line anchor that would point into the notebook's JSON.
+fn cite_chunk(repo_url: &str, commit: Option<&str>, chunk: &ChunkPayload) -> String {
+   let (Some(path), Some(start), Some(end)) = (&chunk.file_path, chunk.start_line, chunk.end_line)
+   else {
+      return "Source: unknown (re-embed the repository to enable citations)".to_string();
+   };
+
+   let (mut citation, lines) = match chunk.cell {
+      Some(cell) => (
+         format!("Source: {path} (cell {cell}, lines {start}-{end})"),
+         None,
+      ),
+      None => (
+         format!("Source: {path} (lines {start}-{end})"),
+         Some((start, end)),
+      ),
+   };
+   // commit chunks have no file to link to
+   if path != GIT_HISTORY_PATH
+      && let Some(commit) = commit
+      && let Some(permalink) = gen_permalink(repo_url, commit, path, lines)
+   {
+      citation.push_str(&format!("\nPermalink: {permalink}"));
    }
+   citation
+}
 
-   #[tool(description = "Perform semantic search on repository documentation embeddings")]
-   async fn query_embeddings(
-      &self,
-      #[tool(aggr)] req: QueryRequest,
-   ) -> Result<CallToolResult, McpError> {
-      // Check if embeddings exist for this repository
+/// Content of a chunk as the `raw` option returns it. Prose chunks are returned as-is: they
+/// have no code to extract, and a comment chunk would come back empty without its doc comments.
+fn raw_content(chunk: &ChunkPayload) -> String {
+   let is_prose = matches!(
+      chunk.kind,
+      Some(
+         ChunkKind::MarkdownSection | ChunkKind::TextBlock | ChunkKind::Comment | ChunkKind::Commit
+      )
+   );
+   if is_prose {
+      chunk.content.clone()
+   } else {
+      raw_code(&chunk.content)
+   }
+}
+
+/// Lists the files whose chunks were cut to `EMBED_MAX_CHUNKS_PER_FILE`, as the `capped_files`
+/// of a full embed's result do
+fn push_capped_files(message: &mut String, capped_files: &[String]) {
+   if capped_files.is_empty() {
+      return;
+   }
+   message.push_str(&format!(
+      "\n{} files produced more chunks than the per-file limit and were cut to it:",
+      capped_files.len()
+   ));
+   for file in capped_files {
+      message.push_str(&format!("\n- {file}"));
+   }
+}
+
+/// Finds the id of an embed operation still running for the repository
+fn in_progress_operation(ops: &HashMap<String, EmbedOperation>, repo_url: &str) -> Option<String> {
+   ops.iter()
+      .find(|(_, op)| matches!(op.status, EmbedStatus::InProgress) && op.repo_url == repo_url)
+      .map(|(id, _)| id.clone())
+}
+
+/// The commit an embedded repository was embedded at, when it is still the HEAD of the
+/// remote. `None` when the remote has moved on, or when either commit is unknown, in which
+/// case the repository is re-embedded.
+async fn up_to_date_commit(
+   qdrant_client: &qdrant_client::Qdrant,
+   repo_url: &str,
+) -> Option<String> {
+   let embedded = DataStore::get_metadata(qdrant_client, repo_url)
+      .await
+      .ok()
+      .flatten()?
+      .commit?;
+   let remote = match remote_head_commit(repo_url).await {
+      Ok(remote) => remote?,
+      Err(e) => {
+         tracing::warn!("Failed to read the HEAD of {repo_url}: {e:#}");
+         return None;
+      }
+   };
+
+   if remote != embedded {
+      tracing::info!(
+         "Repository {repo_url} moved from {embedded} to {remote} since it was embedded"
+      );
+      return None;
+   }
+   Some(embedded)
+}
+
+/// Lists chunks by kind, line range and first non-blank line, numbered from 1
+fn chunk_preview_lines(chunks: &[Chunk]) -> Vec<String> {
+   chunks
+      .iter()
+      .enumerate()
+      .map(|(i, chunk)| {
+         let first_line = chunk
+            .content
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or_default()
+            .trim();
+         format!(
+            "{}. {:?} lines {}-{}: {}",
+            i + 1,
+            chunk.kind,
+            chunk.start_line,
+            chunk.end_line,
+            first_line
+         )
+      })
+      .collect()
+}
+
+/// Describes how every file of a repository is chunked, files in path order
+fn repo_chunk_breakdown(repo_url: &str, chunks_map: &HashMap<String, Vec<Chunk>>) -> String {
+   let mut file_paths: Vec<&String> = chunks_map.keys().collect();
+   file_paths.sort();
+
+   let chunk_count: usize = chunks_map.values().map(Vec::len).sum();
+   let mut lines = vec![format!(
+      "{repo_url} is chunked into {chunk_count} chunks across {} files:",
+      file_paths.len()
+   )];
+   for file_path in file_paths {
+      let chunks = &chunks_map[file_path];
+      lines.push(format!("\n{file_path}: {} chunks", chunks.len()));
+      lines.extend(chunk_preview_lines(chunks));
+   }
+   lines.join("\n")
+}
+
+/// Response for an embed request that joined an operation already running for the repository
+fn already_running_response(operation_id: &str, repo_url: &str) -> CallToolResult {
+   tracing::info!(
+      "Repository {} is already being embedded by operation {}",
+      repo_url,
+      operation_id
+   );
+   CallToolResult::success(vec![Content::text(format!(
+      "Repository {repo_url} is already being embedded with ID: {operation_id}. Use \
+       \"query_embed_status\" to monitor progress."
+   ))])
+}
+
+#[tool(tool_box)]
+impl Backend {
+   /// Provides graceful shutdown capability by allowing background operations
+   /// to be cancelled when the server needs to terminate. The metrics and query cache are
+   /// shared by all sessions. `READ_ONLY` is read here so every session honors it.
+   pub fn new(
+      embed_operations: EmbedOperations,
+      cancellation_token: CancellationToken,
+      metrics: Arc<Metrics>,
+      query_cache: Arc<QueryEmbeddingCache>,
+      query_limiter: Arc<QueryLimiter>,
+   ) -> Self {
+      Self {
+         embed_operations,
+         cancellation_token,
+         metrics,
+         query_cache,
+         query_limiter,
+         read_only: read_only_mode(),
+      }
+   }
+
+   #[tool(description = "Generate and embed documentation from a Git repository")]
+   async fn embed_repo(&self, #[tool(aggr)] req: EmbedRequest) -> Result<CallToolResult, McpError> {
+      self.ensure_writable("embed_repo")?;
+      let mut req = req;
+      resolve_repo_alias(&mut req.repo_url).await?;
+      tracing::info!("Starting embed_repo for repository: {}", req.repo_url);
+      if let Some(alias) = &req.alias {
+         ensure_alias_available(alias, &req.repo_url).await?;
+      }
+      // Extract a safe name from the URL for the operation ID
+      let repo_name = extract_repo_name_from_url(&req.repo_url).map_err(BackendError::from)?;
+      let operation_id = format!("embed_{}_{}", repo_name, Uuid::new_v4());
+      tracing::debug!("Generated operation ID: {}", operation_id);
+      let ops = self.embed_operations.clone();
+      let cancellation_token = self.cancellation_token.child_token();
+
+      // An embed already running for this repo has likely created its collection, so check
+      // for it before concluding the repo is fully embedded
+      if let Some(existing_id) = in_progress_operation(&*ops.read().await, &req.repo_url) {
+         return Ok(already_running_response(&existing_id, &req.repo_url));
+      }
+
+      // Check if this repo is already embedded
       let table_name = gen_table_name_for_repo(&req.repo_url).map_err(|e| {
          McpError::invalid_request(format!("Failed to generate table name: {e}"), None)
       })?;
+      tracing::debug!("Generated table name: {}", table_name);
+
+      tracing::info!("Checking if {} is already embedded", req.repo_url);
+
       if let Ok(qdrant_url) = dotenvy::var("QDRANT_URL")
          && let Ok(qdrant_client) = qdrant_client::Qdrant::from_url(&qdrant_url)
             .api_key(dotenvy::var("QDRANT_API_KEY").ok())
             .build()
+         && let Ok(exists) = qdrant_client.collection_exists(&table_name).await
+         && exists
+         && !req.append
+         && let Some(commit) = up_to_date_commit(&qdrant_client, &req.repo_url).await
       {
-         match qdrant_client.collection_exists(&table_name).await {
-            Ok(exists) => {
-               if !exists {
-                  return Err(McpError::invalid_request(
-                     format!("No embeddings found for repository: {}", req.repo_url),
-                     None,
-                  ));
-               }
+         tracing::info!(
+            "Repository {} is already embedded at its latest commit {commit}, skipping",
+            req.repo_url
+         );
+         let mut message = format!(
+            "Repository {} is already embedded at its latest commit {commit}",
+            req.repo_url
+         );
+         if let Some(alias) = &req.alias {
+            let data_store = DataStore::new_deferred(&req.repo_url).map_err(BackendError::from)?;
+            data_store
+               .set_alias(alias)
+               .await
+               .context("failed to set repository alias")
+               .map_err(BackendError::from)?;
+            message.push_str(&format!("; it can now be queried as \"{alias}\""));
+         }
+         return Ok(CallToolResult::success(vec![Content::text(message)]));
+      }
+      tracing::info!(
+         "Repository {} not embedded or out of date, proceeding with embedding",
+         req.repo_url
+      );
+
+      // Registration re-checks under the write lock, since another request may have started
+      // an embed of this repo while the collection check was pending
+      if let Some(existing_id) = self.register_operation(&operation_id, &req.repo_url).await {
+         return Ok(already_running_response(&existing_id, &req.repo_url));
+      }
+
+      let repo_url = req.repo_url.clone();
+      let options = EmbedOptions {
+         symbols: req.symbols.clone(),
+         append: req.append,
+         force: req.force,
+         alias: req.alias.clone(),
+         api_key: req.api_key.clone(),
+      };
+      let metrics = self.metrics.clone();
+      metrics.embed_started();
+      let preferences = req.preferences.clone().validated();
+
+      self.spawn_operation(
+         operation_id.clone(),
+         req.repo_url.clone(),
+         cancellation_token,
+         async move {
+            process_and_embed_github_repo(&repo_url, &options, &preferences, &metrics).await
+         },
+      );
+
+      tracing::info!(
+         "Embed operation {} started for repository {}",
+         operation_id,
+         req.repo_url
+      );
+      Ok(CallToolResult::success(vec![Content::text(format!(
+         "Started repository processing and embedding with ID: {operation_id}. Sleep for about 6 \
+          seconds and then Use \"check_embed_status\" to monitor progress --- do this until it \
+          either succeeds or fails."
+      ))]))
+   }
+
+   #[tool(
+      description = "Embed a single file from a GitHub URL (raw.githubusercontent.com file, \
+                     GitHub file page or gist) without cloning a repository. Returns the \
+                     repository id to pass as `repo_url` when querying the file"
+   )]
+   async fn embed_file_url(
+      &self,
+      #[tool(aggr)] req: EmbedFileUrlRequest,
+   ) -> Result<CallToolResult, McpError> {
+      self.ensure_writable("embed_file_url")?;
+      tracing::info!("Starting embed_file_url for {}", req.url);
+      self.metrics.embed_started();
+
+      let result = match process_and_embed_file_url(&req.url, &self.metrics).await {
+         Ok(result) => {
+            self
+               .metrics
+               .embed_completed(Duration::from_secs_f64(result.duration_secs));
+            result
+         }
+         Err(e) => {
+            self.metrics.embed_failed();
+            return Err(BackendError::from(e.context("failed to embed file")).into());
+         }
+      };
+
+      let repo_id = parse_collection_name_to_repo(&result.collection_name);
+      Ok(CallToolResult::success(vec![Content::text(format!(
+         "Embedded {} into {} chunks. Query it with repo_url \"{}\".",
+         req.url, result.chunk_count, repo_id
+      ))]))
+   }
+
+   #[tool(description = "Perform semantic search on repository documentation embeddings")]
+   async fn query_embeddings(
+      &self,
+      #[tool(aggr)] req: QueryRequest,
+   ) -> Result<CallToolResult, McpError> {
+      let mut req = req;
+      ensure_repo_embedded(&mut req.repo_url).await?;
+
+      let _permit = self.query_limiter.acquire().await;
+      let _timer = self.metrics.query_started("query_embeddings");
+      let query_service = QueryService::with_api_key(
+         self.metrics.clone(),
+         self.query_cache.clone(),
+         req.api_key.as_ref(),
+      )
+      .context("failed to initialize query service")
+      .map_err(BackendError::from)?;
+
+      let data_store = DataStore::new(&req.repo_url)
+         .await
+         .map_err(BackendError::from)?;
+
+      let (results, timings) = query_service
+         .query_embeddings(
+            &req.query,
+            data_store,
+            req.limit,
+            QueryOptions {
+               dedup: req.dedup,
+               recency_weight: req.recency_weight,
+               search_ef: req.search_ef,
+            },
+            &ChunkFilter {
+               granularity: req.granularity,
+               version: req.version.as_deref().map(normalize_version),
+               text: req.contains.clone(),
+            },
+         )
+         .await
+         .context("failed to query embeddings")
+         .map_err(BackendError::from)?;
+
+      if results.is_empty() {
+         return Err(BackendError::NoQueryResults(req.query.clone()).into());
+      }
+
+      let mut header = format!(
+         "Found {} results for query: {} (from repository: {})",
+         results.len(),
+         req.query,
+         req.repo_url
+      );
+      if req.debug {
+         header.push_str(&format!("\nLatency: {timings}"));
+      }
+
+      let mut contents = vec![Content::text(header)];
+
+      let scores: Vec<f32> = results.iter().map(|result| result.score).collect();
+      let relevance = req.calibrate.then(|| relative_relevance(&scores));
+
+      let outlines = if req.with_outline {
+         let mut file_paths: Vec<String> = results
+            .iter()
+            .filter_map(|result| result.chunk.file_path.clone())
+            .filter(|path| path != GIT_HISTORY_PATH)
+            .collect();
+         file_paths.sort();
+         file_paths.dedup();
+         query_service
+            .file_outlines(&req.repo_url, &file_paths)
+            .await
+            .context("failed to read file outlines")
+            .map_err(BackendError::from)?
+      } else {
+         HashMap::new()
+      };
+
+      for (i, result) in results.iter().enumerate() {
+         let score = match &relevance {
+            Some(relevance) => {
+               format!("score: {:.4}, relevance: {:.2}", result.score, relevance[i])
             }
-            Err(_) => {
-               // if we can't check, proceed with query anyway
+            None => format!("score: {:.4}", result.score),
+         };
+         let mut location = match (
+            &result.chunk.file_path,
+            result.chunk.start_line,
+            result.chunk.end_line,
+         ) {
+            (Some(path), Some(start), Some(end)) => match result.chunk.cell {
+               Some(cell) => format!(" [{path}, cell {cell}:{start}-{end}]"),
+               None => format!(" [{path}:{start}-{end}]"),
+            },
+            (Some(path), _, _) => format!(" [{path}]"),
+            _ => String::new(),
+         };
+         if result.chunk.sources.len() > 1 {
+            location.push_str(&format!(
+               " (appears in {} files)",
+               result.chunk.sources.len()
+            ));
+         }
+         if let Some(version) = &result.chunk.version {
+            location.push_str(&format!(" (version {version})"));
+         }
+         let content = if req.raw {
+            raw_content(&result.chunk)
+         } else {
+            result.chunk.content.clone()
+         };
+         let content = if req.highlight {
+            highlight_matching_lines(&content, &req.query)
+         } else {
+            content
+         };
+         let mut text = format!(
+            "\n--- Result {} ({}){} ---\n{}",
+            i + 1,
+            score,
+            location,
+            content
+         );
+         if let Some(path) = &result.chunk.file_path
+            && let Some(outline) = outlines.get(path)
+         {
+            text.push_str(&format!("\n\nOutline of {path}:"));
+            for entry in outline {
+               let is_result = (entry.start_line, entry.end_line)
+                  == (result.chunk.start_line, result.chunk.end_line);
+               let marker = if is_result { " <- this result" } else { "" };
+               text.push_str(&format!("\n- {entry}{marker}"));
             }
          }
+         contents.push(Content::text(text));
+      }
+
+      Ok(CallToolResult::success(contents))
+   }
+
+   #[tool(
+      description = "Search repository embeddings with a precomputed query vector instead of \
+                     query text, without calling the embedding API"
+   )]
+   async fn query_by_vector(
+      &self,
+      #[tool(aggr)] req: VectorQueryRequest,
+   ) -> Result<CallToolResult, McpError> {
+      let mut req = req;
+      ensure_repo_embedded(&mut req.repo_url).await?;
+
+      let _permit = self.query_limiter.acquire().await;
+      let _timer = self.metrics.query_started("query_by_vector");
+      let data_store = DataStore::new(&req.repo_url)
+         .await
+         .map_err(BackendError::from)?;
+
+      let results = search_by_vector(&data_store, req.vector, req.limit)
+         .await
+         .map_err(|e| match e.downcast_ref::<VectorSizeMismatch>() {
+            Some(mismatch) => McpError::invalid_params(mismatch.to_string(), None),
+            None => BackendError::from(e.context("failed to query by vector")).into(),
+         })?;
+      record_query(data_store);
+
+      if results.is_empty() {
+         return Err(BackendError::NoQueryResults("<vector>".to_string()).into());
+      }
+
+      let mut contents = vec![Content::text(format!(
+         "Found {} results for the query vector (from repository: {})",
+         results.len(),
+         req.repo_url
+      ))];
+      for (i, result) in results.iter().enumerate() {
+         let location = match (
+            &result.chunk.file_path,
+            result.chunk.start_line,
+            result.chunk.end_line,
+         ) {
+            (Some(path), Some(start), Some(end)) => format!(" [{path}:{start}-{end}]"),
+            (Some(path), _, _) => format!(" [{path}]"),
+            _ => String::new(),
+         };
+         contents.push(Content::text(format!(
+            "\n--- Result {} (score: {:.4}){} ---\n{}",
+            i + 1,
+            result.score,
+            location,
+            result.chunk.content
+         )));
+      }
+
+      Ok(CallToolResult::success(contents))
+   }
+
+   #[tool(
+      description = "Perform semantic search across all embedded repositories matching a pattern"
+   )]
+   async fn query_repos(
+      &self,
+      #[tool(aggr)] req: MultiRepoQueryRequest,
+   ) -> Result<CallToolResult, McpError> {
+      let qdrant_client = connect_qdrant()?;
+
+      let repo_urls: Vec<String> = list_repo_collections(&qdrant_client)
+         .await?
+         .into_iter()
+         .filter(|(_, repo_name)| matches_repo_pattern(&req.repo_pattern, repo_name))
+         .map(|(_, repo_name)| format!("https://github.com/{repo_name}"))
+         .collect();
+
+      if repo_urls.is_empty() {
+         return Err(McpError::invalid_request(
+            format!(
+               "No embedded repositories match the pattern: {}",
+               req.repo_pattern
+            ),
+            None,
+         ));
+      }
+      tracing::info!(
+         "Pattern {} matched {} repositories",
+         req.repo_pattern,
+         repo_urls.len()
+      );
+
+      let _permit = self.query_limiter.acquire().await;
+      let _timer = self.metrics.query_started("query_repos");
+      let query_service = QueryService::with_api_key(
+         self.metrics.clone(),
+         self.query_cache.clone(),
+         req.api_key.as_ref(),
+      )
+      .context("failed to initialize query service")
+      .map_err(BackendError::from)?;
+
+      let MultiRepoResults {
+         results,
+         timed_out,
+         failed,
+      } = query_service
+         .query_repos(&req.query, &repo_urls, req.limit)
+         .await
+         .context("failed to query embeddings")
+         .map_err(BackendError::from)?;
+
+      if results.is_empty() && timed_out.is_empty() && failed.is_empty() {
+         return Err(BackendError::NoQueryResults(req.query.clone()).into());
+      }
+
+      let mut header = format!(
+         "Found {} results for query: {} (from {} repositories matching: {})",
+         results.len(),
+         req.query,
+         repo_urls.len(),
+         req.repo_pattern
+      );
+      if !timed_out.is_empty() {
+         header.push_str(&format!(
+            "\nNote: results are partial, the search timed out for {} repositories: {}",
+            timed_out.len(),
+            timed_out.join(", ")
+         ));
+      }
+      if !failed.is_empty() {
+         header.push_str(&format!(
+            "\nNote: results are partial, the search failed for {} repositories: {}",
+            failed.len(),
+            failed.join(", ")
+         ));
+      }
+
+      let mut contents = vec![Content::text(header)];
+
+      for (i, (repo_url, result)) in results.iter().enumerate() {
+         let location = match &result.chunk.file_path {
+            Some(path) => format!(" [{path}]"),
+            None => String::new(),
+         };
+         contents.push(Content::text(format!(
+            "\n--- Result {} (score: {:.4}) {}{} ---\n{}",
+            i + 1,
+            result.score,
+            repo_url,
+            location,
+            result.chunk.content
+         )));
+      }
+
+      Ok(CallToolResult::success(contents))
+   }
+
+   #[tool(
+      description = "Return the single most relevant snippet for a question with a citation (file \
+                     path, line range and permalink), or report that no confident answer exists"
+   )]
+   async fn best_answer(
+      &self,
+      #[tool(aggr)] req: BestAnswerRequest,
+   ) -> Result<CallToolResult, McpError> {
+      let mut req = req;
+      ensure_repo_embedded(&mut req.repo_url).await?;
+
+      let _permit = self.query_limiter.acquire().await;
+      let _timer = self.metrics.query_started("best_answer");
+      let query_service = QueryService::with_api_key(
+         self.metrics.clone(),
+         self.query_cache.clone(),
+         req.api_key.as_ref(),
+      )
+      .context("failed to initialize query service")
+      .map_err(BackendError::from)?;
+
+      let best_match = query_service
+         .best_match(&req.query, &req.repo_url)
+         .await
+         .context("failed to query embeddings")
+         .map_err(BackendError::from)?;
+
+      let Some(top) = best_match else {
+         return Err(BackendError::NoQueryResults(req.query.clone()).into());
+      };
+
+      if top.score < req.min_score {
+         tracing::info!(
+            "Top result for '{}' scored {:.4}, below threshold {:.4}",
+            req.query,
+            top.score,
+            req.min_score
+         );
+         return Ok(CallToolResult::success(vec![Content::text(format!(
+            "No confident answer found for query: {} (best score {:.4} is below the threshold of \
+             {:.4})",
+            req.query, top.score, req.min_score
+         ))]));
+      }
+
+      let commit = DataStore::get_metadata(&connect_qdrant()?, &req.repo_url)
+         .await
+         .ok()
+         .flatten()
+         .and_then(|metadata| metadata.commit);
+      let citation = cite_chunk(&req.repo_url, commit.as_deref(), &top.chunk);
+      // fences name languages in lowercase
+      let language = top
+         .chunk
+         .file_path
+         .as_deref()
+         .and_then(language_name)
+         .map(str::to_lowercase)
+         .unwrap_or_default();
+
+      Ok(CallToolResult::success(vec![Content::text(format!(
+         "Best answer for query: {} (from repository: {}, score: {:.4})\n\n{}\n\n```{}\n{}\n```",
+         req.query, req.repo_url, top.score, citation, language, top.chunk.content
+      ))]))
+   }
+
+   #[tool(
+      description = "Export the chunks of an embedded repository as JSONL for backup, migration \
+                     or offline analysis"
+   )]
+   async fn export_collection(
+      &self,
+      #[tool(aggr)] req: ExportRequest,
+   ) -> Result<CallToolResult, McpError> {
+      let mut req = req;
+      ensure_repo_embedded(&mut req.repo_url).await?;
+
+      // validate the destination before doing any work
+      let path = req
+         .path
+         .as_deref()
+         .map(|path| resolve_export_path(path, &allowed_export_dirs()))
+         .transpose()
+         .map_err(|e| McpError::invalid_request(e.to_string(), None))?;
+
+      let data_store = DataStore::new(&req.repo_url)
+         .await
+         .map_err(BackendError::from)?;
+      let (jsonl, chunk_count) = export_jsonl(&data_store, &req.repo_url, req.include_vectors)
+         .await
+         .map_err(BackendError::from)?;
+
+      let Some(path) = path else {
+         return Ok(CallToolResult::success(vec![Content::text(jsonl)]));
+      };
+
+      tokio::fs::write(&path, jsonl)
+         .await
+         .with_context(|| format!("failed to write export to {}", path.display()))
+         .map_err(BackendError::from)?;
+
+      tracing::info!(
+         "Exported {} chunks from {} to {}",
+         chunk_count,
+         req.repo_url,
+         path.display()
+      );
+      Ok(CallToolResult::success(vec![Content::text(format!(
+         "Exported {} chunks from {} to {}",
+         chunk_count,
+         req.repo_url,
+         path.display()
+      ))]))
+   }
+
+   #[tool(
+      description = "Import chunks from a JSONL export into a new collection, re-embedding any \
+                     chunks exported without vectors"
+   )]
+   async fn import_collection(
+      &self,
+      #[tool(aggr)] req: ImportRequest,
+   ) -> Result<CallToolResult, McpError> {
+      self.ensure_writable("import_collection")?;
+      let mut req = req;
+      resolve_repo_alias(&mut req.repo_url).await?;
+      let jsonl = match (&req.path, req.jsonl) {
+         (Some(path), _) => {
+            let path = resolve_export_path(path, &allowed_export_dirs())
+               .map_err(|e| McpError::invalid_request(e.to_string(), None))?;
+            tokio::fs::read_to_string(&path).await.map_err(|e| {
+               McpError::invalid_request(format!("Failed to read import: {e}"), None)
+            })?
+         }
+         (None, Some(jsonl)) => jsonl,
+         (None, None) => {
+            return Err(McpError::invalid_request(
+               "Either a path or inline jsonl must be provided",
+               None,
+            ));
+         }
+      };
+
+      let (prefixes, chunks) = parse_jsonl(&jsonl)
+         .and_then(|(prefixes, chunks)| {
+            validate_vector_dimensions(&chunks).map(|_| (prefixes, chunks))
+         })
+         .map_err(|e| McpError::invalid_request(format!("Invalid import: {e:#}"), None))?;
+
+      let qdrant_client = connect_qdrant()?;
+      let table_name = gen_table_name_for_repo(&req.repo_url).map_err(|e| {
+         McpError::invalid_request(format!("Failed to generate table name: {e}"), None)
+      })?;
+      if qdrant_client
+         .collection_exists(&table_name)
+         .await
+         .context("failed to check collection")
+         .map_err(BackendError::from)?
+      {
+         return Err(McpError::invalid_request(
+            format!(
+               "Repository {} is already embedded; imports only load into new collections",
+               req.repo_url
+            ),
+            None,
+         ));
+      }
+
+      let data_store = DataStore::new_deferred(&req.repo_url).map_err(BackendError::from)?;
+      let summary = import_chunks(data_store, prefixes, chunks, &self.metrics)
+         .await
+         .context("failed to import chunks")
+         .map_err(BackendError::from)?;
+
+      Ok(CallToolResult::success(vec![Content::text(format!(
+         "Imported {} chunks into {} ({} with stored vectors, {} re-embedded)",
+         summary.with_vectors + summary.re_embedded,
+         req.repo_url,
+         summary.with_vectors,
+         summary.re_embedded
+      ))]))
+   }
+
+   #[tool(
+      description = "Re-embed the files of an embedded repository that changed between two git \
+                     refs, as they are at the head ref. Chunks of files deleted since the base \
+                     ref are removed"
+   )]
+   async fn embed_diff(
+      &self,
+      #[tool(aggr)] req: EmbedDiffRequest,
+   ) -> Result<CallToolResult, McpError> {
+      self.ensure_writable("embed_diff")?;
+      let mut req = req;
+      ensure_repo_embedded(&mut req.repo_url).await?;
+
+      let (changed_files, update) =
+         update_github_repo_diff(&req.repo_url, &req.base_ref, &req.head_ref, &self.metrics)
+            .await
+            .context("failed to embed changes")
+            .map_err(BackendError::from)?;
+
+      let mut message = format!(
+         "Updated {} files changed between {} and {} in {} ({} chunks embedded)",
+         changed_files.len(),
+         req.base_ref,
+         req.head_ref,
+         req.repo_url,
+         update.chunk_count
+      );
+      for file in &changed_files {
+         message.push_str(&format!("\n- {file}"));
+      }
+      push_capped_files(&mut message, &update.capped_files);
+
+      Ok(CallToolResult::success(vec![Content::text(message)]))
+   }
+
+   #[tool(
+      description = "Re-embed only the given files of an embedded repository, replacing their \
+                     existing chunks"
+   )]
+   async fn update_files(
+      &self,
+      #[tool(aggr)] req: UpdateFilesRequest,
+   ) -> Result<CallToolResult, McpError> {
+      self.ensure_writable("update_files")?;
+      let mut req = req;
+      ensure_repo_embedded(&mut req.repo_url).await?;
+
+      if req.paths.is_empty() {
+         return Err(McpError::invalid_request(
+            "At least one file path must be provided",
+            None,
+         ));
       }
 
-      let query_service = QueryService::new()
-         .context("failed to initialize query service")
-         .map_err(BackendError::Internal)?;
+      let update = update_github_repo_files(&req.repo_url, &req.paths, &self.metrics)
+         .await
+         .context("failed to update files")
+         .map_err(BackendError::from)?;
+
+      let mut message = format!(
+         "Updated {} files in {} ({} chunks embedded)",
+         req.paths.len(),
+         req.repo_url,
+         update.chunk_count
+      );
+      push_capped_files(&mut message, &update.capped_files);
+      Ok(CallToolResult::success(vec![Content::text(message)]))
+   }
+
+   #[tool(
+      description = "Update the labels of an embedded repository without re-embedding it and \
+                     return its updated metadata"
+   )]
+   async fn update_labels(
+      &self,
+      #[tool(aggr)] req: UpdateLabelsRequest,
+   ) -> Result<CallToolResult, McpError> {
+      self.ensure_writable("update_labels")?;
+      let mut req = req;
+      ensure_repo_embedded(&mut req.repo_url).await?;
 
-      let results = query_service
-         .query_embeddings(&req.query, &req.repo_url, req.limit)
+      let data_store = DataStore::new_deferred(&req.repo_url).map_err(BackendError::from)?;
+      let mut metadata = data_store
+         .update_labels(req.labels)
          .await
-         .context("failed to query embeddings")
-         .map_err(BackendError::Internal)?;
+         .context("failed to update labels")
+         .map_err(BackendError::from)?;
 
-      if results.is_empty() {
-         return Err(BackendError::NoQueryResults(req.query.clone()).into());
+      // the centroid is only used internally and would dwarf the rest of the response
+      metadata.centroid = None;
+      let json_output = serde_json::to_string_pretty(&metadata)
+         .context("failed to serialize metadata")
+         .map_err(BackendError::from)?;
+
+      Ok(CallToolResult::success(vec![Content::text(json_output)]))
+   }
+
+   #[tool(
+      description = "Point a repository that was renamed or transferred at its existing \
+                     embeddings, so it can be queried under its new URL without re-embedding, and \
+                     return its updated metadata. The old URL keeps working"
+   )]
+   async fn rename_repo(
+      &self,
+      #[tool(aggr)] req: RenameRepoRequest,
+   ) -> Result<CallToolResult, McpError> {
+      self.ensure_writable("rename_repo")?;
+      let mut req = req;
+      ensure_repo_embedded(&mut req.old_url).await?;
+      if is_repo_alias(&req.new_url) {
+         return Err(McpError::invalid_request(
+            format!(
+               "Invalid new URL: {}. Use a GitHub URL or owner/repo",
+               req.new_url
+            ),
+            None,
+         ));
       }
 
-      let header = format!(
-         "Found {} results for query: {} (from repository: {})",
-         results.len(),
-         req.query,
-         req.repo_url
+      let data_store = DataStore::new_deferred(&req.new_url).map_err(BackendError::from)?;
+      let mut metadata = data_store
+         .adopt_renamed(&req.old_url)
+         .await
+         .context("failed to rename repository")
+         .map_err(BackendError::from)?;
+
+      // the centroid is only used internally and would dwarf the rest of the response
+      metadata.centroid = None;
+      let json_output = serde_json::to_string_pretty(&metadata)
+         .context("failed to serialize metadata")
+         .map_err(BackendError::from)?;
+
+      Ok(CallToolResult::success(vec![Content::text(json_output)]))
+   }
+
+   #[tool(
+      description = "Show how a repository, or a single file of it, is chunked, without embedding \
+                     anything: the chunk count of each file and the kind, line range and first \
+                     line of each chunk. Useful for diagnosing why code is missing from search \
+                     results"
+   )]
+   async fn preview_chunks(
+      &self,
+      #[tool(aggr)] req: PreviewChunksRequest,
+   ) -> Result<CallToolResult, McpError> {
+      let mut req = req;
+      resolve_repo_alias(&mut req.repo_url).await?;
+      let preview_failed = |e: anyhow::Error| {
+         McpError::invalid_request(format!("Failed to preview chunks: {e:#}"), None)
+      };
+
+      let Some(file_path) = &req.file_path else {
+         let repo = process_github_repo(
+            &req.repo_url,
+            &EmbeddingConfig::from_env(),
+            &[],
+            &ChunkPreferences::default(),
+         )
+         .await
+         .map_err(preview_failed)?;
+         return Ok(CallToolResult::success(vec![Content::text(
+            repo_chunk_breakdown(&req.repo_url, &repo.chunks),
+         )]));
+      };
+
+      let preview = preview_file_chunks(&req.repo_url, file_path, &EmbeddingConfig::from_env())
+         .await
+         .map_err(preview_failed)?;
+
+      let Some(language) = preview.language else {
+         return Ok(CallToolResult::success(vec![Content::text(format!(
+            "{} is not in a supported language and is skipped when embedding {}",
+            file_path, req.repo_url
+         ))]));
+      };
+
+      let mut lines = vec![format!(
+         "{} is chunked as {:?} into {} chunks:",
+         file_path,
+         language,
+         preview.chunks.len()
+      )];
+      lines.extend(chunk_preview_lines(&preview.chunks));
+
+      Ok(CallToolResult::success(vec![Content::text(
+         lines.join("\n"),
+      )]))
+   }
+
+   #[tool(
+      description = "Summarize what an embedded repository is about with its most distinctive \
+                     terms and identifiers, ranked by TF-IDF over its stored chunks"
+   )]
+   async fn repo_keywords(
+      &self,
+      #[tool(aggr)] req: EmbedRequest,
+   ) -> Result<CallToolResult, McpError> {
+      const MAX_KEYWORDS: usize = 30;
+
+      let mut req = req;
+      ensure_repo_embedded(&mut req.repo_url).await?;
+
+      let data_store = DataStore::new(&req.repo_url)
+         .await
+         .map_err(BackendError::from)?;
+      let chunks = data_store
+         .scroll_chunks(false)
+         .await
+         .context("failed to read chunks from collection")
+         .map_err(BackendError::from)?;
+
+      let terms = top_terms(
+         chunks.iter().map(|chunk| chunk.chunk.content.as_str()),
+         MAX_KEYWORDS,
       );
+      if terms.is_empty() {
+         return Ok(CallToolResult::success(vec![Content::text(format!(
+            "No keywords found in {}",
+            req.repo_url
+         ))]));
+      }
 
-      let mut contents = vec![Content::text(header)];
+      let mut lines = vec![format!(
+         "Top keywords of {} ({} chunks):",
+         req.repo_url,
+         chunks.len()
+      )];
+      for (i, (term, score)) in terms.iter().enumerate() {
+         lines.push(format!("{}. {} (score: {:.2})", i + 1, term, score));
+      }
 
-      for (i, (score, content)) in results.iter().enumerate() {
-         contents.push(Content::text(format!(
-            "\n--- Result {} (score: {:.4}) ---\n{}",
+      Ok(CallToolResult::success(vec![Content::text(
+         lines.join("\n"),
+      )]))
+   }
+
+   #[tool(
+      description = "Find embedded repositories with content similar to a given repository, \
+                     ranked by the similarity of their average embeddings"
+   )]
+   async fn similar_repos(
+      &self,
+      #[tool(aggr)] req: EmbedRequest,
+   ) -> Result<CallToolResult, McpError> {
+      const MAX_SIMILAR_REPOS: usize = 10;
+
+      let mut req = req;
+      ensure_repo_embedded(&mut req.repo_url).await?;
+
+      let qdrant_client = connect_qdrant()?;
+      let target_collection = gen_table_name_for_repo(&req.repo_url).map_err(|e| {
+         McpError::invalid_request(format!("Failed to generate table name: {e}"), None)
+      })?;
+
+      let mut target = None;
+      let mut candidates = Vec::new();
+      for (collection_name, repo_name) in list_repo_collections(&qdrant_client).await? {
+         let repo_url = format!("https://github.com/{repo_name}");
+         let Some(centroid) = DataStore::get_metadata(&qdrant_client, &repo_url)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|metadata| metadata.centroid)
+         else {
+            continue;
+         };
+
+         if collection_name == target_collection {
+            target = Some(centroid);
+         } else {
+            candidates.push((repo_name, centroid));
+         }
+      }
+
+      let Some(target) = target else {
+         return Err(McpError::invalid_request(
+            format!(
+               "Repository {} has no centroid recorded; re-embed it to enable similarity search",
+               req.repo_url
+            ),
+            None,
+         ));
+      };
+
+      let mut ranked = rank_by_similarity(&target, candidates);
+      ranked.truncate(MAX_SIMILAR_REPOS);
+
+      if ranked.is_empty() {
+         return Ok(CallToolResult::success(vec![Content::text(format!(
+            "No other embedded repositories to compare with {}",
+            req.repo_url
+         ))]));
+      }
+
+      let mut lines = vec![format!("Repositories most similar to {}:", req.repo_url)];
+      for (i, (repo_name, score)) in ranked.iter().enumerate() {
+         lines.push(format!(
+            "{}. {} (similarity: {:.4})",
             i + 1,
-            score,
-            content
-         )));
+            repo_name,
+            score
+         ));
       }
 
-      Ok(CallToolResult::success(contents))
+      Ok(CallToolResult::success(vec![Content::text(
+         lines.join("\n"),
+      )]))
    }
 
    #[tool(description = "Check the status of an embedding operation")]
@@ -309,16 +1704,12 @@ impl Backend {
 
       match op_data {
          Some(op) => {
-            let status_text = match &op.status {
-               EmbedStatus::InProgress => "in_progress",
-               EmbedStatus::Completed => "completed",
-               EmbedStatus::Failed => "failed",
-            };
-
-            Ok(CallToolResult::success(vec![Content::text(format!(
-               "Embed operation {} for {}: {} - {}",
-               req.operation_id, op.repo_url, status_text, op.message
-            ))]))
+            let report = op
+               .status_report(&req.operation_id)
+               .context("failed to serialize embed result")
+               .map_err(BackendError::from)?;
+
+            Ok(CallToolResult::success(vec![Content::text(report)]))
          }
          None => Err(BackendError::OperationNotFound(req.operation_id.clone()).into()),
       }
@@ -329,42 +1720,21 @@ impl Backend {
       #[derive(Serialize)]
       struct RepoInfo {
          repo_name: String,
+         #[serde(skip_serializing_if = "Option::is_none")]
+         alias: Option<String>,
          embedded_at: Option<String>,
          doc_count: Option<usize>,
+         #[serde(skip_serializing_if = "Option::is_none")]
+         primary_language: Option<String>,
+         #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+         labels: BTreeMap<String, String>,
       }
 
       let mut repo_info: Vec<RepoInfo> = Vec::new();
 
-      let qdrant_url = dotenvy::var("QDRANT_URL")
-         .context("QDRANT_URL environment variable not set")
-         .map_err(BackendError::Internal)?;
-      let qdrant_api_key = dotenvy::var("QDRANT_API_KEY").ok();
-
-      let qdrant_client = qdrant_client::Qdrant::from_url(&qdrant_url)
-         .api_key(qdrant_api_key)
-         .build()
-         .context("failed to create Qdrant client")
-         .map_err(BackendError::Internal)?;
-
-      // list all collections from qdrant
-      let collections = qdrant_client
-         .list_collections()
-         .await
-         .context("failed to list collections from Qdrant")
-         .map_err(BackendError::Internal)?;
-
-      for collection in collections.collections {
-         let name = collection.name;
-
-         // parse collection name to extract repo name
-         // format is: {owner}__{repo}
-         let repo_name = parse_collection_name_to_repo(&name);
-
-         // Skip collections that don't look like repo names (don't contain /)
-         if !repo_name.contains('/') {
-            continue;
-         }
+      let qdrant_client = connect_qdrant()?;
 
+      for (name, repo_name) in list_repo_collections(&qdrant_client).await? {
          // Try to get metadata for this collection
          let repo_url = format!("https://github.com/{}", repo_name);
          tracing::debug!(
@@ -386,10 +1756,16 @@ impl Backend {
             continue;
          };
 
+         // a renamed repository keeps its collection but is listed under its new URL
+         let repo_name = gen_table_name_for_repo(&meta.repo_url)
+            .map_or(repo_name, |name| parse_collection_name_to_repo(&name));
          let info = RepoInfo {
             repo_name,
+            alias: meta.alias,
             embedded_at: Some(meta.embedded_at.to_rfc3339()),
             doc_count: Some(meta.doc_count),
+            primary_language: meta.primary_language,
+            labels: meta.labels,
          };
 
          repo_info.push(info);
@@ -400,25 +1776,186 @@ impl Backend {
 
       let json_output = serde_json::to_string_pretty(&repo_info)
          .context("failed to serialize repo info")
-         .map_err(BackendError::Internal)?;
+         .map_err(BackendError::from)?;
+
+      Ok(CallToolResult::success(vec![Content::text(json_output)]))
+   }
+
+   #[tool(
+      description = "List the embedding providers the server can embed with, their models and the \
+                     vector dimension of each model"
+   )]
+   async fn list_providers(&self) -> Result<CallToolResult, McpError> {
+      let json_output = serde_json::to_string_pretty(&available_providers())
+         .context("failed to serialize providers")
+         .map_err(BackendError::from)?;
 
       Ok(CallToolResult::success(vec![Content::text(json_output)]))
    }
 }
 
+/// Builds a Qdrant client from the `QDRANT_URL` and `QDRANT_API_KEY` environment variables
+fn connect_qdrant() -> Result<qdrant_client::Qdrant, BackendError> {
+   let qdrant_url =
+      dotenvy::var("QDRANT_URL").context("QDRANT_URL environment variable not set")?;
+   let qdrant_api_key = dotenvy::var("QDRANT_API_KEY").ok();
+
+   let qdrant_client = qdrant_client::Qdrant::from_url(&qdrant_url)
+      .api_key(qdrant_api_key)
+      .build()
+      .context("failed to create Qdrant client")?;
+
+   Ok(qdrant_client)
+}
+
+/// Lists the Qdrant collections that hold repository embeddings as `(collection_name,
+/// owner/repo)` pairs
+async fn list_repo_collections(
+   qdrant_client: &qdrant_client::Qdrant,
+) -> Result<Vec<(String, String)>, BackendError> {
+   // list all collections from qdrant
+   let collections = qdrant_client
+      .list_collections()
+      .await
+      .context("failed to list collections from Qdrant")?;
+
+   let repos = collections
+      .collections
+      .into_iter()
+      .filter_map(|collection| {
+         // parse collection name to extract repo name
+         // format is: {owner}__{repo}
+         let repo_name = parse_collection_name_to_repo(&collection.name);
+
+         // Skip collections that don't look like repo names (don't contain /)
+         repo_name
+            .contains('/')
+            .then_some((collection.name, repo_name))
+      })
+      .collect();
+
+   Ok(repos)
+}
+
+/// Replaces a repository alias with the URL of the repository it was given to, leaving URLs
+/// untouched
+async fn resolve_repo_alias(repo: &mut String) -> Result<(), McpError> {
+   if !is_repo_alias(repo) {
+      return Ok(());
+   }
+   let qdrant_client = connect_qdrant()?;
+   *repo = lookup_alias(repo, |alias| {
+      DataStore::resolve_alias(&qdrant_client, alias)
+   })
+   .await?;
+   Ok(())
+}
+
+/// Resolves `alias` with `lookup`, reporting unknown aliases as invalid requests
+async fn lookup_alias<'a, F, Fut>(alias: &'a str, lookup: F) -> Result<String, McpError>
+where
+   F: FnOnce(&'a str) -> Fut,
+   Fut: Future<Output = anyhow::Result<Option<String>>>,
+{
+   match lookup(alias)
+      .await
+      .context("failed to look up repository alias")
+      .map_err(BackendError::from)?
+   {
+      Some(repo_url) => Ok(repo_url),
+      None => Err(McpError::invalid_request(
+         format!("Unknown repository alias: {alias}. Embed a repository with it first"),
+         None,
+      )),
+   }
+}
+
+/// Rejects an alias already given to another repository, since an alias names a single
+/// repository. Skipped when Qdrant cannot be reached, like the other pre-embed checks.
+async fn ensure_alias_available(alias: &str, repo_url: &str) -> Result<(), McpError> {
+   if !is_repo_alias(alias) {
+      return Err(McpError::invalid_request(
+         format!(
+            "Invalid alias: {alias}. Use letters, digits, '-', '_' and '.', without '__' or '/'"
+         ),
+         None,
+      ));
+   }
+   if let Ok(qdrant_client) = connect_qdrant()
+      && let Ok(Some(current)) = DataStore::resolve_alias(&qdrant_client, alias).await
+      && current != repo_url
+   {
+      return Err(McpError::invalid_request(
+         format!("Alias {alias} is already used by {current}"),
+         None,
+      ));
+   }
+   Ok(())
+}
+
+/// Rejects queries against repositories that have no embeddings, or when Qdrant cannot be
+/// reached. Other failures skip the check and let the query proceed. An alias is replaced
+/// with the URL of its repository first.
+async fn ensure_repo_embedded(repo_url: &mut String) -> Result<(), McpError> {
+   resolve_repo_alias(repo_url).await?;
+   let repo_url = repo_url.as_str();
+   let table_name = gen_table_name_for_repo(repo_url).map_err(|e| {
+      McpError::invalid_request(format!("Failed to generate table name: {e}"), None)
+   })?;
+   if let Ok(qdrant_url) = dotenvy::var("QDRANT_URL")
+      && let Ok(qdrant_client) = qdrant_client::Qdrant::from_url(&qdrant_url)
+         .api_key(dotenvy::var("QDRANT_API_KEY").ok())
+         .build()
+   {
+      match qdrant_client.collection_exists(&table_name).await {
+         Ok(false) => {
+            return Err(McpError::invalid_request(
+               format!("No embeddings found for repository: {repo_url}"),
+               None,
+            ));
+         }
+         // an unreachable Qdrant is reported as retryable rather than failing later on
+         Err(e) => {
+            if let err @ BackendError::VectorStoreUnavailable(_) =
+               BackendError::from(anyhow::Error::new(e))
+            {
+               return Err(err.into());
+            }
+         }
+         Ok(true) => {}
+      }
+   }
+
+   Ok(())
+}
+
 #[tool(tool_box)]
 impl ServerHandler for Backend {
    fn get_info(&self) -> ServerInfo {
+      let mut capabilities = ServerCapabilities::builder()
+         .enable_prompts()
+         .enable_tools()
+         .build();
+      let mut instructions =
+         "MCP server for Git repository documentation embedding and search".to_string();
+      if self.read_only {
+         capabilities.experimental = Some(BTreeMap::from([(
+            "readOnly".to_string(),
+            JsonObject::new(),
+         )]));
+         instructions.push_str(
+            ". The server is read-only: embedding, importing and updating repositories is disabled",
+         );
+      }
+
       ServerInfo {
          protocol_version: ProtocolVersion::V_2024_11_05,
-         capabilities: ServerCapabilities::builder().enable_tools().build(),
+         capabilities,
          server_info: Implementation {
             name: "mcp-rust-docs-embed".to_string(),
             version: "0.1.0".to_string(),
          },
-         instructions: Some(
-            "MCP server for Git repository documentation embedding and search".to_string(),
-         ),
+         instructions: Some(instructions),
       }
    }
 
@@ -429,4 +1966,435 @@ impl ServerHandler for Backend {
    ) -> Result<InitializeResult, McpError> {
       Ok(self.get_info())
    }
+
+   async fn list_prompts(
+      &self,
+      _request: PaginatedRequestParam,
+      _context: RequestContext<RoleServer>,
+   ) -> Result<ListPromptsResult, McpError> {
+      Ok(ListPromptsResult {
+         next_cursor: None,
+         prompts: prompts::list_prompts(),
+      })
+   }
+
+   async fn get_prompt(
+      &self,
+      request: GetPromptRequestParam,
+      _context: RequestContext<RoleServer>,
+   ) -> Result<GetPromptResult, McpError> {
+      prompts::get_prompt(&request.name, request.arguments.as_ref())
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::mock_qdrant::MockQdrant;
+
+   #[test]
+   fn test_completed_status_reports_structured_result() -> Result<()> {
+      let op = EmbedOperation {
+         status: EmbedStatus::Completed,
+         repo_url: "https://github.com/owner/repo".to_string(),
+         message: "Successfully processed and embedded repository".to_string(),
+         result: Some(EmbedResult {
+            collection_name: "owner__repo".to_string(),
+            chunk_count: 42,
+            file_count: 7,
+            commit: Some("0123abc".to_string()),
+            duration_secs: 1.5,
+            missing_symbols: vec![],
+            capped_files: vec![],
+            failed_ranges: vec![],
+         }),
+      };
+
+      let report = op.status_report("embed_owner_repo_1")?;
+      let (summary, json) = report.split_once('\n').expect("report has a JSON result");
+      assert!(summary.contains("completed"));
+
+      let result: serde_json::Value = serde_json::from_str(json)?;
+      assert_eq!(result["chunk_count"], 42);
+      assert_eq!(result["collection_name"], "owner__repo");
+      Ok(())
+   }
+
+   #[test]
+   fn test_zero_limit_uses_default() -> Result<()> {
+      let query = |limit: serde_json::Value| -> Result<u64> {
+         let mut request = serde_json::json!({
+            "query": "spawn a task",
+            "repo_url": "tokio-rs/tokio",
+         });
+         if !limit.is_null() {
+            request["limit"] = limit;
+         }
+         Ok(serde_json::from_value::<QueryRequest>(request)?.limit)
+      };
+
+      assert_eq!(
+         query(serde_json::json!(0))?,
+         query(serde_json::Value::Null)?
+      );
+      assert_eq!(query(serde_json::json!(0))?, default_limit());
+      assert_eq!(query(serde_json::json!(3))?, 3);
+      Ok(())
+   }
+
+   #[test]
+   fn test_notebook_citations_name_the_cell() {
+      let repo_url = "https://github.com/owner/repo";
+      let chunk = ChunkPayload {
+         content: "df.describe()".to_string(),
+         file_path: Some("notebooks/analysis.ipynb".to_string()),
+         start_line: Some(1),
+         end_line: Some(2),
+         cell: Some(3),
+         ..Default::default()
+      };
+
+      assert_eq!(
+         cite_chunk(repo_url, Some("0123abc"), &chunk),
+         "Source: notebooks/analysis.ipynb (cell 3, lines 1-2)\nPermalink: \
+          https://github.com/owner/repo/blob/0123abc/notebooks/analysis.ipynb"
+      );
+
+      let file_chunk = ChunkPayload {
+         file_path: Some("src/lib.rs".to_string()),
+         cell: None,
+         ..chunk
+      };
+      assert!(
+         cite_chunk(repo_url, Some("0123abc"), &file_chunk)
+            .ends_with("/blob/0123abc/src/lib.rs#L1-L2")
+      );
+      // without the embedded commit a link would drift with the default branch
+      assert_eq!(
+         cite_chunk(repo_url, None, &file_chunk),
+         "Source: src/lib.rs (lines 1-2)"
+      );
+   }
+
+   #[test]
+   fn test_raw_content_keeps_prose_chunks() {
+      let comment = ChunkPayload {
+         content: "//! Runtime for async tasks\n//! Spawns work on a thread pool".to_string(),
+         kind: Some(ChunkKind::Comment),
+         ..Default::default()
+      };
+      assert_eq!(raw_content(&comment), comment.content);
+
+      let function = ChunkPayload {
+         content: "/// Spawns a task\npub fn spawn() {}".to_string(),
+         kind: Some(ChunkKind::Function),
+         ..Default::default()
+      };
+      assert_eq!(raw_content(&function), "pub fn spawn() {}");
+   }
+
+   #[tokio::test]
+   async fn test_query_by_alias_given_at_embed_time() -> Result<()> {
+      let embed: EmbedRequest = serde_json::from_value(serde_json::json!({
+         "repo_url": "tokio-rs/tokio",
+         "alias": "tokio",
+      }))?;
+      assert_eq!(embed.repo_url, "https://github.com/tokio-rs/tokio");
+
+      // the aliases and metadata Qdrant keeps once the embed has finished
+      let qdrant = MockQdrant::start().await?;
+      let config = EmbeddingConfig::default();
+      let data_store = DataStore::with_client(qdrant.client(), &embed.repo_url, &config)?;
+      let chunk = ChunkPayload {
+         content: "pub fn spawn() {}".to_string(),
+         ..Default::default()
+      };
+      data_store
+         .add_embeddings_batch(vec![(chunk, vec![1.0, 0.0])])
+         .await?;
+      data_store
+         .store_metadata(1, None, None, None, BTreeMap::new(), &config)
+         .await?;
+      data_store
+         .set_alias(embed.alias.as_deref().unwrap())
+         .await?;
+      let client = qdrant.client();
+      let lookup = |alias| DataStore::resolve_alias(&client, alias);
+
+      let query: QueryRequest = serde_json::from_value(serde_json::json!({
+         "query": "spawn a task",
+         "repo_url": "tokio",
+      }))?;
+      assert_eq!(query.repo_url, "tokio");
+      assert_eq!(lookup_alias(&query.repo_url, lookup).await?, embed.repo_url);
+
+      let unknown = lookup_alias("hyper", lookup).await.unwrap_err();
+      assert!(unknown.message.contains("Unknown repository alias: hyper"));
+      Ok(())
+   }
+
+   #[tokio::test]
+   async fn test_read_only_mode_rejects_mutating_tools() -> Result<()> {
+      let backend = Backend {
+         read_only: true,
+         ..Default::default()
+      };
+      let req: UpdateLabelsRequest = serde_json::from_value(serde_json::json!({
+         "repo_url": "owner/repo",
+         "labels": {"team": "b"},
+      }))?;
+
+      let err = backend.update_labels(req).await.unwrap_err();
+      assert!(
+         err.message
+            .contains("Server is read-only: update_labels is disabled")
+      );
+      assert!(
+         backend
+            .get_info()
+            .capabilities
+            .experimental
+            .is_some_and(|experimental| experimental.contains_key("readOnly"))
+      );
+      assert!(
+         Backend::default()
+            .get_info()
+            .capabilities
+            .experimental
+            .is_none()
+      );
+      Ok(())
+   }
+
+   #[test]
+   fn test_prompts_capability_is_advertised() {
+      let info = Backend::default().get_info();
+
+      assert!(info.capabilities.prompts.is_some());
+      assert!(info.capabilities.tools.is_some());
+   }
+
+   #[tokio::test]
+   async fn test_repo_preview_matches_extractor_output() -> Result<()> {
+      let root = tempfile::TempDir::new()?;
+      let lib = "/// Adds two numbers\npub fn add(a: u32, b: u32) -> u32 {\n   a + b\n}\n\npub \
+                 struct Point {\n   x: i32,\n}\n";
+      std::fs::create_dir(root.path().join("src"))?;
+      std::fs::write(root.path().join("src/lib.rs"), lib)?;
+      std::fs::write(root.path().join("notes.txt"), "not chunked\n")?;
+      let repo = git2::Repository::init(root.path())?;
+      let mut index = repo.index()?;
+      index.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+      let tree = repo.find_tree(index.write_tree()?)?;
+      let signature = git2::Signature::now("Ada", "ada@example.com")?;
+      repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+
+      let repo_url = url::Url::from_directory_path(root.path()).expect("absolute path");
+      let config = EmbeddingConfig {
+         // the local transport does not support shallow clones
+         clone_depth: 0,
+         ..Default::default()
+      };
+      let processed = process_github_repo(
+         repo_url.as_str(),
+         &config,
+         &[],
+         &ChunkPreferences::default(),
+      )
+      .await?;
+      let breakdown = repo_chunk_breakdown("owner/repo", &processed.chunks);
+
+      let extracted = crate::chunks::rust::extract_rust_chunks(lib)?;
+      let mut expected = vec![
+         format!(
+            "owner/repo is chunked into {} chunks across 1 files:",
+            extracted.len()
+         ),
+         format!("\nsrc/lib.rs: {} chunks", extracted.len()),
+      ];
+      expected.extend(chunk_preview_lines(&extracted));
+      assert_eq!(breakdown, expected.join("\n"));
+      assert!(breakdown.contains("Function lines 1-4: /// Adds two numbers"));
+      Ok(())
+   }
+
+   #[tokio::test]
+   async fn test_concurrent_embeds_of_same_repo_share_one_operation() {
+      let backend = Backend::default();
+      let repo_url = "https://github.com/owner/repo";
+      let spawned = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+      // each simulated request comes from its own session, with its own backend, and spawns a
+      // background embed only if it registered a new operation
+      let request = |operation_id: &'static str| {
+         let backend = Backend {
+            embed_operations: backend.embed_operations.clone(),
+            ..Backend::default()
+         };
+         let spawned = spawned.clone();
+         tokio::spawn(async move {
+            match backend.register_operation(operation_id, repo_url).await {
+               Some(existing_id) => existing_id,
+               None => {
+                  spawned.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                  operation_id.to_string()
+               }
+            }
+         })
+      };
+      let (first, second) = tokio::join!(request("embed_repo_1"), request("embed_repo_2"));
+
+      assert_eq!(spawned.load(std::sync::atomic::Ordering::SeqCst), 1);
+      assert_eq!(first.unwrap(), second.unwrap());
+      assert_eq!(backend.embed_operations.read().await.len(), 1);
+
+      // once the running embed finishes, a new request starts a fresh operation
+      for op in backend.embed_operations.write().await.values_mut() {
+         op.status = EmbedStatus::Completed;
+      }
+      assert_eq!(
+         backend.register_operation("embed_repo_3", repo_url).await,
+         None
+      );
+   }
+
+   fn embed_result(chunk_count: usize) -> EmbedResult {
+      EmbedResult {
+         collection_name: "owner__repo".to_string(),
+         chunk_count,
+         file_count: 1,
+         commit: None,
+         duration_secs: 0.0,
+         missing_symbols: vec![],
+         capped_files: vec![],
+         failed_ranges: vec![],
+      }
+   }
+
+   #[tokio::test]
+   async fn test_concurrent_operations_all_resolve() {
+      let backend = Backend::default();
+
+      let mut handles = Vec::new();
+      for i in 0..8u64 {
+         let operation_id = format!("embed_repo_{i}");
+         let repo_url = format!("https://github.com/owner/repo-{i}");
+         assert_eq!(
+            backend.register_operation(&operation_id, &repo_url).await,
+            None
+         );
+         handles.push(backend.spawn_operation(
+            operation_id,
+            repo_url,
+            backend.cancellation_token.child_token(),
+            async move {
+               // finish out of order, holding the map lock at different times
+               tokio::time::sleep(Duration::from_millis(8 - i)).await;
+               if i % 2 == 0 {
+                  Ok(embed_result(i as usize))
+               } else {
+                  Err(anyhow::anyhow!("clone failed"))
+               }
+            },
+         ));
+      }
+      for handle in handles {
+         handle.await.unwrap();
+      }
+
+      let ops = backend.embed_operations.read().await;
+      assert_eq!(ops.len(), 8);
+      for i in 0..8 {
+         let op = &ops[&format!("embed_repo_{i}")];
+         assert_eq!(op.repo_url, format!("https://github.com/owner/repo-{i}"));
+         if i % 2 == 0 {
+            assert!(matches!(op.status, EmbedStatus::Completed));
+            assert_eq!(op.result.as_ref().map(|result| result.chunk_count), Some(i));
+         } else {
+            assert!(matches!(op.status, EmbedStatus::Failed));
+            assert!(op.message.contains("clone failed"));
+         }
+      }
+   }
+
+   #[tokio::test]
+   async fn test_operation_with_timed_out_batches_is_partially_completed() {
+      let backend = Backend::default();
+      let repo_url = "https://github.com/owner/repo";
+      backend.register_operation("embed_repo", repo_url).await;
+
+      backend
+         .spawn_operation(
+            "embed_repo".to_string(),
+            repo_url.to_string(),
+            backend.cancellation_token.child_token(),
+            async {
+               Ok(EmbedResult {
+                  failed_ranges: vec!["src/lib.rs:1-40".to_string(), "src/io.rs:10-90".to_string()],
+                  ..embed_result(12)
+               })
+            },
+         )
+         .await
+         .unwrap();
+
+      let ops = backend.embed_operations.read().await;
+      let op = &ops["embed_repo"];
+      assert!(matches!(op.status, EmbedStatus::PartiallyCompleted));
+      assert!(op.message.contains("Embedded 12 chunks"));
+      assert!(op.message.contains("2 ranges timed out"));
+      let result = op.result.as_ref().unwrap();
+      assert_eq!(result.chunk_count, 12);
+      assert_eq!(result.failed_ranges.len(), 2);
+   }
+
+   #[tokio::test]
+   async fn test_cancelling_one_operation_leaves_others_running() {
+      let backend = Backend::default();
+      let repo_url = |operation_id: &str| format!("https://github.com/owner/{operation_id}");
+      for operation_id in ["cancelled", "finished", "stuck"] {
+         backend
+            .register_operation(operation_id, &repo_url(operation_id))
+            .await;
+      }
+      let spawn = |operation_id: &str, token: CancellationToken, finishes: bool| {
+         let embed = async move {
+            if !finishes {
+               std::future::pending::<()>().await;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(embed_result(1))
+         };
+         backend.spawn_operation(
+            operation_id.to_string(),
+            repo_url(operation_id),
+            token,
+            embed,
+         )
+      };
+
+      let cancelled_token = backend.cancellation_token.child_token();
+      let cancelled = spawn("cancelled", cancelled_token.clone(), false);
+      let finished = spawn("finished", backend.cancellation_token.child_token(), true);
+      let stuck = spawn("stuck", backend.cancellation_token.child_token(), false);
+
+      cancelled_token.cancel();
+      cancelled.await.unwrap();
+      finished.await.unwrap();
+      {
+         let ops = backend.embed_operations.read().await;
+         assert!(matches!(ops["cancelled"].status, EmbedStatus::Failed));
+         assert!(ops["cancelled"].message.contains("Operation cancelled"));
+         assert!(matches!(ops["finished"].status, EmbedStatus::Completed));
+         assert!(matches!(ops["stuck"].status, EmbedStatus::InProgress));
+      }
+
+      // shutting the server down cancels what is still running
+      backend.cancellation_token.cancel();
+      stuck.await.unwrap();
+      assert!(matches!(
+         backend.embed_operations.read().await["stuck"].status,
+         EmbedStatus::Failed
+      ));
+   }
 }
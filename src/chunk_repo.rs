@@ -1,20 +1,41 @@
-use crate::chunks::{self, Chunk};
-use anyhow::{Result, bail};
-use std::collections::HashMap;
+use crate::{
+   chunks::{
+      Chunk, ChunkKind,
+      detect::{Language, LanguageCounts},
+      text,
+   },
+   config::{ChunkPreferences, EmbeddingConfig, MinifiedPolicy},
+   git_history::{GIT_HISTORY_PATH, commit_chunks, file_modification_times},
+};
+use anyhow::{Context, Result, bail};
+use std::{
+   collections::{HashMap, HashSet},
+   path::{Component, Path, PathBuf},
+   time::{Duration, Instant},
+};
 use tempfile::TempDir;
-use tracing::info;
+use tracing::{info, warn};
 use url::Url;
-use walkdir::WalkDir;
+use walkdir::{DirEntry, WalkDir};
 
-/// Processes a GitHub repository by cloning it and extracting semantic chunks from all Rust and
-/// Markdown files.
+/// Processes a GitHub repository by cloning it and extracting semantic chunks from all Rust,
+/// TypeScript and Markdown files.
 ///
 /// # Arguments
 /// * `repo_url` - The GitHub repository URL (e.g., "https://github.com/owner/repo") or shorthand
 ///   format ("owner/repo")
+/// * `config` - When `detect_languages` is set, files with other extensions are chunked too if
+///   their language can be detected from the file name or shebang
+/// * `symbols` - When not empty, only chunks declaring one of these symbols are kept
+/// * `preferences` - Chunking preferences of the request, applied over those the repository sets in
+///   its `.embed-config.toml`, which are applied over `config`
+///
+/// With `embed_commits`, the repository is cloned with its full history and its commit messages
+/// are added as chunks under the `<git history>` path. With `file_recency`, the full history
+/// is cloned as well to date the last change of every chunked file.
 ///
 /// # Returns
-/// A `HashMap` where:
+/// A `ProcessedRepo` whose `chunks` map has:
 /// - Keys are relative file paths within the repository (e.g., "src/main.rs", "docs/README.md")
 /// - Values are vectors of `Chunk` structs containing semantic code segments from each file
 ///
@@ -22,48 +43,85 @@ use walkdir::WalkDir;
 ///
 /// # Example
 /// ```
-/// let chunks = process_github_repo("rust-lang/rust").await?;
-/// // chunks["src/main.rs"] contains all extracted chunks from that file
+/// let repo = process_github_repo(
+///    "rust-lang/rust",
+///    &EmbeddingConfig::default(),
+///    &[],
+///    &ChunkPreferences::default(),
+/// )
+/// .await?;
+/// // repo.chunks["src/main.rs"] contains all extracted chunks from that file
 /// ```
-pub async fn process_github_repo(repo_url: &str) -> Result<HashMap<String, Vec<Chunk>>> {
-   // Clone repository in blocking context
-   let temp_dir = tokio::task::spawn_blocking({
-      let repo_url = repo_url.to_string();
-      move || clone_repo(&repo_url)
-   })
-   .await??;
+pub async fn process_github_repo(
+   repo_url: &str,
+   config: &EmbeddingConfig,
+   symbols: &[String],
+   preferences: &ChunkPreferences,
+) -> Result<ProcessedRepo> {
+   let mut config = config.clone();
+   // commit messages and file dates need the history that a shallow clone leaves out
+   if config.embed_commits || config.file_recency {
+      config.clone_depth = 0;
+   }
+   let temp_dir = clone_repo_with_timeout(repo_url, &config).await?;
+
+   ChunkPreferences::from_repo(temp_dir.path()).apply(&mut config);
+   preferences.apply(&mut config);
+
+   // the primary language decides which further directories only hold build output
+   let repo_files = walk_repo_files(temp_dir.path(), &config.excluded_dirs);
+   let mut language_counts = LanguageCounts::default();
+   for file_path in &repo_files {
+      language_counts.record(file_path);
+   }
+   let primary_language = language_counts.primary();
+   info!(
+      "Detected primary language of {repo_url}: {}",
+      primary_language.unwrap_or("unknown")
+   );
+   if let Some(language) = primary_language {
+      config.exclude_language_dirs(language);
+   }
+
+   let config = &config;
+   let exclude_patterns = compile_globs(&config.exclude_globs);
 
    let mut file_chunks_map = HashMap::new();
+   let mut capped_files = Vec::new();
 
-   // Walk through all Rust and Markdown files
-   for entry in WalkDir::new(temp_dir.path())
-      .into_iter()
-      .filter_map(Result::ok)
-      .filter(|e| {
-         e.file_type().is_file()
-            && e
-               .path()
-               .extension()
-               .and_then(|s| s.to_str())
-               .map(|ext| ext == "rs" || ext == "md" || ext == "ts")
-               .unwrap_or(false)
-      })
-   {
-      let file_path = entry.path();
-      let relative_path = file_path
-         .strip_prefix(temp_dir.path())
-         .unwrap_or(file_path)
-         .to_string_lossy()
-         .to_string();
-
-      if let Ok(source) = std::fs::read_to_string(file_path) {
+   // Walk through all files, chunking those in a supported language
+   for file_path in repo_files {
+      let file_path = file_path.as_path();
+      let relative = file_path.strip_prefix(temp_dir.path()).unwrap_or(file_path);
+      if is_in_excluded_dir(relative, &config.excluded_dirs) {
+         continue;
+      }
+
+      let extension_language = Language::from_extension(file_path);
+      let explicitly_included = is_explicitly_included(file_path, config);
+      if extension_language.is_none() && !config.detect_languages && !explicitly_included {
+         continue;
+      }
+
+      if !is_selected(relative, config, &exclude_patterns) {
+         continue;
+      }
+      let relative_path = relative.to_string_lossy().to_string();
+
+      // only files selected by extension are transcoded, detection needs UTF-8 to be reliable
+      let transcode = extension_language.is_some() || explicitly_included;
+      if let Ok(source) = read_source(file_path, transcode) {
          // Extract chunks based on file type
-         let chunks = match file_path.extension().and_then(|s| s.to_str()) {
-            Some("rs") => chunks::rust::extract_rust_chunks(&source)?,
-            Some("md") => chunks::markdown::extract_markdown_chunks(&source)?,
-            Some("ts") => chunks::typescript::extract_typescript_chunks(&source)?,
-            _ => continue,
+         let mut chunks = match extension_language.or_else(|| Language::detect(file_path, &source))
+         {
+            Some(language) => chunk_source(language, &source, config)?,
+            // files listed by extension are embedded even without a dedicated chunker
+            None if explicitly_included => chunk_plain_text(&source, config)?,
+            None => continue,
          };
+         if cap_chunks(&relative_path, &mut chunks, config) {
+            capped_files.push(relative_path.clone());
+         }
 
          if !chunks.is_empty() {
             file_chunks_map.insert(relative_path, chunks);
@@ -71,30 +129,906 @@ pub async fn process_github_repo(repo_url: &str) -> Result<HashMap<String, Vec<C
       }
    }
 
-   Ok(file_chunks_map)
+   let modified_at = if config.file_recency {
+      let file_paths: Vec<&str> = file_chunks_map.keys().map(String::as_str).collect();
+      file_modification_times(temp_dir.path(), &file_paths)
+         .context("Failed to read file modification times")?
+   } else {
+      HashMap::new()
+   };
+
+   if config.embed_commits {
+      let commits = commit_chunks(temp_dir.path(), config.max_commits, config.commit_diffs)
+         .context("Failed to read commit history")?;
+      info!(
+         "Extracted {} commit messages from {repo_url}",
+         commits.len()
+      );
+      file_chunks_map.insert(GIT_HISTORY_PATH.to_string(), commits);
+   }
+
+   let missing_symbols = if symbols.is_empty() {
+      Vec::new()
+   } else {
+      retain_symbols(&mut file_chunks_map, symbols)
+   };
+   if !missing_symbols.is_empty() {
+      warn!(
+         "Requested symbols not found in {repo_url}: {}",
+         missing_symbols.join(", ")
+      );
+   }
+
+   Ok(ProcessedRepo {
+      chunks: file_chunks_map,
+      primary_language,
+      commit: head_commit(temp_dir.path()),
+      modified_at,
+      missing_symbols,
+      capped_files,
+   })
+}
+
+/// Keeps only chunks declaring one of the given symbols, dropping files left without chunks.
+/// Returns the requested symbols that no chunk declares.
+fn retain_symbols(chunks_map: &mut HashMap<String, Vec<Chunk>>, symbols: &[String]) -> Vec<String> {
+   let requested: HashSet<&str> = symbols.iter().map(String::as_str).collect();
+   let mut found = HashSet::new();
+
+   chunks_map.retain(|_, chunks| {
+      chunks.retain(|chunk| {
+         let Some(name) = chunk.name.as_deref() else {
+            return false;
+         };
+         let keep = requested.contains(name);
+         if keep {
+            found.insert(name.to_string());
+         }
+         keep
+      });
+      !chunks.is_empty()
+   });
+
+   let mut missing: Vec<String> = requested
+      .into_iter()
+      .filter(|symbol| !found.contains(*symbol))
+      .map(str::to_string)
+      .collect();
+   missing.sort();
+   missing
+}
+
+/// Chunks a file's source with the chunker of its language, see `finish_chunks`. Files that
+/// look minified are chunked according to `minified_policy` instead.
+fn chunk_source(language: Language, source: &str, config: &EmbeddingConfig) -> Result<Vec<Chunk>> {
+   // prose often puts a whole paragraph on each line and notebooks keep outputs such as images
+   // on long lines, without either being minified
+   let may_be_minified = !matches!(
+      language,
+      Language::Markdown | Language::Changelog | Language::Notebook
+   );
+   if may_be_minified && is_minified(source, config.minified_line_chars) {
+      match config.minified_policy {
+         MinifiedPolicy::Keep => {}
+         MinifiedPolicy::Split => return chunk_plain_text(source, config),
+         MinifiedPolicy::Skip => return Ok(Vec::new()),
+      }
+   }
+
+   finish_chunks(
+      language.chunk_with_text_range(source, config.text_chunk_chars.clone())?,
+      config,
+   )
 }
 
-fn clone_repo(repo: &str) -> Result<TempDir> {
-   let repo_url = parse_repo_url(repo)?;
+/// Splits a file without a dedicated chunker as plain text, see `finish_chunks`. Minified
+/// files are split the same way unless `minified_policy` skips them.
+fn chunk_plain_text(source: &str, config: &EmbeddingConfig) -> Result<Vec<Chunk>> {
+   if config.minified_policy == MinifiedPolicy::Skip
+      && is_minified(source, config.minified_line_chars)
+   {
+      return Ok(Vec::new());
+   }
 
-   let mut builder = git2::build::RepoBuilder::new();
+   finish_chunks(
+      text::extract_text_chunks_in_range(source, config.text_chunk_chars.clone())?,
+      config,
+   )
+}
 
-   let mut fetch_options = git2::FetchOptions::new();
-   fetch_options.depth(1);
+/// Whether source looks minified or generated, its non-blank lines averaging more than
+/// `max_line_chars` characters where hand-written code rarely exceeds a hundred
+fn is_minified(source: &str, max_line_chars: usize) -> bool {
+   let (lines, chars) = source
+      .lines()
+      .filter(|line| !line.trim().is_empty())
+      .fold((0, 0), |(lines, chars), line| {
+         (lines + 1, chars + line.chars().count())
+      });
 
-   builder.fetch_options(fetch_options);
+   lines > 0 && chars / lines > max_line_chars
+}
+
+/// Keeps only the first `max_chunks_per_file` chunks of a file, logging a warning when any are
+/// dropped. A limit of 0 keeps every chunk rather than dropping the file. Returns whether the
+/// file was capped.
+fn cap_chunks(file_path: &str, chunks: &mut Vec<Chunk>, config: &EmbeddingConfig) -> bool {
+   match config.max_chunks_per_file {
+      Some(max_chunks) if max_chunks > 0 && chunks.len() > max_chunks => {
+         warn!(
+            "{file_path} produced {} chunks, keeping only the first {max_chunks}",
+            chunks.len()
+         );
+         chunks.truncate(max_chunks);
+         true
+      }
+      _ => false,
+   }
+}
+
+/// Whether the file's extension is listed in the configured extensions, in which case it is
+/// embedded even when no chunker handles it
+fn is_explicitly_included(path: &Path, config: &EmbeddingConfig) -> bool {
+   let extension = path.extension().and_then(|extension| extension.to_str());
+   config.extensions.as_ref().is_some_and(|extensions| {
+      extension.is_some_and(|extension| extensions.iter().any(|listed| listed == extension))
+   })
+}
+
+/// Applies the configured transformer to extracted chunks, leaving out chunks the transformer
+/// emptied and test chunks when tests are excluded
+fn finish_chunks(mut chunks: Vec<Chunk>, config: &EmbeddingConfig) -> Result<Vec<Chunk>> {
+   for chunk in &mut chunks {
+      config.chunk_transformer.transform(chunk);
+   }
+   chunks.retain(|chunk| !chunk.content.trim().is_empty());
+   if config.exclude_tests {
+      chunks.retain(|chunk| chunk.kind != ChunkKind::Test);
+   }
+   if config.min_chunk_chars > 0 {
+      chunks.retain(|chunk| chunk.content.trim().chars().count() >= config.min_chunk_chars);
+   }
+   Ok(chunks)
+}
+
+/// Compiles exclude globs, skipping any that do not parse
+fn compile_globs(globs: &[String]) -> Vec<glob::Pattern> {
+   globs
+      .iter()
+      .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+      .collect()
+}
+
+/// Whether a file, given relative to the repository root, passes the configured extension
+/// filter and matches none of the exclude globs
+fn is_selected(
+   relative_path: &Path,
+   config: &EmbeddingConfig,
+   exclude_patterns: &[glob::Pattern],
+) -> bool {
+   let extension_allowed = config.extensions.as_ref().is_none_or(|extensions| {
+      relative_path
+         .extension()
+         .and_then(|extension| extension.to_str())
+         .is_some_and(|extension| extensions.iter().any(|allowed| allowed == extension))
+   });
+
+   extension_allowed
+      && !exclude_patterns
+         .iter()
+         .any(|pattern| pattern.matches_path(relative_path))
+}
+
+/// Reads the commit checked out in a cloned repository
+fn head_commit(repo_path: &Path) -> Option<String> {
+   let repo = git2::Repository::open(repo_path).ok()?;
+   let commit = repo.head().ok()?.peel_to_commit().ok()?;
+   Some(commit.id().to_string())
+}
+
+/// Chunks extracted from a repository together with properties gathered while walking it
+#[derive(Debug)]
+pub struct ProcessedRepo {
+   pub chunks: HashMap<String, Vec<Chunk>>,
+   /// Language most of the repository's source files are written in
+   pub primary_language: Option<&'static str>,
+   /// Commit the repository was cloned at
+   pub commit: Option<String>,
+   /// Unix time of the last commit changing each chunked file, filled with `file_recency`
+   pub modified_at: HashMap<String, i64>,
+   /// Requested symbols that no chunk declares
+   pub missing_symbols: Vec<String>,
+   /// Files whose chunks were cut to `max_chunks_per_file`
+   pub capped_files: Vec<String>,
+}
+
+/// Files re-chunked to update part of an embedded repository
+#[derive(Debug)]
+pub struct ProcessedFiles {
+   /// Chunks of every requested file, none for files that are gone or that a full embed skips
+   pub chunks: HashMap<String, Vec<Chunk>>,
+   /// Unix time of the last commit changing each chunked file, filled with `file_recency`
+   pub modified_at: HashMap<String, i64>,
+   /// Files whose chunks were cut to `max_chunks_per_file`
+   pub capped_files: Vec<String>,
+}
+
+/// Chunks of a single repository file, as they would be produced when embedding the repository
+#[derive(Debug)]
+pub struct FilePreview {
+   /// Language the file is chunked as, or `None` when embedding skips the file
+   pub language: Option<Language>,
+   pub chunks: Vec<Chunk>,
+}
+
+/// Clones a repository and chunks a single file without embedding anything, to show how the
+/// file is split when the repository is embedded
+///
+/// # Arguments
+/// * `repo_url` - The GitHub repository URL or shorthand format ("owner/repo")
+/// * `file_path` - Path of the file relative to the repository root (e.g., "src/main.rs")
+pub async fn preview_file_chunks(
+   repo_url: &str,
+   file_path: &str,
+   config: &EmbeddingConfig,
+) -> Result<FilePreview> {
+   let relative_path = validate_relative_path(file_path)?;
+
+   let temp_dir = clone_repo_with_timeout(repo_url, config).await?;
+
+   let full_path = temp_dir.path().join(relative_path);
+   if !is_regular_file_within(temp_dir.path(), relative_path) {
+      bail!("File not found in repository: {file_path}");
+   }
+   let source = read_source(&full_path, has_known_extension(&full_path, config))
+      .with_context(|| format!("Failed to read {file_path} as text"))?;
+
+   let language = select_language(&full_path, &source, config);
+   let chunks = match language {
+      Some(language) => chunk_source(language, &source, config)?,
+      None => Vec::new(),
+   };
+
+   Ok(FilePreview { language, chunks })
+}
+
+/// Clones a repository and re-chunks only the given files, for updating part of an existing
+/// collection. Every requested path is present in the result; files that no longer exist, are
+/// not in a supported language or are excluded map to no chunks, so their old points are simply
+/// removed.
+pub async fn process_github_repo_files(
+   repo_url: &str,
+   file_paths: &[String],
+   config: &EmbeddingConfig,
+) -> Result<ProcessedFiles> {
+   for file_path in file_paths {
+      validate_relative_path(file_path)?;
+   }
+
+   let mut config = config.clone();
+   // file dates need the history that a shallow clone leaves out
+   if config.file_recency {
+      config.clone_depth = 0;
+   }
+   let temp_dir = clone_repo_with_timeout(repo_url, &config).await?;
+
+   let (chunks, capped_files) = chunk_files(temp_dir.path(), file_paths, &config)?;
+   processed_files(temp_dir.path(), chunks, capped_files, &config)
+}
 
-   let temp_dir = TempDir::new()?;
+/// Clones a repository with its full history and re-chunks the files that differ between
+/// `base_ref` and `head_ref` (branches, tags or commit SHAs), as they are at `head_ref`. Files
+/// deleted by `head_ref` map to no chunks, like in `process_github_repo_files`.
+pub async fn process_github_repo_diff(
+   repo_url: &str,
+   base_ref: &str,
+   head_ref: &str,
+   config: &EmbeddingConfig,
+) -> Result<ProcessedFiles> {
+   let mut config = config.clone();
+   // both refs need to be reachable, which a shallow clone does not guarantee
+   config.clone_depth = 0;
+   let temp_dir = clone_repo_with_timeout(repo_url, &config).await?;
+
+   let (chunks, capped_files) = chunk_diff(temp_dir.path(), base_ref, head_ref, &config)?;
+   processed_files(temp_dir.path(), chunks, capped_files, &config)
+}
+
+/// Dates the chunked files of a partial update when `file_recency` is set, like a full embed
+fn processed_files(
+   root: &Path,
+   chunks: HashMap<String, Vec<Chunk>>,
+   capped_files: Vec<String>,
+   config: &EmbeddingConfig,
+) -> Result<ProcessedFiles> {
+   let modified_at = if config.file_recency {
+      let file_paths: Vec<&str> = chunks
+         .iter()
+         .filter(|(_, file_chunks)| !file_chunks.is_empty())
+         .map(|(path, _)| path.as_str())
+         .collect();
+      file_modification_times(root, &file_paths)
+         .context("Failed to read file modification times")?
+   } else {
+      HashMap::new()
+   };
+
+   Ok(ProcessedFiles {
+      chunks,
+      modified_at,
+      capped_files,
+   })
+}
+
+/// Chunks of each file by path
+type FileChunks = HashMap<String, Vec<Chunk>>;
+
+/// Checks out `head_ref` in the repository at `root` and chunks the files it changes, adds or
+/// deletes compared to `base_ref`, with the capped files like `chunk_files`
+fn chunk_diff(
+   root: &Path,
+   base_ref: &str,
+   head_ref: &str,
+   config: &EmbeddingConfig,
+) -> Result<(FileChunks, Vec<String>)> {
+   let repo = git2::Repository::open(root)?;
+   let base = resolve_commit(&repo, base_ref)?;
+   let head = resolve_commit(&repo, head_ref)?;
+
+   let diff = repo.diff_tree_to_tree(Some(&base.tree()?), Some(&head.tree()?), None)?;
+   let changed_files: Vec<String> = diff
+      .deltas()
+      .flat_map(|delta| [delta.old_file().path(), delta.new_file().path()])
+      .flatten()
+      .map(|path| path.to_string_lossy().to_string())
+      .collect::<std::collections::BTreeSet<_>>()
+      .into_iter()
+      .collect();
+   info!(
+      "{} files changed between {base_ref} and {head_ref}",
+      changed_files.len()
+   );
+
+   repo.checkout_tree(
+      head.as_object(),
+      Some(git2::build::CheckoutBuilder::new().force()),
+   )?;
+   repo.set_head_detached(head.id())?;
+
+   chunk_files(root, &changed_files, config)
+}
+
+/// Finds the commit a branch, tag or SHA names. Of the branches, a fresh clone only checks out
+/// the default one, so others are looked up as `origin/<branch>` as well.
+fn resolve_commit<'repo>(
+   repo: &'repo git2::Repository,
+   reference: &str,
+) -> Result<git2::Commit<'repo>> {
+   repo
+      .revparse_single(reference)
+      .or_else(|_| repo.revparse_single(&format!("origin/{reference}")))
+      .and_then(|object| object.peel_to_commit())
+      .with_context(|| format!("Unknown git ref: {reference}"))
+}
+
+/// Largest file accepted by `process_file_url`
+const MAX_FILE_URL_BYTES: usize = 5 * 1024 * 1024;
+
+/// Hosts `process_file_url` fetches from: GitHub file and gist pages and their raw content.
+/// Fetching any URL would let clients reach Qdrant, cloud metadata endpoints and other hosts
+/// only the server can see.
+const FILE_URL_HOSTS: &[&str] = &[
+   "github.com",
+   "gist.github.com",
+   "raw.githubusercontent.com",
+   "gist.githubusercontent.com",
+];
+
+/// Maximum number of redirects followed when fetching a file URL
+const MAX_FILE_URL_REDIRECTS: usize = 5;
+
+/// Whether a file may be fetched from `url`
+fn is_allowed_file_url(url: &Url) -> bool {
+   matches!(url.scheme(), "http" | "https")
+      && url
+         .host_str()
+         .is_some_and(|host| FILE_URL_HOSTS.contains(&host))
+}
+
+/// Fetches a single file by URL and chunks it, without cloning a repository. GitHub file pages
+/// and gist pages are fetched from their raw content URLs. The chunker is picked from the file
+/// extension or detected from the content, falling back to plain text splitting. Returns the
+/// file name with the file's chunks.
+pub async fn process_file_url(url: &str, config: &EmbeddingConfig) -> Result<(String, Vec<Chunk>)> {
+   let raw_url = raw_file_url(url)?;
+   info!("Fetching {raw_url}");
+
+   // redirects must stay on the allowed hosts, or they would lead anywhere
+   let redirect_policy = reqwest::redirect::Policy::custom(|attempt| {
+      if !is_allowed_file_url(attempt.url()) {
+         let error = format!("Refusing to follow a redirect to {}", attempt.url());
+         attempt.error(error)
+      } else if attempt.previous().len() > MAX_FILE_URL_REDIRECTS {
+         attempt.error("Too many redirects")
+      } else {
+         attempt.follow()
+      }
+   });
+   let response = reqwest::Client::builder()
+      .timeout(config.clone_timeout)
+      .redirect(redirect_policy)
+      .build()?
+      .get(&raw_url)
+      .send()
+      .await
+      .with_context(|| format!("Failed to fetch {raw_url}"))?
+      .error_for_status()
+      .with_context(|| format!("Failed to fetch {raw_url}"))?;
+   if response
+      .content_length()
+      .is_some_and(|length| length > MAX_FILE_URL_BYTES as u64)
+   {
+      bail!("{raw_url} is larger than {MAX_FILE_URL_BYTES} bytes");
+   }
+   let bytes = response.bytes().await?;
+   if bytes.len() > MAX_FILE_URL_BYTES {
+      bail!("{raw_url} is larger than {MAX_FILE_URL_BYTES} bytes");
+   }
+   let source = String::from_utf8(bytes.to_vec()).context("File is not valid UTF-8 text")?;
+
+   let file_name = Url::parse(&raw_url)?
+      .path_segments()
+      .and_then(|mut segments| segments.next_back().map(str::to_string))
+      .filter(|name| !name.is_empty())
+      .unwrap_or_else(|| "file".to_string());
+   let chunks = chunk_fetched_file(&file_name, &source, config)?;
+
+   Ok((file_name, chunks))
+}
+
+/// Chunks a fetched file by its extension or detected language, splitting it as plain text
+/// when neither is known
+fn chunk_fetched_file(
+   file_name: &str,
+   source: &str,
+   config: &EmbeddingConfig,
+) -> Result<Vec<Chunk>> {
+   let path = Path::new(file_name);
+   match Language::from_extension(path).or_else(|| Language::detect(path, source)) {
+      Some(language) => chunk_source(language, source, config),
+      None => chunk_plain_text(source, config),
+   }
+}
+
+/// Maps GitHub file pages (`github.com/owner/repo/blob/ref/path`) and gist pages
+/// (`gist.github.com/user/id`) to the URLs of their raw content. Other URLs on the GitHub hosts
+/// of `FILE_URL_HOSTS` are fetched as-is, and URLs of any other host are rejected.
+fn raw_file_url(url: &str) -> Result<String> {
+   let parsed = Url::parse(url).with_context(|| format!("Invalid file URL: {url}"))?;
+   if !matches!(parsed.scheme(), "http" | "https") {
+      bail!("File URL must use http or https: {url}");
+   }
+   if !is_allowed_file_url(&parsed) {
+      bail!(
+         "File URL must be on one of {}: {url}",
+         FILE_URL_HOSTS.join(", ")
+      );
+   }
+
+   let segments: Vec<&str> = parsed
+      .path_segments()
+      .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+      .unwrap_or_default();
+
+   match (parsed.host_str(), segments.as_slice()) {
+      (Some("github.com"), [owner, repo, "blob", rest @ ..]) if !rest.is_empty() => Ok(format!(
+         "https://raw.githubusercontent.com/{owner}/{repo}/{}",
+         rest.join("/")
+      )),
+      (Some("gist.github.com"), [user, id]) => Ok(format!(
+         "https://gist.githubusercontent.com/{user}/{id}/raw"
+      )),
+      _ => Ok(url.to_string()),
+   }
+}
+
+/// Chunks the listed files under `root`, mapping each path to its chunks. The repository's
+/// `.embed-config.toml` applies over `config`, and files a full embed would skip, in excluded
+/// directories or filtered out by extension or glob, map to no chunks. The files whose chunks
+/// were cut to `max_chunks_per_file` are returned alongside.
+fn chunk_files(
+   root: &Path,
+   file_paths: &[String],
+   config: &EmbeddingConfig,
+) -> Result<(FileChunks, Vec<String>)> {
+   let mut config = config.clone();
+   ChunkPreferences::from_repo(root).apply(&mut config);
+   let config = &config;
+   let exclude_patterns = compile_globs(&config.exclude_globs);
+   let mut file_chunks_map = HashMap::new();
+   let mut capped_files = Vec::new();
+
+   for file_path in file_paths {
+      let relative_path = validate_relative_path(file_path)?;
+      let full_path = root.join(relative_path);
+
+      if is_in_excluded_dir(relative_path, &config.excluded_dirs)
+         || !is_selected(relative_path, config, &exclude_patterns)
+      {
+         file_chunks_map.insert(file_path.clone(), Vec::new());
+         continue;
+      }
+      if !is_regular_file_within(root, relative_path) {
+         info!("Skipping {file_path}, it is not a regular file of the repository");
+         file_chunks_map.insert(file_path.clone(), Vec::new());
+         continue;
+      }
+
+      let mut chunks = match read_source(&full_path, has_known_extension(&full_path, config)) {
+         Ok(source) => match select_language(&full_path, &source, config) {
+            Some(language) => chunk_source(language, &source, config)?,
+            None if is_explicitly_included(&full_path, config) => {
+               chunk_plain_text(&source, config)?
+            }
+            None => Vec::new(),
+         },
+         Err(e) => {
+            info!("Skipping {file_path}, it could not be read: {e}");
+            Vec::new()
+         }
+      };
+
+      if cap_chunks(file_path, &mut chunks, config) {
+         capped_files.push(file_path.clone());
+      }
+
+      file_chunks_map.insert(file_path.clone(), chunks);
+   }
+
+   Ok((file_chunks_map, capped_files))
+}
+
+/// Rejects file paths that are absolute or escape the repository root
+fn validate_relative_path(file_path: &str) -> Result<&Path> {
+   let relative_path = Path::new(file_path);
+   if relative_path.is_absolute()
+      || relative_path
+         .components()
+         .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+   {
+      bail!("File path must be relative to the repository root: {file_path}");
+   }
+
+   Ok(relative_path)
+}
+
+/// Whether `relative_path` names a regular file under `root` that is reached without following
+/// a symlink, neither the file itself nor any of its parent directories. Repositories can
+/// commit links to files outside of them, like `walk_repo_files` they are never read.
+fn is_regular_file_within(root: &Path, relative_path: &Path) -> bool {
+   let mut path = root.to_path_buf();
+   for component in relative_path.components() {
+      path.push(component);
+      match std::fs::symlink_metadata(&path) {
+         Ok(metadata) if !metadata.file_type().is_symlink() => {}
+         _ => return false,
+      }
+   }
+
+   path.is_file()
+}
+
+/// Reads a source file as UTF-8. With `transcode`, files that are not valid UTF-8 are decoded
+/// from UTF-16 when they start with a byte order mark, or from Windows-1252 (a superset of
+/// Latin-1) when they contain no NUL bytes, which would indicate a binary file.
+fn read_source(path: &Path, transcode: bool) -> std::io::Result<String> {
+   let bytes = std::fs::read(path)?;
+   let bytes = match String::from_utf8(bytes) {
+      Ok(source) => return Ok(source),
+      Err(e) if transcode => e.into_bytes(),
+      Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+   };
+
+   match decode_non_utf8(&bytes) {
+      Some((source, encoding)) => {
+         info!("Transcoded {} from {encoding} to UTF-8", path.display());
+         Ok(source)
+      }
+      None => Err(std::io::Error::new(
+         std::io::ErrorKind::InvalidData,
+         "file is neither UTF-8 nor a decodable text encoding",
+      )),
+   }
+}
+
+/// Decodes text that is not valid UTF-8, returning it with the name of the detected encoding
+fn decode_non_utf8(bytes: &[u8]) -> Option<(String, &'static str)> {
+   let encoding = match encoding_rs::Encoding::for_bom(bytes) {
+      Some((encoding, _)) => encoding,
+      None if !bytes.contains(&0) => encoding_rs::WINDOWS_1252,
+      None => return None,
+   };
+
+   let (source, _, had_errors) = encoding.decode(bytes);
+   (!had_errors).then(|| (source.into_owned(), encoding.name()))
+}
+
+/// Whether a file is selected by its extension, either through a dedicated chunker or by being
+/// listed in the config's extensions
+fn has_known_extension(path: &Path, config: &EmbeddingConfig) -> bool {
+   Language::from_extension(path).is_some() || is_explicitly_included(path, config)
+}
+
+/// Picks the chunker for a file from its extension, falling back to content detection when
+/// enabled in the config
+fn select_language(path: &Path, source: &str, config: &EmbeddingConfig) -> Option<Language> {
+   Language::from_extension(path).or_else(|| {
+      config
+         .detect_languages
+         .then(|| Language::detect(path, source))
+         .flatten()
+   })
+}
+
+/// Lists the regular files of a repository checkout, pruning excluded directories.
+/// Symlinks are never followed, so files linked from elsewhere in the repository are only
+/// reached through their real path and symlink loops cannot cause endless traversal.
+fn walk_repo_files(root: &Path, excluded_dirs: &[String]) -> Vec<PathBuf> {
+   WalkDir::new(root)
+      .follow_links(false)
+      .into_iter()
+      .filter_entry(|e| !is_excluded_dir(e, excluded_dirs))
+      .filter_map(Result::ok)
+      .filter(|e| e.file_type().is_file())
+      .map(DirEntry::into_path)
+      .collect()
+}
+
+/// Whether a walked entry is a directory whose name is in the exclusion list, in which case
+/// it is pruned along with everything below it
+fn is_excluded_dir(entry: &DirEntry, excluded_dirs: &[String]) -> bool {
+   entry.depth() > 0
+      && entry.file_type().is_dir()
+      && entry
+         .file_name()
+         .to_str()
+         .is_some_and(|name| excluded_dirs.iter().any(|dir| dir == name))
+}
+
+/// Whether a path relative to the repository root lies below a directory in the exclusion
+/// list, which `walk_repo_files` would never have entered
+fn is_in_excluded_dir(relative_path: &Path, excluded_dirs: &[String]) -> bool {
+   relative_path.parent().is_some_and(|parent| {
+      parent.components().any(|component| {
+         component
+            .as_os_str()
+            .to_str()
+            .is_some_and(|name| excluded_dirs.iter().any(|dir| dir == name))
+      })
+   })
+}
+
+/// Delay before the first clone retry, doubled for each further attempt
+const CLONE_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Prefix of the temporary directories repositories are cloned into, so directories left
+/// behind by a crashed server can be told apart from those of other programs
+const CLONE_DIR_PREFIX: &str = "mcp-rust-docs-clone-";
+
+/// Age past which a leftover clone directory is considered abandoned. Well above the time
+/// any embed keeps its clone, so directories of a server still running are not removed.
+pub const STALE_CLONE_DIR_AGE: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Removes clone directories older than `max_age` from the system temporary directory.
+/// Clones are removed when their `TempDir` is dropped, but a server that crashes or is killed
+/// mid-embed leaves them behind, which fills the disk of long-running hosts. Returns the
+/// number of directories removed.
+pub fn remove_stale_clone_dirs(max_age: Duration) -> usize {
+   remove_stale_clone_dirs_in(&std::env::temp_dir(), max_age)
+}
+
+fn remove_stale_clone_dirs_in(temp_root: &Path, max_age: Duration) -> usize {
+   let entries = match std::fs::read_dir(temp_root) {
+      Ok(entries) => entries,
+      Err(e) => {
+         warn!(
+            "Failed to list {} for stale clones: {e}",
+            temp_root.display()
+         );
+         return 0;
+      }
+   };
+
+   let mut removed = 0;
+   for entry in entries.flatten() {
+      let is_clone_dir = entry
+         .file_name()
+         .to_str()
+         .is_some_and(|name| name.starts_with(CLONE_DIR_PREFIX));
+      let is_stale = entry
+         .metadata()
+         .ok()
+         .filter(|metadata| metadata.is_dir())
+         .and_then(|metadata| metadata.modified().ok())
+         .and_then(|modified| modified.elapsed().ok())
+         .is_some_and(|age| age >= max_age);
+      if !(is_clone_dir && is_stale) {
+         continue;
+      }
+
+      match std::fs::remove_dir_all(entry.path()) {
+         Ok(()) => removed += 1,
+         Err(e) => warn!(
+            "Failed to remove stale clone {}: {e}",
+            entry.path().display()
+         ),
+      }
+   }
+
+   removed
+}
+
+/// Clones a repository in a blocking context, failing once `clone_timeout` elapses so that a
+/// stalled clone surfaces as an error (and a failed embed operation) instead of hanging
+async fn clone_repo_with_timeout(repo_url: &str, config: &EmbeddingConfig) -> Result<TempDir> {
+   let deadline = Instant::now() + config.clone_timeout;
+   let clone = tokio::task::spawn_blocking({
+      let repo_url = repo_url.to_string();
+      let config = config.clone();
+      move || clone_repo(&repo_url, &config, deadline)
+   });
+
+   match tokio::time::timeout(config.clone_timeout, clone).await {
+      Ok(result) => result?,
+      Err(_) => bail!(
+         "Cloning {repo_url} timed out after {}s",
+         config.clone_timeout.as_secs()
+      ),
+   }
+}
+
+/// Clones a repository into a temporary directory with the configured history depth,
+/// retrying transient network failures up to `clone_attempts` times in total. Transfers
+/// still running at `deadline` are aborted.
+fn clone_repo(repo: &str, config: &EmbeddingConfig, deadline: Instant) -> Result<TempDir> {
+   let repo_url = parse_repo_url(repo)?;
+   // git2 takes the depth as an i32, which a large u32 would wrap to a negative depth
+   let depth = i32::try_from(config.clone_depth).with_context(|| {
+      format!(
+         "CLONE_DEPTH {} is too large, the maximum is {}",
+         config.clone_depth,
+         i32::MAX
+      )
+   })?;
 
    info!("Cloning repository: {repo_url}");
 
-   builder.clone(repo_url.as_str(), temp_dir.path())?;
+   let temp_dir = retry_with_backoff(config.clone_attempts, CLONE_RETRY_BASE_DELAY, || {
+      let mut builder = git2::build::RepoBuilder::new();
+
+      // returning false from the progress callback aborts the transfer
+      let mut callbacks = git2::RemoteCallbacks::new();
+      callbacks.transfer_progress(|_| Instant::now() < deadline);
+
+      let mut fetch_options = git2::FetchOptions::new();
+      fetch_options.remote_callbacks(callbacks);
+      // a depth of zero fetches the full history
+      if depth > 0 {
+         fetch_options.depth(depth);
+      }
+
+      builder.fetch_options(fetch_options);
+
+      // every attempt needs an empty destination
+      let temp_dir = tempfile::Builder::new()
+         .prefix(CLONE_DIR_PREFIX)
+         .tempdir()
+         .map_err(|e| git2::Error::from_str(&e.to_string()))?;
+      builder.clone(repo_url.as_str(), temp_dir.path())?;
+
+      Ok(temp_dir)
+   })
+   .with_context(|| {
+      if Instant::now() >= deadline {
+         format!(
+            "Cloning {repo_url} timed out after {}s",
+            config.clone_timeout.as_secs()
+         )
+      } else {
+         format!("Failed to clone {repo_url}")
+      }
+   })?;
 
    info!("Cloned complete");
 
    Ok(temp_dir)
 }
 
+/// Time allowed for listing the refs of a remote repository
+const LS_REMOTE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Looks up the commit the default branch of a remote repository points to, like
+/// `git ls-remote <repo> HEAD`, without cloning it. `None` when the remote reports no HEAD,
+/// as for an empty repository.
+pub async fn remote_head_commit(repo: &str) -> Result<Option<String>> {
+   let repo_url = parse_repo_url(repo)?;
+   let ls_remote = tokio::task::spawn_blocking({
+      let repo_url = repo_url.to_string();
+      move || ls_remote_head(&repo_url)
+   });
+
+   match tokio::time::timeout(LS_REMOTE_TIMEOUT, ls_remote).await {
+      Ok(result) => result?,
+      Err(_) => bail!(
+         "Listing the refs of {repo_url} timed out after {}s",
+         LS_REMOTE_TIMEOUT.as_secs()
+      ),
+   }
+}
+
+fn ls_remote_head(repo_url: &str) -> Result<Option<String>> {
+   let mut remote = git2::Remote::create_detached(repo_url)?;
+   remote
+      .connect(git2::Direction::Fetch)
+      .with_context(|| format!("Failed to connect to {repo_url}"))?;
+
+   let head = remote
+      .list()?
+      .iter()
+      .find(|head| head.name() == "HEAD")
+      .map(|head| head.oid().to_string());
+   Ok(head)
+}
+
+/// Runs a git operation, retrying with exponential backoff while it fails with a transient
+/// error. Permanent errors such as authentication failures or missing repositories are
+/// returned immediately.
+fn retry_with_backoff<T>(
+   attempts: u32,
+   base_delay: Duration,
+   mut operation: impl FnMut() -> Result<T, git2::Error>,
+) -> Result<T> {
+   let attempts = attempts.max(1);
+   let mut delay = base_delay;
+
+   for attempt in 1..=attempts {
+      match operation() {
+         Ok(value) => return Ok(value),
+         Err(e) if attempt < attempts && is_transient(&e) => {
+            warn!(
+               "Clone attempt {attempt}/{attempts} failed with a transient error, retrying in \
+                {delay:?}: {e}"
+            );
+            std::thread::sleep(delay);
+            delay *= 2;
+         }
+         Err(e) => return Err(e.into()),
+      }
+   }
+
+   unreachable!("the last attempt always returns")
+}
+
+/// Whether a git error is likely to succeed on retry, i.e. a network or I/O failure rather
+/// than a rejected or missing repository
+fn is_transient(error: &git2::Error) -> bool {
+   if matches!(
+      error.code(),
+      git2::ErrorCode::Auth | git2::ErrorCode::NotFound | git2::ErrorCode::Certificate
+   ) {
+      return false;
+   }
+
+   match error.class() {
+      git2::ErrorClass::Net | git2::ErrorClass::Os | git2::ErrorClass::Ssl => true,
+      // http errors include status codes, of which only server-side ones are worth retrying
+      git2::ErrorClass::Http => !["401", "403", "404"]
+         .iter()
+         .any(|status| error.message().contains(status)),
+      _ => false,
+   }
+}
+
 fn parse_repo_url(repo: &str) -> Result<Url> {
    match Url::parse(repo) {
       Ok(url) => Ok(url),
@@ -105,3 +1039,599 @@ fn parse_repo_url(repo: &str) -> Result<Url> {
       _ => bail!("Invalid input: expected URL or owner/repo format"),
    }
 }
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_stale_clone_dirs_are_removed() -> Result<()> {
+      let root = TempDir::new()?;
+      let clone = root.path().join(format!("{CLONE_DIR_PREFIX}abc123"));
+      std::fs::create_dir_all(clone.join(".git"))?;
+      let other = root.path().join("other-program");
+      std::fs::create_dir(&other)?;
+
+      // a fresh clone may belong to an embed still running
+      assert_eq!(
+         remove_stale_clone_dirs_in(root.path(), STALE_CLONE_DIR_AGE),
+         0
+      );
+      assert!(clone.exists());
+
+      assert_eq!(remove_stale_clone_dirs_in(root.path(), Duration::ZERO), 1);
+      assert!(!clone.exists());
+      assert!(other.exists());
+      Ok(())
+   }
+
+   #[test]
+   fn test_non_utf8_sources_are_transcoded() -> Result<()> {
+      let root = TempDir::new()?;
+      let latin1 = root.path().join("latin1.rs");
+      std::fs::write(&latin1, b"// caf\xe9 au lait\nfn brew() {}\n")?;
+      let utf16 = root.path().join("utf16.ts");
+      let mut bytes = vec![0xFF, 0xFE];
+      bytes.extend(
+         "export const tea = 'th\u{e9}';\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes),
+      );
+      std::fs::write(&utf16, bytes)?;
+      let binary = root.path().join("binary.rs");
+      std::fs::write(&binary, b"\x00\xff\x00\xfe")?;
+
+      assert_eq!(
+         read_source(&latin1, true)?,
+         "// caf\u{e9} au lait\nfn brew() {}\n"
+      );
+      assert_eq!(
+         read_source(&utf16, true)?,
+         "export const tea = 'th\u{e9}';\n"
+      );
+      assert!(read_source(&binary, true).is_err());
+      // without transcoding only UTF-8 is accepted
+      assert!(read_source(&latin1, false).is_err());
+      Ok(())
+   }
+
+   #[test]
+   fn test_excluded_dirs_are_not_walked() -> Result<()> {
+      let root = TempDir::new()?;
+      for dir in ["src", "node_modules/pkg", "vendor/docs", "docs/target"] {
+         std::fs::create_dir_all(root.path().join(dir))?;
+         std::fs::write(root.path().join(dir).join("README.md"), "# Title\n")?;
+      }
+
+      let walk = |excluded_dirs: &[String]| relative_files(root.path(), excluded_dirs);
+
+      assert_eq!(
+         walk(&EmbeddingConfig::default().excluded_dirs),
+         vec!["src/README.md"]
+      );
+
+      // overriding the defaults lets intentionally vendored docs through
+      assert_eq!(
+         walk(&["node_modules".to_string()]),
+         vec![
+            "docs/target/README.md",
+            "src/README.md",
+            "vendor/docs/README.md"
+         ]
+      );
+      Ok(())
+   }
+
+   #[test]
+   fn test_raw_file_url() -> Result<()> {
+      assert_eq!(
+         raw_file_url("https://github.com/owner/repo/blob/main/src/lib.rs")?,
+         "https://raw.githubusercontent.com/owner/repo/main/src/lib.rs"
+      );
+      assert_eq!(
+         raw_file_url("https://gist.github.com/someone/0123abcd")?,
+         "https://gist.githubusercontent.com/someone/0123abcd/raw"
+      );
+      let raw = "https://raw.githubusercontent.com/owner/repo/main/README.md";
+      assert_eq!(raw_file_url(raw)?, raw);
+      assert!(raw_file_url("file:///etc/passwd").is_err());
+      Ok(())
+   }
+
+   #[test]
+   fn test_file_urls_outside_github_are_rejected() {
+      for url in [
+         "http://localhost:6334/collections",
+         "http://169.254.169.254/latest/meta-data/",
+         "http://10.0.0.5/internal",
+         "https://example.com/lib.rs",
+         "https://github.com.evil.example/owner/repo/blob/main/src/lib.rs",
+      ] {
+         let err = raw_file_url(url).unwrap_err();
+         assert!(
+            err.to_string().contains("must be on one of"),
+            "{url}: {err}"
+         );
+      }
+   }
+
+   #[test]
+   fn test_fetched_file_with_unknown_extension_is_split_as_text() -> Result<()> {
+      let config = EmbeddingConfig::default();
+
+      let chunks = chunk_fetched_file("notes.txt", "Some notes.\n", &config)?;
+      assert_eq!(chunks[0].kind, ChunkKind::TextBlock);
+
+      let chunks = chunk_fetched_file("lib.rs", "pub fn parse() {}\n", &config)?;
+      assert_eq!(chunks[0].kind, ChunkKind::Function);
+      Ok(())
+   }
+
+   #[test]
+   fn test_explicitly_included_files_fall_back_to_plain_text() -> Result<()> {
+      let root = tempfile::tempdir()?;
+      std::fs::write(root.path().join("NOTES.txt"), "Release checklist.\n")?;
+      let files = ["NOTES.txt".to_string()];
+
+      let (chunks, _) = chunk_files(root.path(), &files, &EmbeddingConfig::default())?;
+      assert!(chunks["NOTES.txt"].is_empty());
+
+      let config = EmbeddingConfig {
+         extensions: Some(vec!["rs".to_string(), "txt".to_string()]),
+         ..Default::default()
+      };
+      let (chunks, _) = chunk_files(root.path(), &files, &config)?;
+      assert_eq!(chunks["NOTES.txt"].len(), 1);
+      assert_eq!(chunks["NOTES.txt"][0].kind, ChunkKind::TextBlock);
+      Ok(())
+   }
+
+   #[test]
+   fn test_updated_files_skipped_like_a_full_embed() -> Result<()> {
+      let root = tempfile::tempdir()?;
+      for dir in ["src", "examples", "target/debug"] {
+         std::fs::create_dir_all(root.path().join(dir))?;
+      }
+      for file in ["src/lib.rs", "examples/demo.rs", "target/debug/build.rs"] {
+         std::fs::write(root.path().join(file), "pub fn parse() {}\n")?;
+      }
+      std::fs::write(
+         root.path().join(".embed-config.toml"),
+         "exclude_globs = [\"examples/**\"]\n",
+      )?;
+      let files = [
+         "src/lib.rs".to_string(),
+         "examples/demo.rs".to_string(),
+         "target/debug/build.rs".to_string(),
+      ];
+
+      let (chunks, _) = chunk_files(root.path(), &files, &EmbeddingConfig::default())?;
+
+      assert_eq!(chunks["src/lib.rs"].len(), 1);
+      // listed so that their old points are removed, but never embedded
+      assert!(chunks["examples/demo.rs"].is_empty());
+      assert!(chunks["target/debug/build.rs"].is_empty());
+      Ok(())
+   }
+
+   #[test]
+   fn test_extension_and_glob_filters() {
+      let config = EmbeddingConfig {
+         extensions: Some(vec!["rs".to_string(), "md".to_string()]),
+         exclude_globs: vec!["examples/**".to_string(), "*.generated.rs".to_string()],
+         ..Default::default()
+      };
+      let patterns = compile_globs(&config.exclude_globs);
+      let selected = |path: &str| is_selected(Path::new(path), &config, &patterns);
+
+      assert!(selected("src/lib.rs"));
+      assert!(selected("README.md"));
+      assert!(!selected("web/index.ts"));
+      assert!(!selected("examples/demo.rs"));
+      assert!(!selected("src/bindings.generated.rs"));
+   }
+
+   fn relative_files(root: &Path, excluded_dirs: &[String]) -> Vec<String> {
+      let mut files: Vec<String> = walk_repo_files(root, excluded_dirs)
+         .iter()
+         .map(|path| {
+            path
+               .strip_prefix(root)
+               .unwrap()
+               .to_string_lossy()
+               .to_string()
+         })
+         .collect();
+      files.sort();
+      files
+   }
+
+   #[cfg(unix)]
+   #[test]
+   fn test_symlinks_are_not_followed() -> Result<()> {
+      use std::os::unix::fs::symlink;
+
+      let root = TempDir::new()?;
+      std::fs::create_dir_all(root.path().join("src/nested"))?;
+      std::fs::write(root.path().join("src/lib.rs"), "pub fn parse() {}\n")?;
+      // a second path to the same file and to the same directory
+      symlink("lib.rs", root.path().join("src/alias.rs"))?;
+      symlink("../src", root.path().join("src/nested/src_link"))?;
+      // a link to its own parent would loop forever if followed
+      symlink(".", root.path().join("src/nested/self"))?;
+
+      assert_eq!(relative_files(root.path(), &[]), vec!["src/lib.rs"]);
+      Ok(())
+   }
+
+   #[cfg(unix)]
+   #[test]
+   fn test_listed_symlinks_are_not_read() -> Result<()> {
+      use std::os::unix::fs::symlink;
+
+      let outside = TempDir::new()?;
+      std::fs::write(outside.path().join("secret.md"), "# Secret\n\nhunter2\n")?;
+      let root = TempDir::new()?;
+      std::fs::create_dir_all(root.path().join("docs"))?;
+      symlink(
+         outside.path().join("secret.md"),
+         root.path().join("docs/leak.md"),
+      )?;
+      symlink(outside.path(), root.path().join("linked"))?;
+
+      let paths = vec!["docs/leak.md".to_string(), "linked/secret.md".to_string()];
+      let (chunks, _) = chunk_files(root.path(), &paths, &EmbeddingConfig::default())?;
+
+      assert!(chunks["docs/leak.md"].is_empty());
+      assert!(chunks["linked/secret.md"].is_empty());
+      Ok(())
+   }
+
+   #[test]
+   fn test_chunk_files_only_chunks_listed_files() -> Result<()> {
+      let root = TempDir::new()?;
+      std::fs::create_dir_all(root.path().join("src"))?;
+      std::fs::write(root.path().join("src/lib.rs"), "pub fn updated() {}\n")?;
+      std::fs::write(root.path().join("src/other.rs"), "pub fn untouched() {}\n")?;
+
+      let paths = vec!["src/lib.rs".to_string(), "src/removed.rs".to_string()];
+      let (chunks, _) = chunk_files(root.path(), &paths, &EmbeddingConfig::default())?;
+
+      assert_eq!(chunks.len(), 2);
+      assert!(!chunks.contains_key("src/other.rs"));
+      assert!(chunks["src/lib.rs"][0].content.contains("updated"));
+      // files that were deleted upstream produce no chunks so their points are only removed
+      assert!(chunks["src/removed.rs"].is_empty());
+
+      let escaping = vec!["../outside.rs".to_string()];
+      assert!(chunk_files(root.path(), &escaping, &EmbeddingConfig::default()).is_err());
+      Ok(())
+   }
+
+   #[test]
+   fn test_excluded_tests_are_not_chunked() -> Result<()> {
+      let root = TempDir::new()?;
+      std::fs::write(
+         root.path().join("lib.rs"),
+         "pub fn parse() {}\n\n#[cfg(test)]\nmod tests {\n    #[test]\n    fn parses() {}\n}\n",
+      )?;
+      let paths = vec!["lib.rs".to_string()];
+
+      let kinds = |config: &EmbeddingConfig| -> Result<Vec<ChunkKind>> {
+         let (chunks, _) = chunk_files(root.path(), &paths, config)?;
+         Ok(chunks["lib.rs"].iter().map(|chunk| chunk.kind).collect())
+      };
+
+      assert_eq!(
+         kinds(&EmbeddingConfig::default())?,
+         vec![ChunkKind::Function]
+      );
+      let config = EmbeddingConfig {
+         exclude_tests: false,
+         ..Default::default()
+      };
+      assert_eq!(kinds(&config)?, vec![ChunkKind::Function, ChunkKind::Test]);
+      Ok(())
+   }
+
+   /// Commits the given files to the repository at `root`, deleting those without content
+   fn commit_files(root: &Path, files: &[(&str, Option<&str>)], message: &str) -> Result<String> {
+      let repo = git2::Repository::open(root)?;
+      let mut index = repo.index()?;
+      for (path, content) in files {
+         match content {
+            Some(content) => {
+               std::fs::write(root.join(path), content)?;
+               index.add_path(Path::new(path))?;
+            }
+            None => {
+               std::fs::remove_file(root.join(path))?;
+               index.remove_path(Path::new(path))?;
+            }
+         }
+      }
+      index.write()?;
+      let tree = repo.find_tree(index.write_tree()?)?;
+
+      let signature = git2::Signature::now("Ada", "ada@example.com")?;
+      let parent = repo
+         .head()
+         .ok()
+         .map(|head| head.peel_to_commit())
+         .transpose()?;
+      let parents: Vec<&git2::Commit> = parent.iter().collect();
+      let commit = repo.commit(
+         Some("HEAD"),
+         &signature,
+         &signature,
+         message,
+         &tree,
+         &parents,
+      )?;
+      Ok(commit.to_string())
+   }
+
+   #[tokio::test]
+   async fn test_remote_head_commit_matches_latest_commit() -> Result<()> {
+      let root = TempDir::new()?;
+      git2::Repository::init(root.path())?;
+      let remote = Url::from_directory_path(root.path()).expect("absolute path");
+
+      commit_files(root.path(), &[("lib.rs", Some("fn a() {}\n"))], "first")?;
+      let latest = commit_files(root.path(), &[("lib.rs", Some("fn b() {}\n"))], "second")?;
+
+      assert_eq!(remote_head_commit(remote.as_str()).await?, Some(latest));
+      Ok(())
+   }
+
+   #[test]
+   fn test_diff_chunks_only_changed_files() -> Result<()> {
+      let root = TempDir::new()?;
+      git2::Repository::init(root.path())?;
+      let base = commit_files(
+         root.path(),
+         &[
+            ("parse.rs", Some("pub fn parse() {}\n")),
+            ("render.rs", Some("pub fn render() {}\n")),
+            ("legacy.rs", Some("pub fn legacy() {}\n")),
+         ],
+         "Add modules",
+      )?;
+      let head = commit_files(
+         root.path(),
+         &[
+            ("parse.rs", Some("pub fn parse_strict() {}\n")),
+            ("legacy.rs", None),
+         ],
+         "Rename parse, drop legacy",
+      )?;
+      // start from the base, as a checkout of another branch would
+      let repo = git2::Repository::open(root.path())?;
+      repo.set_head_detached(git2::Oid::from_str(&base)?)?;
+      repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))?;
+
+      let (chunks, _) = chunk_diff(root.path(), &base, &head, &EmbeddingConfig::default())?;
+
+      let mut changed: Vec<&String> = chunks.keys().collect();
+      changed.sort();
+      assert_eq!(changed, vec!["legacy.rs", "parse.rs"]);
+      assert_eq!(chunks["parse.rs"][0].name.as_deref(), Some("parse_strict"));
+      assert!(chunks["legacy.rs"].is_empty());
+      Ok(())
+   }
+
+   #[cfg(unix)]
+   #[test]
+   fn test_diff_does_not_read_committed_symlinks() -> Result<()> {
+      use std::os::unix::fs::symlink;
+
+      let outside = TempDir::new()?;
+      std::fs::write(outside.path().join("passwd.md"), "# Users\n\nroot:x:0:0\n")?;
+      let root = TempDir::new()?;
+      let repo = git2::Repository::init(root.path())?;
+      let base = commit_files(root.path(), &[("README.md", Some("# Readme\n"))], "Init")?;
+      symlink(
+         outside.path().join("passwd.md"),
+         root.path().join("leak.md"),
+      )?;
+      let mut index = repo.index()?;
+      index.add_path(Path::new("leak.md"))?;
+      index.write()?;
+      let head = commit_files(root.path(), &[], "Link a host file")?;
+
+      let (chunks, _) = chunk_diff(root.path(), &base, &head, &EmbeddingConfig::default())?;
+
+      assert_eq!(chunks.keys().collect::<Vec<_>>(), vec!["leak.md"]);
+      assert!(chunks["leak.md"].is_empty());
+      Ok(())
+   }
+
+   #[test]
+   fn test_chunks_per_file_are_capped() -> Result<()> {
+      let root = TempDir::new()?;
+      let bindings: String = (0..200)
+         .map(|i| format!("pub fn binding_{i}() {{}}\n\n"))
+         .collect();
+      std::fs::write(root.path().join("bindings.rs"), bindings)?;
+      let paths = vec!["bindings.rs".to_string()];
+
+      let (uncapped, capped_files) = chunk_files(root.path(), &paths, &EmbeddingConfig::default())?;
+      assert_eq!(uncapped["bindings.rs"].len(), 200);
+      assert!(capped_files.is_empty());
+
+      let config = EmbeddingConfig {
+         max_chunks_per_file: Some(50),
+         ..Default::default()
+      };
+      let (capped, capped_files) = chunk_files(root.path(), &paths, &config)?;
+      assert_eq!(capped["bindings.rs"].len(), 50);
+      assert_eq!(capped_files, ["bindings.rs"]);
+      assert_eq!(
+         capped["bindings.rs"][49].name.as_deref(),
+         Some("binding_49")
+      );
+
+      let mut chunks = uncapped["bindings.rs"].clone();
+      assert!(cap_chunks("bindings.rs", &mut chunks, &config));
+      assert!(!cap_chunks("bindings.rs", &mut chunks, &config));
+
+      // a limit of 0 keeps every chunk instead of none
+      let unlimited = EmbeddingConfig {
+         max_chunks_per_file: Some(0),
+         ..Default::default()
+      };
+      let (kept, capped_files) = chunk_files(root.path(), &paths, &unlimited)?;
+      assert_eq!(kept["bindings.rs"].len(), 200);
+      assert!(capped_files.is_empty());
+      Ok(())
+   }
+
+   #[test]
+   fn test_minified_files_are_split_or_skipped() -> Result<()> {
+      let root = TempDir::new()?;
+      let bundle: String = (0..2000)
+         .map(|i| format!("function f{i}(a){{return a+{i}}}"))
+         .collect();
+      std::fs::write(root.path().join("bundle.min.ts"), format!("{bundle}\n"))?;
+      let paths = vec!["bundle.min.ts".to_string()];
+
+      let (split, _) = chunk_files(root.path(), &paths, &EmbeddingConfig::default())?;
+      let windows = &split["bundle.min.ts"];
+      assert!(windows.len() > 1);
+      assert!(
+         windows
+            .iter()
+            .all(|chunk| chunk.kind == ChunkKind::TextBlock)
+      );
+      // the windows cover the whole file instead of a truncated first chunk
+      let content: String = windows.iter().map(|chunk| chunk.content.as_str()).collect();
+      assert_eq!(content.trim_end(), bundle);
+
+      let config = EmbeddingConfig {
+         minified_policy: MinifiedPolicy::Skip,
+         ..Default::default()
+      };
+      let (skipped, _) = chunk_files(root.path(), &paths, &config)?;
+      assert!(skipped["bundle.min.ts"].is_empty());
+
+      assert!(!is_minified("fn parse() {}\n\nfn render() {}\n", 500));
+      Ok(())
+   }
+
+   #[test]
+   fn test_prose_with_long_lines_keeps_its_sections() -> Result<()> {
+      let paragraph = "Lorem ipsum dolor sit amet. ".repeat(50);
+      let source = format!("# Usage\n\n{paragraph}\n\n# Configuration\n\n{paragraph}\n");
+      assert!(is_minified(&source, 500));
+
+      // sections are chunked as markdown rather than split into fixed-size windows
+      let chunks = chunk_source(Language::Markdown, &source, &EmbeddingConfig::default())?;
+      assert!(chunks[0].content.starts_with("# Usage"));
+      assert!(
+         chunks
+            .iter()
+            .all(|chunk| chunk.kind == ChunkKind::MarkdownSection)
+      );
+      Ok(())
+   }
+
+   fn transient_error() -> git2::Error {
+      git2::Error::new(
+         git2::ErrorCode::GenericError,
+         git2::ErrorClass::Net,
+         "failed to connect to github.com: Connection timed out",
+      )
+   }
+
+   #[test]
+   fn test_retain_symbols_keeps_requested_items() -> Result<()> {
+      let source = "/// Parses input\npub fn parse() {}\n\npub struct Config;\n\nimpl<T> \
+                    Wrapper<T> {}\n\nfn render() {}\n";
+      let mut chunks_map = HashMap::from([
+         ("src/lib.rs".to_string(), Language::Rust.chunk(source)?),
+         (
+            "src/other.rs".to_string(),
+            Language::Rust.chunk("fn helper() {}\n")?,
+         ),
+      ]);
+
+      let symbols = ["parse", "Wrapper", "missing"].map(String::from);
+      let missing = retain_symbols(&mut chunks_map, &symbols);
+
+      assert_eq!(missing, vec!["missing".to_string()]);
+      assert_eq!(chunks_map.len(), 1);
+      let names: Vec<_> = chunks_map["src/lib.rs"]
+         .iter()
+         .map(|chunk| chunk.name.as_deref())
+         .collect();
+      assert_eq!(names, vec![Some("parse"), Some("Wrapper")]);
+      Ok(())
+   }
+
+   #[test]
+   fn test_clone_retries_transient_errors() -> Result<()> {
+      let mut calls = 0;
+      let result = retry_with_backoff(3, Duration::ZERO, || {
+         calls += 1;
+         if calls < 3 {
+            Err(transient_error())
+         } else {
+            Ok("cloned")
+         }
+      })?;
+
+      assert_eq!(result, "cloned");
+      assert_eq!(calls, 3);
+      Ok(())
+   }
+
+   #[test]
+   fn test_clone_gives_up_after_configured_attempts() {
+      let mut calls = 0;
+      let result: Result<()> = retry_with_backoff(2, Duration::ZERO, || {
+         calls += 1;
+         Err(transient_error())
+      });
+
+      assert!(result.is_err());
+      assert_eq!(calls, 2);
+   }
+
+   #[test]
+   fn test_clone_fails_immediately_on_permanent_errors() {
+      let mut calls = 0;
+      let result: Result<()> = retry_with_backoff(5, Duration::ZERO, || {
+         calls += 1;
+         Err(git2::Error::new(
+            git2::ErrorCode::Auth,
+            git2::ErrorClass::Http,
+            "authentication required",
+         ))
+      });
+
+      assert!(result.is_err());
+      assert_eq!(calls, 1);
+
+      let not_found = git2::Error::new(
+         git2::ErrorCode::GenericError,
+         git2::ErrorClass::Http,
+         "unexpected http status code: 404",
+      );
+      assert!(!is_transient(&not_found));
+   }
+
+   #[test]
+   fn test_clone_rejects_depth_beyond_i32() {
+      let config = EmbeddingConfig {
+         clone_depth: u32::MAX,
+         ..Default::default()
+      };
+      let deadline = Instant::now() + Duration::from_secs(1);
+
+      let err = clone_repo("https://github.com/owner/repo", &config, deadline).unwrap_err();
+      assert!(
+         err.to_string()
+            .contains("CLONE_DEPTH 4294967295 is too large")
+      );
+   }
+}
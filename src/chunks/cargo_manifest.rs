@@ -0,0 +1,135 @@
+use super::{
+   text,
+   types::{Chunk, ChunkKind},
+};
+use anyhow::Result;
+use std::path::Path;
+use toml::{Table, Value};
+use tracing::warn;
+
+/// Dependency tables of a manifest, in the order they are listed in the chunk
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// Whether a file is a Cargo manifest
+pub fn is_cargo_manifest(path: &Path) -> bool {
+   path.file_name().is_some_and(|name| name == "Cargo.toml")
+}
+
+/// Turns a `Cargo.toml` into a single `ChunkKind::Dependencies` chunk listing the crate's
+/// dependencies with their versions and sources, one per line, so questions such as which
+/// version of a crate a repository uses can be answered. Platform-specific and workspace
+/// dependencies are listed under their own headings. Manifests that are not valid TOML, such
+/// as project templates, are split as plain text instead.
+pub fn extract_manifest_chunks(source: &str) -> Result<Vec<Chunk>> {
+   let manifest: Table = match toml::from_str(source) {
+      Ok(manifest) => manifest,
+      Err(e) => {
+         warn!("Failed to parse Cargo manifest, chunking it as plain text: {e}");
+         return text::extract_text_chunks(source);
+      }
+   };
+
+   let mut sections = Vec::new();
+   for table in DEPENDENCY_TABLES {
+      sections.extend(dependency_section(table, manifest.get(table)));
+   }
+   if let Some(targets) = manifest.get("target").and_then(Value::as_table) {
+      for (target, target_table) in targets {
+         for table in DEPENDENCY_TABLES {
+            sections.extend(dependency_section(
+               &format!("target.{target}.{table}"),
+               target_table.get(table),
+            ));
+         }
+      }
+   }
+   sections.extend(dependency_section(
+      "workspace.dependencies",
+      manifest
+         .get("workspace")
+         .and_then(|workspace| workspace.get("dependencies")),
+   ));
+   if sections.is_empty() {
+      return Ok(Vec::new());
+   }
+
+   let package = manifest.get("package");
+   let name = package
+      .and_then(|package| package.get("name"))
+      .and_then(Value::as_str)
+      .map(str::to_string);
+   let heading = match (&name, package.and_then(|package| package.get("version"))) {
+      (Some(name), Some(Value::String(version))) => {
+         format!("Dependencies of crate {name} {version}")
+      }
+      (Some(name), _) => format!("Dependencies of crate {name}"),
+      (None, _) => "Dependencies of the workspace".to_string(),
+   };
+
+   Ok(vec![Chunk {
+      kind: ChunkKind::Dependencies,
+      name: Some(name.unwrap_or_else(|| "workspace".to_string())),
+      start_line: 1,
+      end_line: source.lines().count().max(1),
+      content: format!("{heading}\n\n{}", sections.join("\n\n")),
+      ..Default::default()
+   }])
+}
+
+/// Lists the dependencies of one table under its `[name]` heading, or nothing when the table
+/// is missing or empty
+fn dependency_section(name: &str, table: Option<&Value>) -> Option<String> {
+   let table = table?.as_table().filter(|table| !table.is_empty())?;
+
+   let mut lines = vec![format!("[{name}]")];
+   for (dependency, spec) in table {
+      lines.push(format!("{dependency} = {}", describe_dependency(spec)));
+   }
+   Some(lines.join("\n"))
+}
+
+/// Describes where a dependency comes from, e.g. `1.45.1, features: full` or
+/// `git https://github.com/owner/repo, branch main`
+fn describe_dependency(spec: &Value) -> String {
+   let Some(spec) = spec.as_table() else {
+      return spec.as_str().unwrap_or("*").to_string();
+   };
+   let field = |key: &str| spec.get(key).and_then(Value::as_str);
+
+   let mut parts = Vec::new();
+   if spec.get("workspace").and_then(Value::as_bool) == Some(true) {
+      parts.push("inherited from the workspace".to_string());
+   }
+   if let Some(version) = field("version") {
+      parts.push(version.to_string());
+   }
+   if let Some(git) = field("git") {
+      parts.push(format!("git {git}"));
+      for reference in ["branch", "tag", "rev"] {
+         if let Some(value) = field(reference) {
+            parts.push(format!("{reference} {value}"));
+         }
+      }
+   }
+   if let Some(path) = field("path") {
+      parts.push(format!("path {path}"));
+   }
+   if let Some(package) = field("package") {
+      parts.push(format!("package {package}"));
+   }
+   if let Some(features) = spec.get("features").and_then(Value::as_array) {
+      let features: Vec<&str> = features.iter().filter_map(Value::as_str).collect();
+      if !features.is_empty() {
+         parts.push(format!("features: {}", features.join(", ")));
+      }
+   }
+   if spec.get("optional").and_then(Value::as_bool) == Some(true) {
+      parts.push("optional".to_string());
+   }
+
+   if parts.is_empty() {
+      "*".to_string()
+   } else {
+      parts.join(", ")
+   }
+}
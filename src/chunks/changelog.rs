@@ -0,0 +1,107 @@
+use super::{Chunk, markdown};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::Path;
+use tracing::trace;
+
+/// File stems of Markdown files holding release notes, compared case-insensitively
+const CHANGELOG_STEMS: &[&str] = &[
+   "changelog",
+   "changes",
+   "history",
+   "news",
+   "releases",
+   "release_notes",
+   "release-notes",
+];
+
+/// Version headers such as `## [1.2.3] - 2024-01-01`, `## v1.2.3` or `# Release 1.2.3`
+static VERSION_HEADER: Lazy<Regex> = Lazy::new(|| {
+   Regex::new(
+      r"^#{1,3}\s+\[?(?i:(?:version|release)\s+)?v?(\d+\.\d+(?:\.\d+)?(?:-[0-9A-Za-z.]+)?)\b",
+   )
+   .expect("valid regex")
+});
+
+/// Header of the section collecting changes not released yet
+static UNRELEASED_HEADER: Lazy<Regex> =
+   Lazy::new(|| Regex::new(r"^#{1,3}\s+\[?(?i:unreleased)\]?").expect("valid regex"));
+
+/// Whether a Markdown file is a changelog or release notes, judged by its file name
+pub fn is_changelog(path: &Path) -> bool {
+   path
+      .file_stem()
+      .and_then(|stem| stem.to_str())
+      .is_some_and(|stem| {
+         CHANGELOG_STEMS
+            .iter()
+            .any(|changelog| stem.eq_ignore_ascii_case(changelog))
+      })
+}
+
+/// Version a changelog header line introduces, without a leading `v`. The section of
+/// unreleased changes is named `Unreleased`.
+pub fn version_header(line: &str) -> Option<String> {
+   if let Some(captures) = VERSION_HEADER.captures(line) {
+      return Some(captures[1].to_string());
+   }
+   UNRELEASED_HEADER
+      .is_match(line)
+      .then(|| "Unreleased".to_string())
+}
+
+/// Normalizes a version given by a user the way `version_header` reports it, so `v1.2.3`
+/// matches the section of `1.2.3`
+pub fn normalize_version(version: &str) -> String {
+   let version = version.trim();
+   if version.eq_ignore_ascii_case("unreleased") {
+      return "Unreleased".to_string();
+   }
+   version
+      .strip_prefix(['v', 'V'])
+      .unwrap_or(version)
+      .to_string()
+}
+
+/// Splits a changelog into one section per version header and chunks each section as
+/// Markdown, tagging its chunks with the version it documents. Text before the first version
+/// header is chunked without a version.
+pub fn extract_changelog_chunks(source: &str) -> Result<Vec<Chunk>> {
+   let lines: Vec<&str> = source.split_inclusive('\n').collect();
+
+   let mut sections: Vec<(usize, Option<String>)> = vec![(0, None)];
+   for (index, line) in lines.iter().enumerate() {
+      if let Some(version) = version_header(line) {
+         sections.push((index, Some(version)));
+      }
+   }
+
+   let mut chunks = Vec::new();
+   for (i, (start, version)) in sections.iter().enumerate() {
+      let end = sections.get(i + 1).map_or(lines.len(), |(next, _)| *next);
+      let section = lines[*start..end].concat();
+      if section.trim().is_empty() {
+         continue;
+      }
+
+      chunks.extend(
+         markdown::extract_markdown_chunks(&section)?
+            .into_iter()
+            .map(|chunk| Chunk {
+               start_line: chunk.start_line + start,
+               end_line: chunk.end_line + start,
+               version: version.clone(),
+               ..chunk
+            }),
+      );
+   }
+
+   trace!(
+      "Changelog chunk extraction produced {} chunks over {} versions",
+      chunks.len(),
+      sections.len() - 1
+   );
+
+   Ok(chunks)
+}
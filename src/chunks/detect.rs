@@ -0,0 +1,167 @@
+use super::{Chunk, cargo_manifest, changelog, markdown, notebook, rust, text, typescript};
+use anyhow::Result;
+use std::{collections::HashMap, ops::Range, path::Path};
+
+/// Languages a repository file can be chunked as
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Language {
+   Rust,
+   TypeScript,
+   Markdown,
+   /// Markdown changelogs and release notes, chunked per version
+   Changelog,
+   /// Cargo manifests, reduced to the dependencies they declare
+   CargoManifest,
+   /// Jupyter notebooks, whose cells are chunked by their own language
+   Notebook,
+   // Languages without a dedicated chunker, split as plain text
+   Python,
+   Shell,
+   JavaScript,
+   Ruby,
+   Dockerfile,
+   Makefile,
+}
+
+impl Language {
+   /// Picks the language from the file extension, which is how files are routed by default.
+   /// Markdown changelogs and Cargo manifests are told apart by their file name.
+   pub fn from_extension(path: &Path) -> Option<Self> {
+      match path.extension()?.to_str()? {
+         "rs" => Some(Language::Rust),
+         "ts" => Some(Language::TypeScript),
+         "md" if changelog::is_changelog(path) => Some(Language::Changelog),
+         // MDX is a markdown superset, its JSX components are kept as section content
+         "md" | "mdx" => Some(Language::Markdown),
+         "ipynb" => Some(Language::Notebook),
+         "toml" if cargo_manifest::is_cargo_manifest(path) => Some(Language::CargoManifest),
+         _ => None,
+      }
+   }
+
+   /// Detects the language of a file whose extension is not recognized, using the file
+   /// name, template suffixes such as `.rs.in`, and the shebang line
+   pub fn detect(path: &Path, source: &str) -> Option<Self> {
+      let file_name = path.file_name()?.to_str()?;
+
+      match file_name {
+         "Dockerfile" | "Containerfile" => return Some(Language::Dockerfile),
+         "Makefile" | "GNUmakefile" => return Some(Language::Makefile),
+         _ => {}
+      }
+      if file_name.starts_with("Dockerfile.") {
+         return Some(Language::Dockerfile);
+      }
+
+      // generated-source templates keep the real extension before the template suffix
+      if let Some(stem) = file_name.strip_suffix(".in")
+         && let Some(language) = Self::from_extension(Path::new(stem))
+      {
+         return Some(language);
+      }
+
+      Self::from_shebang(source)
+   }
+
+   /// Reads the interpreter from a `#!` line, looking through `env` to the program it runs
+   fn from_shebang(source: &str) -> Option<Self> {
+      let shebang = source.lines().next()?.strip_prefix("#!")?;
+      let mut words = shebang.split_whitespace();
+      let mut interpreter = words.next()?.rsplit('/').next()?;
+      if interpreter == "env" {
+         interpreter = words.find(|word| !word.starts_with('-'))?;
+      }
+
+      // strip version suffixes such as python3 or python3.12
+      let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+      match name {
+         "python" => Some(Language::Python),
+         "sh" | "bash" | "zsh" | "dash" | "ksh" => Some(Language::Shell),
+         "node" | "deno" | "bun" => Some(Language::JavaScript),
+         "ruby" => Some(Language::Ruby),
+         _ => None,
+      }
+   }
+
+   /// Splits source in this language into chunks with the matching chunker
+   pub fn chunk(&self, source: &str) -> Result<Vec<Chunk>> {
+      self.chunk_with_text_range(source, text::DEFAULT_TEXT_CHUNK_CHARS)
+   }
+
+   /// Like `chunk`, splitting languages without a dedicated chunker into plain text chunks
+   /// of the given range of characters
+   pub fn chunk_with_text_range(
+      &self,
+      source: &str,
+      text_chars: Range<usize>,
+   ) -> Result<Vec<Chunk>> {
+      match self {
+         Language::Rust => rust::extract_rust_chunks(source),
+         Language::TypeScript => typescript::extract_typescript_chunks(source),
+         Language::Markdown => markdown::extract_markdown_chunks(source),
+         Language::Changelog => changelog::extract_changelog_chunks(source),
+         Language::CargoManifest => cargo_manifest::extract_manifest_chunks(source),
+         Language::Notebook => notebook::extract_notebook_chunks(source, text_chars),
+         _ => text::extract_text_chunks_in_range(source, text_chars),
+      }
+   }
+}
+
+/// Tallies the programming languages of the files in a repository by extension, including
+/// languages that are not chunked, to find what a repository is predominantly written in
+#[derive(Debug, Default)]
+pub struct LanguageCounts {
+   counts: HashMap<&'static str, usize>,
+}
+
+impl LanguageCounts {
+   /// Counts a file towards its language, ignoring files that are not source code
+   pub fn record(&mut self, path: &Path) {
+      if let Some(language) = path
+         .extension()
+         .and_then(|ext| ext.to_str())
+         .and_then(source_language_name)
+      {
+         *self.counts.entry(language).or_default() += 1;
+      }
+   }
+
+   /// The language with the most files, ties broken alphabetically so the result is stable
+   pub fn primary(&self) -> Option<&'static str> {
+      self
+         .counts
+         .iter()
+         .max_by(|(a_name, a_count), (b_name, b_count)| {
+            a_count.cmp(b_count).then_with(|| b_name.cmp(a_name))
+         })
+         .map(|(name, _)| *name)
+   }
+}
+
+/// Maps a source file extension to its language name. Documentation and data formats are
+/// left out so a repository with extensive docs is still classified by its code.
+fn source_language_name(extension: &str) -> Option<&'static str> {
+   let name = match extension {
+      "rs" => "Rust",
+      "ts" | "tsx" | "mts" | "cts" => "TypeScript",
+      "js" | "jsx" | "mjs" | "cjs" => "JavaScript",
+      "go" => "Go",
+      "py" => "Python",
+      "java" => "Java",
+      "kt" | "kts" => "Kotlin",
+      "rb" => "Ruby",
+      "c" | "h" => "C",
+      "cc" | "cpp" | "cxx" | "hpp" => "C++",
+      "cs" => "C#",
+      "swift" => "Swift",
+      "php" => "PHP",
+      "scala" => "Scala",
+      "ex" | "exs" => "Elixir",
+      "hs" => "Haskell",
+      "zig" => "Zig",
+      _ => return None,
+   };
+
+   Some(name)
+}
@@ -1,10 +1,22 @@
-use super::types::{Chunk, ChunkKind};
+use super::{
+   tokenizer::{count_tokens, tokenizer},
+   types::{Chunk, ChunkKind},
+};
 use anyhow::Result;
 use text_splitter::{ChunkConfig, MarkdownSplitter};
 use tracing::trace;
 
+/// Maximum token limit for chunks
+const MAX_TOKENS: usize = 8192;
+
 /// Splits Markdown documents into semantic sections preserving headings and content
-/// relationships for optimal documentation search and retrieval
+/// relationships for optimal documentation search and retrieval. Also used for MDX, whose
+/// JSX component tags are parsed as inline HTML and kept in the section content.
+///
+/// Sections are split without trimming, so `reconstruct` of the chunks yields the source
+/// exactly, whitespace-only lines included. This only fails to hold when a section exceeds
+/// the token limit, since its pieces are cut at line boundaries and may close and reopen a
+/// code fence.
 pub fn extract_markdown_chunks(source: &str) -> Result<Vec<Chunk>> {
    let start = std::time::Instant::now();
    trace!(
@@ -14,18 +26,34 @@ pub fn extract_markdown_chunks(source: &str) -> Result<Vec<Chunk>> {
 
    let splitter = MarkdownSplitter::new(ChunkConfig::new(1000..1500).with_trim(false));
    let mut chunks = Vec::new();
+   let mut search_from = 0;
+   let mut split_sections = 0;
 
    for (i, chunk_text) in splitter.chunks(source).enumerate() {
-      let byte_offset = source.find(chunk_text).unwrap_or(0);
+      // search after the previous chunk so repeated text (e.g. closing JSX tags) does not
+      // resolve to an earlier occurrence
+      let byte_offset = source[search_from..]
+         .find(chunk_text)
+         .map(|offset| offset + search_from)
+         .unwrap_or(search_from);
+      search_from = byte_offset + chunk_text.len();
       let start_line = source[..byte_offset].matches('\n').count() + 1;
       let end_line = start_line + chunk_text.matches('\n').count();
 
-      chunks.push(Chunk {
-         kind: ChunkKind::MarkdownSection,
-         start_line,
-         end_line,
-         content: chunk_text.to_string(),
-      });
+      let pieces = split_to_token_limit(
+         Chunk {
+            kind: ChunkKind::MarkdownSection,
+            start_line,
+            end_line,
+            content: chunk_text.to_string(),
+            ..Default::default()
+         },
+         MAX_TOKENS,
+      );
+      if pieces.len() > 1 {
+         split_sections += 1;
+      }
+      chunks.extend(pieces);
 
       trace!(
          "Created markdown chunk {} with {} chars at lines {}-{}",
@@ -36,6 +64,11 @@ pub fn extract_markdown_chunks(source: &str) -> Result<Vec<Chunk>> {
       );
    }
 
+   debug_assert!(
+      split_sections > 0 || reconstruct(&chunks) == source,
+      "markdown chunks do not reconstruct their source"
+   );
+
    let elapsed = start.elapsed();
    trace!(
       "Markdown chunk extraction completed in {:?} - produced {} chunks",
@@ -45,3 +78,151 @@ pub fn extract_markdown_chunks(source: &str) -> Result<Vec<Chunk>> {
 
    Ok(chunks)
 }
+
+/// Concatenates the content of chunks in order, which gives back the source of
+/// `extract_markdown_chunks` when none of its sections had to be split
+pub fn reconstruct(chunks: &[Chunk]) -> String {
+   chunks.iter().map(|chunk| chunk.content.as_str()).collect()
+}
+
+/// Splits a chunk exceeding `max_tokens` at line boundaries. A code fence that spans a split
+/// is closed at the end of one piece and reopened with its info string at the start of the
+/// next, so every piece stays valid Markdown. Lines too long to fit on their own are trimmed.
+pub fn split_to_token_limit(chunk: Chunk, max_tokens: usize) -> Vec<Chunk> {
+   if count_tokens(&chunk.content) <= max_tokens {
+      return vec![chunk];
+   }
+
+   let mut pieces = Vec::new();
+   let mut piece = Piece::new(chunk.start_line, None);
+   let mut open_fence: Option<Fence> = None;
+
+   for (i, line) in chunk.content.lines().enumerate() {
+      let line_number = chunk.start_line + i;
+      let line_tokens = count_tokens(line) + 1;
+      // leave room for the marker closing a fence that is still open at the end of the piece
+      let reserved = open_fence
+         .as_ref()
+         .map_or(0, |fence| count_tokens(&fence.marker) + 1);
+
+      if piece.has_source_lines && piece.tokens + line_tokens + reserved > max_tokens {
+         pieces.push(piece.finish(&chunk, open_fence.as_ref()));
+         piece = Piece::new(line_number, open_fence.as_ref());
+      }
+
+      let budget = max_tokens.saturating_sub(piece.tokens + reserved + 1);
+      if line_tokens > budget + 1 {
+         piece.push(&trim_to_tokens(line, budget), line_number, budget + 1);
+      } else {
+         piece.push(line, line_number, line_tokens);
+      }
+
+      match &open_fence {
+         Some(fence) if fence.is_closed_by(line) => open_fence = None,
+         None => open_fence = Fence::opened_by(line),
+         Some(_) => {}
+      }
+   }
+
+   if piece.has_source_lines {
+      pieces.push(piece.finish(&chunk, None));
+   }
+
+   trace!(
+      "Split markdown chunk at lines {}-{} into {} pieces",
+      chunk.start_line,
+      chunk.end_line,
+      pieces.len()
+   );
+
+   pieces
+}
+
+/// A code fence opened in Markdown content
+struct Fence {
+   /// Opening line including the info string, e.g. "```rust"
+   opening: String,
+   /// Run of backticks or tildes that closes the fence
+   marker: String,
+}
+
+impl Fence {
+   fn opened_by(line: &str) -> Option<Self> {
+      let trimmed = line.trim_start();
+      let fence_char = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+      let marker: String = trimmed.chars().take_while(|&c| c == fence_char).collect();
+      (marker.len() >= 3).then(|| Fence {
+         opening: trimmed.to_string(),
+         marker,
+      })
+   }
+
+   fn is_closed_by(&self, line: &str) -> bool {
+      let trimmed = line.trim();
+      trimmed.starts_with(&self.marker)
+         && trimmed
+            .trim_start_matches(self.marker.chars().next().unwrap_or('`'))
+            .is_empty()
+   }
+}
+
+/// Lines collected for one piece of a split chunk
+struct Piece {
+   lines: Vec<String>,
+   tokens: usize,
+   start_line: usize,
+   end_line: usize,
+   /// Whether any source line was added, as opposed to only a reopened fence
+   has_source_lines: bool,
+}
+
+impl Piece {
+   fn new(start_line: usize, reopened_fence: Option<&Fence>) -> Self {
+      let mut piece = Piece {
+         lines: Vec::new(),
+         tokens: 0,
+         start_line,
+         end_line: start_line,
+         has_source_lines: false,
+      };
+      if let Some(fence) = reopened_fence {
+         piece.tokens = count_tokens(&fence.opening) + 1;
+         piece.lines.push(fence.opening.clone());
+      }
+      piece
+   }
+
+   fn push(&mut self, line: &str, line_number: usize, tokens: usize) {
+      self.lines.push(line.to_string());
+      self.tokens += tokens;
+      self.end_line = line_number;
+      self.has_source_lines = true;
+   }
+
+   fn finish(mut self, chunk: &Chunk, open_fence: Option<&Fence>) -> Chunk {
+      if let Some(fence) = open_fence {
+         self.lines.push(fence.marker.clone());
+      }
+      Chunk {
+         kind: chunk.kind,
+         name: chunk.name.clone(),
+         signature: chunk.signature.clone(),
+         cell: chunk.cell,
+         version: chunk.version.clone(),
+         start_line: self.start_line,
+         end_line: self.end_line,
+         content: self.lines.join("\n"),
+         ..Default::default()
+      }
+   }
+}
+
+fn trim_to_tokens(text: &str, max_tokens: usize) -> String {
+   let tokens = tokenizer().encode_with_special_tokens(text);
+   if tokens.len() <= max_tokens {
+      return text.to_string();
+   }
+   tokenizer()
+      .decode(tokens[..max_tokens].to_vec())
+      .unwrap_or_default()
+}
@@ -1,6 +1,13 @@
+pub mod cargo_manifest;
+pub mod changelog;
+pub mod detect;
 pub mod markdown;
+pub mod notebook;
 pub mod rust;
+pub mod text;
+pub mod tokenizer;
+pub mod transform;
 pub mod types;
 pub mod typescript;
 
-pub use types::{Chunk, ChunkKind};
+pub use types::{Chunk, ChunkKind, CommitInfo, FunctionSignature, MethodSignature};
@@ -0,0 +1,126 @@
+use super::{Chunk, detect::Language, markdown, text};
+use anyhow::Result;
+use serde::Deserialize;
+use std::ops::Range;
+use tracing::{trace, warn};
+
+/// The parts of a Jupyter notebook (`.ipynb`) that are chunked
+#[derive(Debug, Deserialize)]
+struct Notebook {
+   #[serde(default)]
+   cells: Vec<Cell>,
+   #[serde(default)]
+   metadata: NotebookMetadata,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct NotebookMetadata {
+   kernelspec: Option<KernelSpec>,
+   language_info: Option<LanguageInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KernelSpec {
+   language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LanguageInfo {
+   name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Cell {
+   cell_type: String,
+   #[serde(default)]
+   source: CellSource,
+}
+
+/// Cell sources are stored either as one string or as a list of lines
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CellSource {
+   Text(String),
+   Lines(Vec<String>),
+}
+
+impl Default for CellSource {
+   fn default() -> Self {
+      CellSource::Text(String::new())
+   }
+}
+
+impl CellSource {
+   fn text(self) -> String {
+      match self {
+         CellSource::Text(text) => text,
+         CellSource::Lines(lines) => lines.concat(),
+      }
+   }
+}
+
+impl NotebookMetadata {
+   /// Language of the code cells, from the kernel spec or else the language info. Kernels
+   /// without a matching chunker are split as plain text.
+   fn code_language(&self) -> Language {
+      let name = self
+         .kernelspec
+         .as_ref()
+         .and_then(|kernel| kernel.language.as_deref())
+         .or_else(|| {
+            self
+               .language_info
+               .as_ref()
+               .and_then(|info| info.name.as_deref())
+         })
+         .unwrap_or("python");
+
+      match name.to_ascii_lowercase().as_str() {
+         "rust" => Language::Rust,
+         "typescript" => Language::TypeScript,
+         "javascript" => Language::JavaScript,
+         "bash" | "sh" | "shell" => Language::Shell,
+         "ruby" => Language::Ruby,
+         _ => Language::Python,
+      }
+   }
+}
+
+/// Splits a Jupyter notebook into chunks per cell: markdown cells with the markdown chunker
+/// and code cells with the chunker of the notebook's kernel language. Each chunk records the
+/// index of its cell, and its line numbers are relative to the cell. Raw cells and outputs
+/// are left out. Notebooks that are not valid JSON, such as Git LFS pointers or files left
+/// with merge conflicts, are split as plain text instead.
+pub fn extract_notebook_chunks(source: &str, text_chars: Range<usize>) -> Result<Vec<Chunk>> {
+   let notebook: Notebook = match serde_json::from_str(source) {
+      Ok(notebook) => notebook,
+      Err(e) => {
+         warn!("Failed to parse Jupyter notebook, chunking it as plain text: {e}");
+         return text::extract_text_chunks_in_range(source, text_chars);
+      }
+   };
+   let code_language = notebook.metadata.code_language();
+
+   let mut chunks = Vec::new();
+   for (index, cell) in notebook.cells.into_iter().enumerate() {
+      let cell_type = cell.cell_type;
+      let cell_source = cell.source.text();
+      if cell_source.trim().is_empty() {
+         continue;
+      }
+
+      let cell_chunks = match cell_type.as_str() {
+         "markdown" => markdown::extract_markdown_chunks(&cell_source)?,
+         "code" => code_language.chunk_with_text_range(&cell_source, text_chars.clone())?,
+         _ => continue,
+      };
+      chunks.extend(cell_chunks.into_iter().map(|chunk| Chunk {
+         cell: Some(index),
+         ..chunk
+      }));
+   }
+
+   trace!("Notebook chunk extraction produced {} chunks", chunks.len());
+
+   Ok(chunks)
+}
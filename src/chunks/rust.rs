@@ -1,8 +1,9 @@
-use super::types::{Chunk, ChunkKind};
+use super::{
+   tokenizer::tokenizer,
+   types::{Chunk, ChunkKind, FunctionSignature, MethodSignature},
+};
 use anyhow::{Context, Result};
-use once_cell::sync::Lazy;
 use std::{collections::HashSet, ops::RangeInclusive};
-use tiktoken_rs::{CoreBPE, cl100k_base};
 use tracing::trace;
 use tree_sitter::Node;
 
@@ -12,9 +13,6 @@ const NODES_TO_IGNORE: [&str; 1] = ["use_declaration"];
 /// Maximum token limit for chunks
 const MAX_TOKENS: usize = 8192;
 
-/// Lazy-initialized BPE tokenizer to avoid repeated initialization
-static BPE: Lazy<CoreBPE> = Lazy::new(|| cl100k_base().expect("Failed to initialize tiktoken BPE"));
-
 /// Parses Rust source code into semantic chunks preserving documentation context
 /// and respecting token limits for effective embedding generation
 pub fn extract_rust_chunks(source: &str) -> Result<Vec<Chunk>> {
@@ -77,8 +75,11 @@ fn process_node(node: &Node, source: &str, processed_lines: &mut HashSet<usize>)
    let kind = match node.kind() {
       "struct_item" => ChunkKind::Struct,
       "enum_item" => ChunkKind::Enum,
+      "function_item" if has_attribute(node, source, is_test_attribute) => ChunkKind::Test,
       "function_item" => ChunkKind::Function,
       "impl_item" => ChunkKind::Impl,
+      // other modules are not chunked as a whole
+      "mod_item" if is_test_module(node, source) => ChunkKind::Test,
       "line_comment" => {
          return handle_comment(node, source, start_line, processed_lines);
       }
@@ -92,12 +93,136 @@ fn process_node(node: &Node, source: &str, processed_lines: &mut HashSet<usize>)
 
    Some(Chunk {
       kind,
+      name: item_name(node, source),
+      signature: match kind {
+         ChunkKind::Function => function_signature(node, source),
+         _ => None,
+      },
+      methods: match kind {
+         ChunkKind::Impl => method_signatures(node, source),
+         _ => Vec::new(),
+      },
       start_line: start_line + 1,
       end_line: end_line + 1,
       content,
+      ..Default::default()
+   })
+}
+
+/// Name of the item a node declares. Impl blocks are named after the type they implement,
+/// without generic arguments.
+fn item_name(node: &Node, source: &str) -> Option<String> {
+   let name = node
+      .child_by_field_name("name")
+      .or_else(|| node.child_by_field_name("type"))?;
+   let name = name.utf8_text(source.as_bytes()).ok()?;
+   name.split('<').next().map(|name| name.trim().to_string())
+}
+
+/// Signature of a function item, read from its modifiers, name, type parameters, parameters,
+/// return type and where clause
+fn function_signature(node: &Node, source: &str) -> Option<FunctionSignature> {
+   let text = |node: Node| {
+      node
+         .utf8_text(source.as_bytes())
+         .ok()
+         .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+   };
+   let child_of_kind = |kind: &str| {
+      let mut cursor = node.walk();
+      node
+         .children(&mut cursor)
+         .find(|child| child.kind() == kind)
+   };
+
+   let name = text(node.child_by_field_name("name")?)?;
+   let parameters = node.child_by_field_name("parameters")?;
+   let mut cursor = parameters.walk();
+   let params = parameters
+      .named_children(&mut cursor)
+      .filter(|param| !param.is_extra() && param.kind() != "attribute_item")
+      .filter_map(text)
+      .collect();
+
+   Some(FunctionSignature {
+      qualifiers: child_of_kind("function_modifiers").and_then(text),
+      name,
+      generics: node.child_by_field_name("type_parameters").and_then(text),
+      params,
+      return_type: node.child_by_field_name("return_type").and_then(text),
+      where_clause: child_of_kind("where_clause")
+         .and_then(text)
+         .map(|clause| clause.trim_end_matches(',').to_string()),
    })
 }
 
+/// Signatures of the methods declared in an impl block, each spanning its method from the
+/// first doc comment or attribute. Test functions are left out.
+fn method_signatures(node: &Node, source: &str) -> Vec<MethodSignature> {
+   let Some(body) = node.child_by_field_name("body") else {
+      return Vec::new();
+   };
+   let mut cursor = body.walk();
+
+   body
+      .named_children(&mut cursor)
+      .filter(|child| {
+         child.kind() == "function_item" && !has_attribute(child, source, is_test_attribute)
+      })
+      .filter_map(|method| {
+         let start_line = match method.prev_sibling() {
+            Some(prev) if is_adjacent_decoration(&prev, &method) => find_first_decoration(&prev),
+            _ => method.start_position().row,
+         };
+         Some(MethodSignature {
+            signature: function_signature(&method, source)?,
+            start_line: start_line + 1,
+            end_line: method.end_position().row + 1,
+         })
+      })
+      .collect()
+}
+
+/// Whether a module holds tests, either by being gated on `#[cfg(test)]` or by being named
+/// `tests`
+fn is_test_module(node: &Node, source: &str) -> bool {
+   item_name(node, source).as_deref() == Some("tests")
+      || has_attribute(node, source, |attribute| attribute == "cfg(test)")
+}
+
+/// Whether an attribute marks a test function: `#[test]` or a runtime's variant of it such
+/// as `#[tokio::test]`
+fn is_test_attribute(attribute: &str) -> bool {
+   let path = attribute.split('(').next().unwrap_or_default();
+   path == "test" || path.ends_with("::test")
+}
+
+/// Whether any attribute directly preceding a node matches the predicate. The predicate
+/// receives the attribute without its `#[` and `]` delimiters and without whitespace.
+fn has_attribute(node: &Node, source: &str, predicate: impl Fn(&str) -> bool) -> bool {
+   let mut current = *node;
+
+   while let Some(prev) = current.prev_sibling() {
+      if !is_adjacent_decoration(&prev, &current) {
+         break;
+      }
+      if prev.kind() == "attribute_item"
+         && let Ok(text) = prev.utf8_text(source.as_bytes())
+      {
+         let attribute: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+         let attribute = attribute
+            .strip_prefix("#[")
+            .and_then(|attribute| attribute.strip_suffix(']'));
+         if attribute.is_some_and(&predicate) {
+            return true;
+         }
+      }
+      current = prev;
+   }
+
+   false
+}
+
 fn is_adjacent_decoration(previous_sibling: &Node, next_sibling: &Node) -> bool {
    matches!(previous_sibling.kind(), "line_comment" | "attribute_item")
       && previous_sibling.end_position().row + 1 >= next_sibling.start_position().row
@@ -126,7 +251,7 @@ fn handle_comment(
    processed_lines: &mut HashSet<usize>,
 ) -> Option<Chunk> {
    // Check if this comment precedes an item declaration
-   if is_comment_before_item(node) {
+   if is_comment_before_item(node, source) {
       return None;
    }
 
@@ -142,10 +267,11 @@ fn handle_comment(
       start_line: start_line + 1,
       end_line: end_line + 1,
       content,
+      ..Default::default()
    })
 }
 
-fn is_comment_before_item(node: &Node) -> bool {
+fn is_comment_before_item(node: &Node, source: &str) -> bool {
    let mut check_node = *node;
 
    // Look ahead through comments and attributes to find an item
@@ -155,6 +281,10 @@ fn is_comment_before_item(node: &Node) -> bool {
             // Found an item - check if adjacent
             return check_node.end_position().row + 1 >= next.start_position().row;
          }
+         "mod_item" if is_test_module(&next, source) => {
+            // Found an item - check if adjacent
+            return check_node.end_position().row + 1 >= next.start_position().row;
+         }
          "line_comment" | "attribute_item"
             if next.start_position().row <= check_node.end_position().row + 1 =>
          {
@@ -201,9 +331,9 @@ fn extract_lines(source: &str, range: RangeInclusive<usize>) -> String {
       .join("\n")
 }
 
-fn trim_to_token_limit(content: &str) -> Result<String> {
+pub(crate) fn trim_to_token_limit(content: &str) -> Result<String> {
    let start = std::time::Instant::now();
-   let tokens = BPE.encode_with_special_tokens(content);
+   let tokens = tokenizer().encode_with_special_tokens(content);
    let encode_time = start.elapsed();
 
    trace!(
@@ -220,7 +350,7 @@ fn trim_to_token_limit(content: &str) -> Result<String> {
    // Trim to MAX_TOKENS
    let trimmed_tokens = &tokens[..MAX_TOKENS];
    let decode_start = std::time::Instant::now();
-   let trimmed_content = BPE.decode(trimmed_tokens.to_vec())?;
+   let trimmed_content = tokenizer().decode(trimmed_tokens.to_vec())?;
    let decode_time = decode_start.elapsed();
 
    trace!(
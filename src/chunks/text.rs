@@ -0,0 +1,54 @@
+use super::types::{Chunk, ChunkKind};
+use anyhow::Result;
+use std::ops::Range;
+use text_splitter::{ChunkConfig, TextSplitter};
+use tracing::trace;
+
+/// Default range of characters per plain text chunk
+pub const DEFAULT_TEXT_CHUNK_CHARS: Range<usize> = 1000..1500;
+
+/// Splits source in a language without a dedicated chunker into plain text blocks on
+/// paragraph and line boundaries, so the file remains searchable without structural parsing
+pub fn extract_text_chunks(source: &str) -> Result<Vec<Chunk>> {
+   extract_text_chunks_in_range(source, DEFAULT_TEXT_CHUNK_CHARS)
+}
+
+/// Splits source into plain text blocks of between `chars.start` and `chars.end` characters
+/// where the paragraph and line boundaries allow it
+pub fn extract_text_chunks_in_range(source: &str, chars: Range<usize>) -> Result<Vec<Chunk>> {
+   trace!(
+      "Starting text chunk extraction for {} chars of source",
+      source.len()
+   );
+
+   let splitter = TextSplitter::new(ChunkConfig::new(chars).with_trim(false));
+   let mut chunks = Vec::new();
+   let mut search_from = 0;
+
+   for chunk_text in splitter.chunks(source) {
+      if chunk_text.trim().is_empty() {
+         continue;
+      }
+
+      let byte_offset = source[search_from..]
+         .find(chunk_text)
+         .map(|offset| offset + search_from)
+         .unwrap_or(search_from);
+      search_from = byte_offset + chunk_text.len();
+
+      let start_line = source[..byte_offset].matches('\n').count() + 1;
+      let end_line = start_line + chunk_text.trim_end().matches('\n').count();
+
+      chunks.push(Chunk {
+         kind: ChunkKind::TextBlock,
+         start_line,
+         end_line,
+         content: chunk_text.to_string(),
+         ..Default::default()
+      });
+   }
+
+   trace!("Text chunk extraction produced {} chunks", chunks.len());
+
+   Ok(chunks)
+}
@@ -0,0 +1,65 @@
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use tiktoken_rs::{CoreBPE, cl100k_base, get_bpe_from_model, o200k_base, p50k_base, r50k_base};
+use tracing::warn;
+
+/// Tokenizer used when `EMBED_TOKENIZER` is unset, the one of OpenAI's embedding models
+pub const DEFAULT_TOKENIZER: &str = "cl100k_base";
+
+/// Tokenizer chunks are measured and trimmed with, loaded once per process from
+/// `EMBED_TOKENIZER`. An unknown name falls back to `DEFAULT_TOKENIZER`.
+static BPE: Lazy<CoreBPE> = Lazy::new(|| {
+   let name = dotenvy::var("EMBED_TOKENIZER").unwrap_or_else(|_| DEFAULT_TOKENIZER.to_string());
+   load_tokenizer(&name).unwrap_or_else(|e| {
+      warn!("{e:#}, using {DEFAULT_TOKENIZER}");
+      cl100k_base().expect("Failed to initialize tiktoken BPE")
+   })
+});
+
+/// Loads a tokenizer by encoding name (`cl100k_base`, `o200k_base`, `p50k_base` or
+/// `r50k_base`, with or without the `_base` suffix) or by the name of a model whose encoding
+/// tiktoken knows, e.g. `gpt-4o`
+pub fn load_tokenizer(name: &str) -> Result<CoreBPE> {
+   match name.trim() {
+      "cl100k" | "cl100k_base" => cl100k_base(),
+      "o200k" | "o200k_base" => o200k_base(),
+      "p50k" | "p50k_base" => p50k_base(),
+      "r50k" | "r50k_base" => r50k_base(),
+      model => get_bpe_from_model(model).with_context(|| format!("Unknown tokenizer {model}")),
+   }
+}
+
+/// The tokenizer selected for this process
+pub fn tokenizer() -> &'static CoreBPE {
+   &BPE
+}
+
+/// Number of tokens `text` takes with the selected tokenizer
+pub fn count_tokens(text: &str) -> usize {
+   BPE.encode_with_special_tokens(text).len()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_tokenizers_count_differently() -> Result<()> {
+      let text =
+         "東京の天気予報によると、明日は一日中雨が降るそうです。\nfn main() {\n                \
+          println!(\"hello\");\n}\n";
+
+      let cl100k = load_tokenizer("cl100k_base")?;
+      let o200k = load_tokenizer("o200k")?;
+      let cl100k_count = cl100k.encode_with_special_tokens(text).len();
+      let o200k_count = o200k.encode_with_special_tokens(text).len();
+      assert_ne!(cl100k_count, o200k_count);
+
+      // a model name selects the encoding of that model
+      let gpt_4o = load_tokenizer("gpt-4o")?;
+      assert_eq!(gpt_4o.encode_with_special_tokens(text).len(), o200k_count);
+
+      assert!(load_tokenizer("not-a-tokenizer").is_err());
+      Ok(())
+   }
+}
@@ -0,0 +1,75 @@
+use super::Chunk;
+use std::fmt;
+
+/// Post-processes chunks after they are extracted and before they are embedded, e.g. to
+/// strip boilerplate or add context. Chunks left empty by a transformer are dropped.
+pub trait ChunkTransformer: Send + Sync + fmt::Debug {
+   fn transform(&self, chunk: &mut Chunk);
+}
+
+/// Leaves chunks unchanged
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopTransformer;
+
+impl ChunkTransformer for NoopTransformer {
+   fn transform(&self, _chunk: &mut Chunk) {
+   }
+}
+
+/// Phrases that mark a comment block as a license or copyright header
+const LICENSE_MARKERS: &[&str] = &[
+   "SPDX-License-Identifier",
+   "Copyright",
+   "Licensed under",
+   "Permission is hereby granted",
+];
+
+/// Removes a license header from the start of a chunk: a leading block of `//` or `/* */`
+/// comments mentioning a license or copyright, along with the blank lines after it. Doc
+/// comments are never removed.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StripLicenseHeader;
+
+impl ChunkTransformer for StripLicenseHeader {
+   fn transform(&self, chunk: &mut Chunk) {
+      let lines: Vec<&str> = chunk.content.lines().collect();
+      let header_len = leading_comment_block_len(&lines);
+
+      let is_license = lines[..header_len]
+         .iter()
+         .any(|line| LICENSE_MARKERS.iter().any(|marker| line.contains(marker)));
+      if !is_license {
+         return;
+      }
+
+      let removed = header_len
+         + lines[header_len..]
+            .iter()
+            .take_while(|line| line.trim().is_empty())
+            .count();
+      chunk.content = lines[removed..].join("\n");
+      chunk.start_line = (chunk.start_line + removed).min(chunk.end_line);
+   }
+}
+
+/// Number of lines of the comment block a chunk starts with, ending at the first line that is
+/// not part of a comment
+fn leading_comment_block_len(lines: &[&str]) -> usize {
+   let mut in_block_comment = false;
+
+   lines
+      .iter()
+      .take_while(|line| {
+         let line = line.trim();
+         if in_block_comment {
+            in_block_comment = !line.contains("*/");
+            return true;
+         }
+         if line.starts_with("/*") && !line.starts_with("/**") && !line.starts_with("/*!") {
+            in_block_comment = !line.contains("*/");
+            return true;
+         }
+         line.starts_with("//") && !line.starts_with("///") && !line.starts_with("//!")
+      })
+      .count()
+}
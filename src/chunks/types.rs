@@ -1,12 +1,84 @@
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
 pub struct Chunk {
    pub kind: ChunkKind,
+   /// Name of the symbol the chunk declares, for chunks of named items
+   pub name: Option<String>,
+   /// Signature of the function the chunk declares, for chunks of functions
+   pub signature: Option<FunctionSignature>,
+   /// Signatures of the methods an impl block declares, for chunks of impl blocks
+   pub methods: Vec<MethodSignature>,
+   /// Index of the notebook cell the chunk comes from, for chunks of Jupyter notebooks.
+   /// Line numbers are relative to the cell in that case.
+   pub cell: Option<usize>,
+   /// Release a changelog chunk documents, e.g. `1.2.3` or `Unreleased`
+   pub version: Option<String>,
+   /// Author and time of the commit a commit chunk describes
+   pub commit: Option<CommitInfo>,
    pub start_line: usize,
    pub end_line: usize,
    pub content: String,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Author and time of a commit, for chunks of commit messages
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CommitInfo {
+   pub author: String,
+   /// Unix time the commit was made
+   pub time: i64,
+}
+
+/// Declaration of a function without its body: qualifiers, name, generics, parameters,
+/// return type and where clause
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct FunctionSignature {
+   /// Qualifiers before `fn`, e.g. `async` or `const unsafe`
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub qualifiers: Option<String>,
+   pub name: String,
+   /// Generic parameters with their angle brackets, e.g. `<T: Clone>`
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub generics: Option<String>,
+   /// Parameters as declared, e.g. `input: &str`, with whitespace collapsed
+   pub params: Vec<String>,
+   pub return_type: Option<String>,
+   /// Where clause including its `where` keyword, without a trailing comma
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub where_clause: Option<String>,
+}
+
+impl fmt::Display for FunctionSignature {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      if let Some(qualifiers) = &self.qualifiers {
+         write!(f, "{qualifiers} ")?;
+      }
+      write!(f, "fn {}", self.name)?;
+      if let Some(generics) = &self.generics {
+         write!(f, "{generics}")?;
+      }
+      write!(f, "({})", self.params.join(", "))?;
+      if let Some(return_type) = &self.return_type {
+         write!(f, " -> {return_type}")?;
+      }
+      if let Some(where_clause) = &self.where_clause {
+         write!(f, " {where_clause}")?;
+      }
+      Ok(())
+   }
+}
+
+/// Signature of a method inside an impl block, with the lines of the method including its
+/// doc comments and attributes
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MethodSignature {
+   pub signature: FunctionSignature,
+   pub start_line: usize,
+   pub end_line: usize,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
 pub enum ChunkKind {
    Struct,
    Enum,
@@ -19,4 +91,39 @@ pub enum ChunkKind {
    Interface,
    TypeAlias,
    Const,
+   // Files without a dedicated chunker
+   #[default]
+   TextBlock,
+   // Test functions and test modules
+   Test,
+   // Commit messages from the repository's history
+   Commit,
+   // Whole files, aggregated from their chunks for coarse-grained retrieval
+   File,
+   // Dependencies declared in a Cargo manifest
+   Dependencies,
+}
+
+impl ChunkKind {
+   /// Human readable name of the structural role a chunk plays, used to describe chunks to
+   /// the embedding model
+   pub fn description(&self) -> &'static str {
+      match self {
+         ChunkKind::Struct => "struct",
+         ChunkKind::Enum => "enum",
+         ChunkKind::Function => "function",
+         ChunkKind::Impl => "impl block",
+         ChunkKind::Comment => "comment",
+         ChunkKind::MarkdownSection => "section",
+         ChunkKind::Class => "class",
+         ChunkKind::Interface => "interface",
+         ChunkKind::TypeAlias => "type alias",
+         ChunkKind::Const => "constant",
+         ChunkKind::TextBlock => "text block",
+         ChunkKind::Test => "test",
+         ChunkKind::Commit => "commit message",
+         ChunkKind::File => "file",
+         ChunkKind::Dependencies => "dependency list",
+      }
+   }
 }
@@ -1,8 +1,9 @@
-use super::types::{Chunk, ChunkKind};
+use super::{
+   tokenizer::tokenizer,
+   types::{Chunk, ChunkKind},
+};
 use anyhow::{Context, Result};
-use once_cell::sync::Lazy;
 use std::{collections::HashSet, ops::RangeInclusive};
-use tiktoken_rs::{CoreBPE, cl100k_base};
 use tracing::trace;
 use tree_sitter::Node;
 
@@ -12,9 +13,6 @@ const NODES_TO_IGNORE: [&str; 2] = ["import_statement", "import_alias"];
 /// Maximum token limit for chunks
 const MAX_TOKENS: usize = 8192;
 
-/// Lazy-initialized BPE tokenizer to avoid repeated initialization
-static BPE: Lazy<CoreBPE> = Lazy::new(|| cl100k_base().expect("Failed to initialize tiktoken BPE"));
-
 /// Parses TypeScript source code into semantic chunks preserving documentation context
 /// and respecting token limits for effective embedding generation
 pub fn extract_typescript_chunks(source: &str) -> Result<Vec<Chunk>> {
@@ -152,9 +150,11 @@ fn process_node(node: &Node, source: &str, processed_lines: &mut HashSet<usize>)
             let content = trim_to_token_limit(&content).unwrap_or_default();
             return Some(Chunk {
                kind: ChunkKind::Const,
+               name: declaration_name(node, source),
                start_line: start_line + 1,
                end_line: node.end_position().row + 1,
                content,
+               ..Default::default()
             });
          }
 
@@ -183,9 +183,11 @@ fn process_node(node: &Node, source: &str, processed_lines: &mut HashSet<usize>)
 
                   return Some(Chunk {
                      kind,
+                     name: declaration_name(&child, source),
                      start_line: start_line + 1,
                      end_line: node.end_position().row + 1,
                      content,
+                     ..Default::default()
                   });
                }
                _ => {}
@@ -210,12 +212,30 @@ fn process_node(node: &Node, source: &str, processed_lines: &mut HashSet<usize>)
 
    Some(Chunk {
       kind,
+      name: declaration_name(node, source),
       start_line: start_line + 1,
       end_line: end_line + 1,
       content,
+      ..Default::default()
    })
 }
 
+/// Name of the symbol a declaration introduces, looking through export statements,
+/// decorated definitions and variable declarations
+fn declaration_name(node: &Node, source: &str) -> Option<String> {
+   if let Some(name) = node.child_by_field_name("name") {
+      return name.utf8_text(source.as_bytes()).ok().map(str::to_string);
+   }
+
+   let mut cursor = node.walk();
+   node
+      .named_children(&mut cursor)
+      .filter(|child| {
+         child.kind().ends_with("declaration") || child.kind() == "variable_declarator"
+      })
+      .find_map(|child| declaration_name(&child, source))
+}
+
 fn is_const_or_export(node: &Node, source: &str) -> bool {
    // Check if this is an exported const or let declaration
    let text = node.utf8_text(source.as_bytes()).unwrap_or("");
@@ -277,6 +297,7 @@ fn handle_comment(
       start_line: start_line + 1,
       end_line: end_line + 1,
       content,
+      ..Default::default()
    })
 }
 
@@ -384,9 +405,11 @@ fn process_decorated_node(
 
    Some(Chunk {
       kind,
+      name: declaration_name(&actual_node, source),
       start_line: start_line + 1,
       end_line: end_line + 1,
       content,
+      ..Default::default()
    })
 }
 
@@ -426,9 +449,11 @@ fn process_decorated_export(
 
       return Some(Chunk {
          kind,
+         name: declaration_name(node, source),
          start_line: start_line + 1,
          end_line: end_line + 1,
          content,
+         ..Default::default()
       });
    }
 
@@ -476,9 +501,11 @@ fn process_decorated_definition(
 
       return Some(Chunk {
          kind,
+         name: declaration_name(node, source),
          start_line: start_line + 1,
          end_line: end_line + 1,
          content,
+         ..Default::default()
       });
    }
 
@@ -487,7 +514,7 @@ fn process_decorated_definition(
 
 fn trim_to_token_limit(content: &str) -> Result<String> {
    let start = std::time::Instant::now();
-   let tokens = BPE.encode_with_special_tokens(content);
+   let tokens = tokenizer().encode_with_special_tokens(content);
    let encode_time = start.elapsed();
 
    trace!(
@@ -504,7 +531,7 @@ fn trim_to_token_limit(content: &str) -> Result<String> {
    // Trim to MAX_TOKENS
    let trimmed_tokens = &tokens[..MAX_TOKENS];
    let decode_start = std::time::Instant::now();
-   let trimmed_content = BPE.decode(trimmed_tokens.to_vec())?;
+   let trimmed_content = tokenizer().decode(trimmed_tokens.to_vec())?;
    let decode_time = decode_start.elapsed();
 
    trace!(
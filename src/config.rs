@@ -1,4 +1,68 @@
+use crate::{
+   chunks::{
+      text::DEFAULT_TEXT_CHUNK_CHARS,
+      transform::{ChunkTransformer, NoopTransformer, StripLicenseHeader},
+   },
+   embedder::ApiKey,
+};
+use anyhow::Result;
+use schemars::JsonSchema;
 use serde::Deserialize;
+use std::{ops::Range, path::Path, sync::Arc, time::Duration};
+use tracing::warn;
+
+/// File at the root of a repository in which its authors set chunking preferences
+pub const REPO_CONFIG_FILE: &str = ".embed-config.toml";
+
+/// Directories skipped when walking a repository: dependency caches, build output and VCS
+/// metadata that would otherwise embed third-party or generated code
+pub const DEFAULT_EXCLUDED_DIRS: &[&str] = &[
+   ".git",
+   "node_modules",
+   "vendor",
+   "target",
+   "dist",
+   "build",
+   ".venv",
+   "__pycache__",
+   ".ipynb_checkpoints",
+];
+
+/// Directories excluded on top of `DEFAULT_EXCLUDED_DIRS` in repositories written mostly in a
+/// language, where they can only hold build output or dependencies. Names such as `bin` or
+/// `out` are only safe to skip when the language is known to use them that way.
+fn language_excluded_dirs(language: &str) -> &'static [&'static str] {
+   match language {
+      "JavaScript" | "TypeScript" => &[".next", ".nuxt", ".turbo", "bower_components", "coverage"],
+      "Python" => &[
+         "venv",
+         ".tox",
+         ".mypy_cache",
+         ".pytest_cache",
+         "site-packages",
+      ],
+      "Java" | "Kotlin" => &[".gradle", "out"],
+      "Ruby" => &[".bundle"],
+      "C#" => &["bin", "obj"],
+      _ => &[],
+   }
+}
+
+/// Average line length, in characters, above which a file is considered minified or generated
+pub const DEFAULT_MINIFIED_LINE_CHARS: usize = 500;
+
+/// How files that look minified or generated are chunked. Such files are a few enormous lines,
+/// which structural chunkers return whole and which are then truncated to the token limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MinifiedPolicy {
+   /// Chunk them with the chunker of their language, like any other file
+   Keep,
+   /// Split them into windows of `text_chunk_chars` characters regardless of their structure
+   #[default]
+   Split,
+   /// Leave them out of the embedding
+   Skip,
+}
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
@@ -12,6 +76,98 @@ pub struct EmbeddingConfig {
    pub chunk_size: usize,
    pub chunk_overlap: f32,
    pub batch_size: usize,
+   /// Template for a descriptor prepended to each chunk before embedding (e.g. "Rust
+   /// function:"). Supports the `{language}` and `{kind}` placeholders. Disabled when `None`.
+   pub kind_prefix_template: Option<String>,
+   /// Text prepended to every chunk before embedding, for asymmetric models that expect
+   /// documents to be marked (e.g. "passage: "). Empty for OpenAI models.
+   pub document_prefix: String,
+   /// Text prepended to queries before embedding them (e.g. "query: "). Collections record
+   /// the prefix they were embedded for, which takes precedence at query time.
+   pub query_prefix: String,
+   /// Whether files with an unrecognized extension are chunked after detecting their language
+   /// from the file name or shebang. Disabled by default, in which case they are skipped.
+   pub detect_languages: bool,
+   /// Names of directories that are never walked, wherever they appear in the repository
+   pub excluded_dirs: Vec<String>,
+   /// Total number of attempts made to clone a repository when transient network errors occur
+   pub clone_attempts: u32,
+   /// Number of commits fetched when cloning a repository; zero fetches the full history
+   pub clone_depth: u32,
+   /// Time after which a clone that has not finished is aborted
+   pub clone_timeout: Duration,
+   /// Maximum number of characters of chunk content stored for display. Longer content is
+   /// truncated in the payload after embedding, so the embedded text is unaffected.
+   pub max_stored_chars: Option<usize>,
+   /// Whether embeddings are requested base64-encoded, which roughly halves response sizes
+   /// and parse time compared to JSON float arrays. Enabled by default.
+   pub base64_encoding: bool,
+   /// Store a sparse keyword vector next to each embedding and fuse keyword and semantic
+   /// rankings at query time. Only applies to newly created collections.
+   pub hybrid_search: bool,
+   /// Create a full-text index on chunk content so queries can filter by the words chunks
+   /// contain, at the cost of extra storage. Only applies to newly created collections.
+   pub content_text_index: bool,
+   /// Whether newly created collections keep their vectors and HNSW index on disk instead of
+   /// in RAM, for repositories too large to hold in memory at the cost of slower searches
+   pub on_disk: bool,
+   /// Whether chunk content is normalized before embedding: line endings are unified and
+   /// trailing whitespace removed. The stored content is left byte-exact, and content hashes
+   /// ignore whitespace regardless.
+   pub normalize_content: bool,
+   /// Whether normalization also collapses runs of blank lines into a single blank line
+   pub collapse_blank_lines: bool,
+   /// Whether test functions and test modules are left out of the embedding, which keeps test
+   /// code from crowding out the API it exercises. Enabled by default.
+   pub exclude_tests: bool,
+   /// Whether likely secrets such as API keys and tokens are masked in chunk content before
+   /// it is embedded and stored
+   pub redact_secrets: bool,
+   /// Whether chunks with identical content, e.g. from vendored copies of a module, are
+   /// stored once, listing every file they appear in
+   pub dedup_chunks: bool,
+   /// Whether each file is also embedded as a whole, aggregated from its chunks, in addition
+   /// to the chunks themselves so queries can match at file level
+   pub file_chunks: bool,
+   /// Whether the signature of each function and method is also embedded on its own, so
+   /// queries shaped like a signature are not diluted by function bodies
+   pub signature_chunks: bool,
+   /// File extensions (without the dot) that are chunked. All supported files are chunked
+   /// when `None`.
+   pub extensions: Option<Vec<String>>,
+   /// Glob patterns, relative to the repository root, of files that are never chunked
+   pub exclude_globs: Vec<String>,
+   /// Chunks with fewer characters than this, ignoring surrounding whitespace, are dropped
+   pub min_chunk_chars: usize,
+   /// Chunks kept per file, so a pathological file such as generated bindings cannot dominate
+   /// a collection. Unlimited when `None`, which `EMBED_MAX_CHUNKS_PER_FILE=0` also means.
+   pub max_chunks_per_file: Option<usize>,
+   /// Files whose non-blank lines average more characters than this are treated as minified
+   pub minified_line_chars: usize,
+   /// How files treated as minified are chunked
+   pub minified_policy: MinifiedPolicy,
+   /// Applied to every chunk after extraction and before embedding
+   pub chunk_transformer: Arc<dyn ChunkTransformer>,
+   /// Range of characters per chunk for files split as plain text, which are files without a
+   /// dedicated chunker
+   pub text_chunk_chars: Range<usize>,
+   /// Whether commit messages are embedded alongside the files. Repositories are then cloned
+   /// with their full history, regardless of `clone_depth`.
+   pub embed_commits: bool,
+   /// Maximum number of commits embedded, newest first
+   pub max_commits: usize,
+   /// Number of most recent commits whose diff is embedded with their message
+   pub commit_diffs: usize,
+   /// Whether the time of the last commit changing each file is stored with its chunks, so
+   /// queries can favor recently changed code. Repositories are then cloned with their full
+   /// history, regardless of `clone_depth`.
+   pub file_recency: bool,
+   /// Collections kept in Qdrant. Once a new embed exceeds it, the least recently queried
+   /// collections are deleted. Unlimited when `None`.
+   pub max_collections: Option<usize>,
+   /// Key of the caller a request embeds for. Never read from the environment; the server's
+   /// `OPENAI_API_KEY` is used when unset.
+   pub api_key: Option<ApiKey>,
 }
 
 impl Default for EmbeddingConfig {
@@ -21,6 +177,355 @@ impl Default for EmbeddingConfig {
          chunk_size: 1000,
          chunk_overlap: 0.0,
          batch_size: 32,
+         kind_prefix_template: None,
+         document_prefix: String::new(),
+         query_prefix: String::new(),
+         detect_languages: false,
+         excluded_dirs: DEFAULT_EXCLUDED_DIRS
+            .iter()
+            .map(|dir| dir.to_string())
+            .collect(),
+         clone_attempts: 3,
+         clone_depth: 1,
+         clone_timeout: Duration::from_secs(600),
+         max_stored_chars: None,
+         base64_encoding: true,
+         hybrid_search: false,
+         content_text_index: false,
+         on_disk: false,
+         normalize_content: true,
+         collapse_blank_lines: false,
+         exclude_tests: true,
+         redact_secrets: false,
+         dedup_chunks: false,
+         file_chunks: false,
+         signature_chunks: false,
+         extensions: None,
+         exclude_globs: Vec::new(),
+         min_chunk_chars: 0,
+         max_chunks_per_file: None,
+         minified_line_chars: DEFAULT_MINIFIED_LINE_CHARS,
+         minified_policy: MinifiedPolicy::default(),
+         chunk_transformer: Arc::new(NoopTransformer),
+         text_chunk_chars: DEFAULT_TEXT_CHUNK_CHARS,
+         embed_commits: false,
+         max_commits: 1000,
+         commit_diffs: 0,
+         file_recency: false,
+         max_collections: None,
+         api_key: None,
+      }
+   }
+}
+
+impl EmbeddingConfig {
+   /// Builds the embedding config from the environment, falling back to defaults for anything
+   /// that is not set
+   pub fn from_env() -> Self {
+      let defaults = Self::default();
+
+      Self {
+         kind_prefix_template: dotenvy::var("EMBED_KIND_PREFIX_TEMPLATE").ok(),
+         document_prefix: dotenvy::var("EMBED_DOCUMENT_PREFIX").unwrap_or_default(),
+         query_prefix: dotenvy::var("EMBED_QUERY_PREFIX").unwrap_or_default(),
+         detect_languages: dotenvy::var("EMBED_DETECT_LANGUAGES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(false),
+         // a comma-separated list replaces the defaults; an empty value disables exclusions
+         excluded_dirs: dotenvy::var("EMBED_EXCLUDED_DIRS")
+            .map(|value| {
+               value
+                  .split(',')
+                  .map(str::trim)
+                  .filter(|dir| !dir.is_empty())
+                  .map(str::to_string)
+                  .collect()
+            })
+            .unwrap_or(defaults.excluded_dirs.clone()),
+         clone_attempts: dotenvy::var("CLONE_ATTEMPTS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.clone_attempts),
+         clone_depth: dotenvy::var("CLONE_DEPTH")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.clone_depth),
+         clone_timeout: dotenvy::var("CLONE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map_or(defaults.clone_timeout, Duration::from_secs),
+         max_stored_chars: dotenvy::var("EMBED_MAX_STORED_CHARS")
+            .ok()
+            .and_then(|value| value.parse().ok()),
+         base64_encoding: dotenvy::var("EMBED_BASE64")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.base64_encoding),
+         hybrid_search: dotenvy::var("EMBED_HYBRID")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.hybrid_search),
+         content_text_index: dotenvy::var("EMBED_CONTENT_TEXT_INDEX")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.content_text_index),
+         on_disk: dotenvy::var("QDRANT_ON_DISK")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.on_disk),
+         normalize_content: dotenvy::var("EMBED_NORMALIZE")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.normalize_content),
+         collapse_blank_lines: dotenvy::var("EMBED_COLLAPSE_BLANK_LINES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.collapse_blank_lines),
+         exclude_tests: dotenvy::var("EMBED_EXCLUDE_TESTS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.exclude_tests),
+         redact_secrets: dotenvy::var("EMBED_REDACT_SECRETS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.redact_secrets),
+         dedup_chunks: dotenvy::var("EMBED_DEDUP_CHUNKS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.dedup_chunks),
+         file_chunks: dotenvy::var("EMBED_FILE_CHUNKS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.file_chunks),
+         signature_chunks: dotenvy::var("EMBED_SIGNATURE_CHUNKS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.signature_chunks),
+         max_chunks_per_file: dotenvy::var("EMBED_MAX_CHUNKS_PER_FILE")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|&max_chunks| max_chunks > 0),
+         minified_line_chars: dotenvy::var("EMBED_MINIFIED_LINE_CHARS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.minified_line_chars),
+         minified_policy: match dotenvy::var("EMBED_MINIFIED_POLICY").ok().as_deref() {
+            Some("keep") => MinifiedPolicy::Keep,
+            Some("split") => MinifiedPolicy::Split,
+            Some("skip") => MinifiedPolicy::Skip,
+            _ => defaults.minified_policy,
+         },
+         chunk_transformer: match dotenvy::var("EMBED_STRIP_LICENSE_HEADERS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+         {
+            Some(true) => Arc::new(StripLicenseHeader),
+            _ => defaults.chunk_transformer.clone(),
+         },
+         // given as `min-max`, e.g. `800-1200`
+         text_chunk_chars: dotenvy::var("EMBED_TEXT_CHUNK_CHARS")
+            .ok()
+            .and_then(|value| {
+               let (min, max) = value.split_once('-')?;
+               let (min, max) = (min.trim().parse().ok()?, max.trim().parse().ok()?);
+               (0 < min && min <= max).then_some(min..max)
+            })
+            .unwrap_or(defaults.text_chunk_chars.clone()),
+         embed_commits: dotenvy::var("EMBED_COMMITS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.embed_commits),
+         max_commits: dotenvy::var("EMBED_MAX_COMMITS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.max_commits),
+         commit_diffs: dotenvy::var("EMBED_COMMIT_DIFFS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.commit_diffs),
+         file_recency: dotenvy::var("EMBED_FILE_RECENCY")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(defaults.file_recency),
+         max_collections: dotenvy::var("MAX_COLLECTIONS")
+            .ok()
+            .and_then(|value| value.parse().ok()),
+         ..defaults
+      }
+   }
+
+   /// Excludes the directories that hold build output or dependencies in repositories written
+   /// mostly in `language`, as named by `LanguageCounts::primary`. Those of the common
+   /// toolchains, such as `node_modules` and `target`, are in `DEFAULT_EXCLUDED_DIRS` already.
+   pub fn exclude_language_dirs(&mut self, language: &str) {
+      for dir in language_excluded_dirs(language) {
+         if !self.excluded_dirs.iter().any(|excluded| excluded == dir) {
+            self.excluded_dirs.push(dir.to_string());
+         }
       }
    }
 }
+
+/// Chunking preferences for a single repository, either shipped by its authors in
+/// `.embed-config.toml` or passed with an embed request. Unset fields keep the configured
+/// value.
+#[derive(Debug, Default, Clone, Deserialize, JsonSchema)]
+pub struct ChunkPreferences {
+   #[schemars(
+      description = "File extensions to chunk, without the dot (e.g. ['rs', 'md']). Overrides the \
+                     repository's own .embed-config.toml"
+   )]
+   pub extensions: Option<Vec<String>>,
+   #[schemars(
+      description = "Glob patterns of files to skip, relative to the repository root (e.g. \
+                     ['examples/**', '*.generated.ts']). Overrides the repository's own \
+                     .embed-config.toml"
+   )]
+   pub exclude_globs: Option<Vec<String>>,
+   #[schemars(
+      description = "Minimum number of characters for a chunk to be embedded. Overrides the \
+                     repository's own .embed-config.toml"
+   )]
+   pub min_chunk_chars: Option<usize>,
+}
+
+impl ChunkPreferences {
+   const KEYS: &[&str] = &["extensions", "exclude_globs", "min_chunk_chars"];
+
+   /// Reads the preferences a repository ships in its root. A missing file yields no
+   /// preferences; an invalid one is reported and ignored, so a mistake by the repository's
+   /// authors never fails an embed.
+   pub fn from_repo(repo_root: &Path) -> Self {
+      let path = repo_root.join(REPO_CONFIG_FILE);
+      let Ok(contents) = std::fs::read_to_string(&path) else {
+         return Self::default();
+      };
+
+      Self::from_toml(&contents).unwrap_or_else(|e| {
+         warn!("Ignoring invalid {REPO_CONFIG_FILE}: {e:#}");
+         Self::default()
+      })
+   }
+
+   /// Parses preferences from TOML, warning about unknown keys and dropping invalid globs
+   pub fn from_toml(contents: &str) -> Result<Self> {
+      let table: toml::Table = toml::from_str(contents)?;
+      for key in table
+         .keys()
+         .filter(|key| !Self::KEYS.contains(&key.as_str()))
+      {
+         warn!(
+            "Unknown key `{key}` in {REPO_CONFIG_FILE}, expected one of {:?}",
+            Self::KEYS
+         );
+      }
+
+      let preferences: Self = table.try_into()?;
+      Ok(preferences.validated())
+   }
+
+   /// Normalizes extensions to be given without a leading dot and drops glob patterns that do
+   /// not parse
+   pub fn validated(mut self) -> Self {
+      if let Some(extensions) = &mut self.extensions {
+         for extension in extensions.iter_mut() {
+            *extension = extension.trim_start_matches('.').to_string();
+         }
+      }
+      if let Some(globs) = &mut self.exclude_globs {
+         globs.retain(|pattern| match glob::Pattern::new(pattern) {
+            Ok(_) => true,
+            Err(e) => {
+               warn!("Ignoring invalid exclude glob `{pattern}`: {e}");
+               false
+            }
+         });
+      }
+      self
+   }
+
+   /// Applies the preferences that are set to the config
+   pub fn apply(&self, config: &mut EmbeddingConfig) {
+      if let Some(extensions) = &self.extensions {
+         config.extensions = Some(extensions.clone());
+      }
+      if let Some(exclude_globs) = &self.exclude_globs {
+         config.exclude_globs = exclude_globs.clone();
+      }
+      if let Some(min_chunk_chars) = self.min_chunk_chars {
+         config.min_chunk_chars = min_chunk_chars;
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_language_dirs_extend_the_excluded_dirs() {
+      let mut config = EmbeddingConfig {
+         excluded_dirs: vec!["coverage".to_string()],
+         ..EmbeddingConfig::default()
+      };
+
+      config.exclude_language_dirs("TypeScript");
+      assert_eq!(
+         config.excluded_dirs,
+         vec!["coverage", ".next", ".nuxt", ".turbo", "bower_components"]
+      );
+
+      // build directories of other languages stay walked
+      config.exclude_language_dirs("Rust");
+      assert!(!config.excluded_dirs.iter().any(|dir| dir == "bin"));
+      assert_eq!(config.excluded_dirs.len(), 5);
+   }
+
+   #[test]
+   fn test_request_preferences_override_repo_file() -> Result<()> {
+      let repo_file = ChunkPreferences::from_toml(
+         r#"
+         extensions = [".rs", "md"]
+         exclude_globs = ["examples/**", "[invalid"]
+         min_chunk_chars = 40
+         chunk_size = 10
+         "#,
+      )?;
+      assert_eq!(
+         repo_file.extensions,
+         Some(vec!["rs".to_string(), "md".to_string()])
+      );
+      assert_eq!(
+         repo_file.exclude_globs,
+         Some(vec!["examples/**".to_string()])
+      );
+
+      let request = ChunkPreferences {
+         min_chunk_chars: Some(10),
+         ..Default::default()
+      };
+
+      let mut config = EmbeddingConfig::default();
+      repo_file.apply(&mut config);
+      request.apply(&mut config);
+
+      assert_eq!(config.extensions, repo_file.extensions);
+      assert_eq!(config.exclude_globs, vec!["examples/**".to_string()]);
+      assert_eq!(config.min_chunk_chars, 10);
+      Ok(())
+   }
+
+   #[test]
+   fn test_invalid_repo_file_is_ignored() -> Result<()> {
+      let repo = tempfile::tempdir()?;
+      std::fs::write(
+         repo.path().join(REPO_CONFIG_FILE),
+         "min_chunk_chars = \"many\"",
+      )?;
+
+      let preferences = ChunkPreferences::from_repo(repo.path());
+      assert!(preferences.min_chunk_chars.is_none());
+      Ok(())
+   }
+}
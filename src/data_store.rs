@@ -1,16 +1,435 @@
-use crate::{config::EmbeddingConfig, utils::gen_table_name_for_repo};
-use anyhow::{Context, Result};
+use crate::{
+   chunks::{ChunkKind, FunctionSignature},
+   config::EmbeddingConfig,
+   embedder::EMBEDDING_MODEL,
+   sparse::SparseVector,
+   utils::{content_hash, gen_table_name_for_repo},
+};
+use anyhow::{Context, Result, bail};
 use chrono::{DateTime, Utc};
 use qdrant_client::{
    Payload, Qdrant,
    qdrant::{
-      CreateCollectionBuilder, Distance, GetPointsBuilder, PointStruct, SearchPointsBuilder,
-      UpsertPointsBuilder, VectorParamsBuilder,
+      Condition, CountPointsBuilder, CreateAliasBuilder, CreateCollectionBuilder,
+      CreateFieldIndexCollectionBuilder, DeletePointsBuilder, Distance, FieldType, Filter, Fusion,
+      GetPointsBuilder, HnswConfigDiffBuilder, Modifier, NamedVectors, PointId, PointStruct,
+      PointsIdsList, PrefetchQueryBuilder, Query, QueryPointsBuilder, ScoredPoint,
+      ScrollPointsBuilder, SearchParamsBuilder, SearchPointsBuilder, SetPayloadPointsBuilder,
+      SparseVectorParamsBuilder, SparseVectorsConfigBuilder, TextIndexParamsBuilder, TokenizerType,
+      UpsertPointsBuilder, Vector, VectorInput, VectorParamsBuilder, Vectors, VectorsConfigBuilder,
+      point_id::PointIdOptions, vectors_config, vectors_output::VectorsOptions,
    },
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tracing::{debug, trace};
+use std::{collections::BTreeMap, future::Future, time::Duration};
+use tokio::sync::OnceCell;
+use tracing::{debug, info, trace, warn};
+
+/// Name of the dense embedding vector in hybrid collections
+const DENSE_VECTOR: &str = "dense";
+/// Name of the sparse keyword vector in hybrid collections
+const SPARSE_VECTOR: &str = "sparse";
+
+/// Largest HNSW `ef` a search may request. Higher values visit more of the graph, improving
+/// recall at the cost of latency that grows roughly linearly with `ef`.
+pub const MAX_SEARCH_EF: u64 = 512;
+
+/// Attempts made to create a collection when creation fails and the collection is not yet
+/// visible, which happens when another embed is creating it at the same time
+const CREATE_COLLECTION_ATTEMPTS: u32 = 3;
+/// Delay between attempts to create a collection
+const CREATE_COLLECTION_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Vector schema of a collection
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CollectionLayout {
+   vector_size: u64,
+   /// Whether points carry named dense and sparse vectors rather than a single dense vector
+   hybrid: bool,
+   /// Whether dense vectors and their HNSW index are stored on disk rather than in RAM
+   on_disk: bool,
+}
+
+/// Creates a collection with `create`, tolerating a concurrent creator. Two embeds of the same
+/// repository can both find the collection missing; the one losing the race fails to create
+/// it, in which case the collection it finds afterwards is used if its dimension matches.
+/// Failures while the collection is not yet visible are retried.
+async fn create_or_reuse_collection<C, CF, E, EF>(
+   collection_name: &str,
+   layout: CollectionLayout,
+   create: C,
+   existing_layout: E,
+) -> Result<CollectionLayout>
+where
+   C: Fn() -> CF,
+   CF: Future<Output = Result<bool>>,
+   E: Fn() -> EF,
+   EF: Future<Output = Result<Option<CollectionLayout>>>,
+{
+   let mut attempt = 1;
+   loop {
+      let error = match create().await {
+         Ok(true) => return Ok(layout),
+         Ok(false) => anyhow::anyhow!("Qdrant did not acknowledge the collection creation"),
+         Err(e) => e,
+      };
+
+      match existing_layout().await {
+         Ok(Some(existing)) if existing.vector_size == layout.vector_size => {
+            debug!("Collection {collection_name} was created concurrently, reusing it");
+            return Ok(existing);
+         }
+         Ok(Some(existing)) => bail!(
+            "Collection {collection_name} was created concurrently with dimension {}, expected {}",
+            existing.vector_size,
+            layout.vector_size
+         ),
+         _ if attempt < CREATE_COLLECTION_ATTEMPTS => {
+            warn!(
+               "Failed to create collection {collection_name} (attempt \
+                {attempt}/{CREATE_COLLECTION_ATTEMPTS}), retrying: {error:#}"
+            );
+            attempt += 1;
+            tokio::time::sleep(CREATE_COLLECTION_RETRY_DELAY).await;
+         }
+         _ => {
+            return Err(error)
+               .with_context(|| format!("Failed to create collection {collection_name}"));
+         }
+      }
+   }
+}
+
+/// Opens the collection a store is about to write embeddings to, creating it with `layout`,
+/// whose dimension is that of the first embedding, when `existing_layout` finds none. Returns
+/// the layout written with and whether the collection had to be created.
+async fn open_or_create_collection<C, CF, E, EF>(
+   collection_name: &str,
+   layout: CollectionLayout,
+   create: C,
+   existing_layout: E,
+) -> Result<(CollectionLayout, bool)>
+where
+   C: Fn() -> CF,
+   CF: Future<Output = Result<bool>>,
+   E: Fn() -> EF,
+   EF: Future<Output = Result<Option<CollectionLayout>>>,
+{
+   if let Some(existing) = existing_layout().await? {
+      return Ok((existing, false));
+   }
+
+   debug!("Creating collection {collection_name} with {layout:?}");
+   let layout =
+      create_or_reuse_collection(collection_name, layout, create, existing_layout).await?;
+   Ok((layout, true))
+}
+
+/// Reads the layout of an existing collection with `existing_layout`. Fails when the
+/// collection does not exist, since only storing embeddings may create it.
+async fn open_collection<E, EF>(
+   collection_name: &str,
+   existing_layout: E,
+) -> Result<CollectionLayout>
+where
+   E: FnOnce() -> EF,
+   EF: Future<Output = Result<Option<CollectionLayout>>>,
+{
+   existing_layout().await?.with_context(|| {
+      format!("Collection {collection_name} does not exist, the repository has not been embedded")
+   })
+}
+
+/// Builds the request creating a cosine-distance collection with the given vector dimension.
+/// Hybrid collections store the embedding as a named dense vector next to a sparse keyword
+/// vector weighted by IDF.
+fn collection_request(collection_name: &str, layout: CollectionLayout) -> CreateCollectionBuilder {
+   let dense =
+      VectorParamsBuilder::new(layout.vector_size, Distance::Cosine).on_disk(layout.on_disk);
+   let mut request = CreateCollectionBuilder::new(collection_name);
+   if layout.on_disk {
+      request = request.hnsw_config(HnswConfigDiffBuilder::default().on_disk(true));
+   }
+   if !layout.hybrid {
+      return request.vectors_config(dense);
+   }
+
+   let mut vectors_config = VectorsConfigBuilder::default();
+   vectors_config.add_named_vector_params(DENSE_VECTOR, dense);
+   let mut sparse_vectors_config = SparseVectorsConfigBuilder::default();
+   sparse_vectors_config.add_named_vector_params(
+      SPARSE_VECTOR,
+      SparseVectorParamsBuilder::default().modifier(Modifier::Idf),
+   );
+
+   request
+      .vectors_config(vectors_config)
+      .sparse_vectors_config(sparse_vectors_config)
+}
+
+/// Builds the dense similarity search request, asking for `search_ef` candidates to be
+/// explored in the HNSW graph, at most `MAX_SEARCH_EF`, instead of Qdrant's default
+fn search_request(
+   collection_name: &str,
+   query_vector: Vec<f32>,
+   max_results: u64,
+   layout: CollectionLayout,
+   filter: &ChunkFilter,
+   search_ef: Option<u64>,
+) -> SearchPointsBuilder {
+   let mut search_req =
+      SearchPointsBuilder::new(collection_name, query_vector, max_results).with_payload(true);
+   if layout.hybrid {
+      search_req = search_req.vector_name(DENSE_VECTOR);
+   }
+   if let Some(filter) = filter.filter() {
+      search_req = search_req.filter(filter);
+   }
+   if let Some(ef) = search_ef {
+      search_req = search_req.params(SearchParamsBuilder::default().hnsw_ef(ef.min(MAX_SEARCH_EF)));
+   }
+   search_req
+}
+
+/// Builds the request creating a full-text index on chunk content, splitting it into
+/// lowercase words so `ChunkFilter::text` matches regardless of case
+fn content_index_request(collection_name: &str) -> CreateFieldIndexCollectionBuilder {
+   CreateFieldIndexCollectionBuilder::new(collection_name, "content", FieldType::Text)
+      .field_index_params(TextIndexParamsBuilder::new(TokenizerType::Word).lowercase(true))
+      .wait(true)
+}
+
+/// Converts search hits into results, skipping the metadata point
+fn to_search_results(points: Vec<ScoredPoint>) -> Result<Vec<SearchResult>> {
+   let mut results = Vec::new();
+
+   for point in points {
+      if !point.payload.contains_key("content") {
+         trace!("skipping result that does not have a content field (probably metadata)");
+         continue;
+      }
+      let payload = serde_json::Value::from(Payload::from(point.payload));
+      let chunk: ChunkPayload = serde_json::from_value(payload)
+         .context("could not convert the point payload into a `ChunkPayload`")?;
+
+      results.push(SearchResult {
+         score: point.score,
+         chunk,
+      });
+   }
+
+   Ok(results)
+}
+
+/// Builds a filter matching the chunks of any of the given source files
+fn file_path_filter(file_paths: &[String]) -> Filter {
+   Filter::must([Condition::matches("file_path", file_paths.to_vec())])
+}
+
+/// Builds a filter matching the chunks found in any of the given source files, including
+/// deduplicated chunks that list them among their `sources` only
+fn file_sources_filter(file_paths: &[String]) -> Filter {
+   Filter::should([
+      Condition::matches("file_path", file_paths.to_vec()),
+      Condition::matches("sources", file_paths.to_vec()),
+   ])
+}
+
+/// Level of detail of a stored chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+   /// A single item such as a function or a section, as extracted by the chunkers
+   Chunk,
+   /// A whole file, aggregated from its chunks
+   File,
+   /// The signature of a function alone, for searches by parameter and return types
+   Signature,
+}
+
+impl Granularity {
+   /// Filter matching the points of this granularity. Chunks stored before granularities were
+   /// recorded count as `Chunk`.
+   fn filter(self) -> Filter {
+      match self {
+         Granularity::Chunk => Filter::must_not([Condition::matches(
+            "granularity",
+            vec!["file".to_string(), "signature".to_string()],
+         )]),
+         Granularity::File => Filter::must([Condition::matches("granularity", "file".to_string())]),
+         Granularity::Signature => {
+            Filter::must([Condition::matches("granularity", "signature".to_string())])
+         }
+      }
+   }
+}
+
+/// Points stored per upsert request by `DataStore::add_embeddings_batch`
+pub const UPSERT_BATCH_SIZE: usize = 256;
+
+/// Builds the point storing a chunk with its embedding, adding the keyword vector of the
+/// content in hybrid collections
+fn chunk_point(
+   id: u64,
+   chunk: &ChunkPayload,
+   vector: Vec<f32>,
+   layout: CollectionLayout,
+) -> Result<PointStruct> {
+   let payload = Payload::try_from(serde_json::to_value(chunk)?)?;
+
+   let vectors: Vectors = if layout.hybrid {
+      let mut named = NamedVectors::default().add_vector(DENSE_VECTOR, vector);
+      let sparse = SparseVector::from_text(&chunk.content);
+      if !sparse.is_empty() {
+         named = named.add_vector(
+            SPARSE_VECTOR,
+            Vector::new_sparse(sparse.indices, sparse.values),
+         );
+      }
+      named.into()
+   } else {
+      vector.into()
+   };
+
+   Ok(PointStruct::new(id, vectors, payload))
+}
+
+/// Restricts a search to some of the chunks of a collection
+#[derive(Debug, Clone, Default)]
+pub struct ChunkFilter {
+   /// Only chunks of this granularity, or all of them when `None`
+   pub granularity: Option<Granularity>,
+   /// Only changelog chunks documenting this version
+   pub version: Option<String>,
+   /// Only chunks whose content contains all the words of this text. Evaluated by Qdrant,
+   /// using the content index of collections created with `content_text_index`.
+   pub text: Option<String>,
+}
+
+impl ChunkFilter {
+   /// Qdrant filter for the restrictions, `None` when nothing is restricted
+   fn filter(&self) -> Option<Filter> {
+      if self.granularity.is_none() && self.version.is_none() && self.text.is_none() {
+         return None;
+      }
+
+      let mut filter = self
+         .granularity
+         .map(Granularity::filter)
+         .unwrap_or_default();
+      if let Some(version) = &self.version {
+         filter
+            .must
+            .push(Condition::matches("version", version.clone()));
+      }
+      if let Some(text) = &self.text {
+         filter
+            .must
+            .push(Condition::matches_text("content", text.clone()));
+      }
+      Some(filter)
+   }
+}
+
+/// Payload stored alongside each chunk embedding
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkPayload {
+   pub content: String,
+   /// Path of the source file relative to the repository root
+   #[serde(default)]
+   pub file_path: Option<String>,
+   #[serde(default)]
+   pub start_line: Option<usize>,
+   #[serde(default)]
+   pub end_line: Option<usize>,
+   #[serde(default)]
+   pub kind: Option<ChunkKind>,
+   /// Name of the symbol the chunk declares
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub name: Option<String>,
+   /// Signature of the function the chunk declares
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub signature: Option<FunctionSignature>,
+   /// Index of the notebook cell the chunk comes from, which its line numbers are relative to
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub cell: Option<usize>,
+   /// Release the chunk documents, for chunks of changelogs
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub version: Option<String>,
+   /// Unix time of the last commit changing the chunk's file, recorded with `file_recency`,
+   /// or of the commit a commit chunk describes
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub modified_at: Option<i64>,
+   /// Author of the commit a commit chunk describes
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub author: Option<String>,
+   /// Length in characters of the original content when the stored content was truncated
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub content_length: Option<usize>,
+   /// Hash of the whitespace-normalized original content, identifying duplicate chunks
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub content_hash: Option<String>,
+   /// Level of detail of the chunk; missing for chunks and for collections embedded before it
+   /// was recorded
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub granularity: Option<Granularity>,
+   /// Every file the content appears in, `file_path` included, when identical chunks were
+   /// merged into this one
+   #[serde(default, skip_serializing_if = "Vec::is_empty")]
+   pub sources: Vec<String>,
+}
+
+impl ChunkPayload {
+   /// Marker appended to content that was truncated for storage
+   pub const TRUNCATION_MARKER: &str = "\n… [truncated]";
+
+   /// Key identifying chunks with the same content. Chunks stored before content hashes were
+   /// recorded fall back to hashing their stored content.
+   pub fn dedup_key(&self) -> String {
+      self
+         .content_hash
+         .clone()
+         .unwrap_or_else(|| content_hash(&self.content))
+   }
+
+   /// Caps the stored content at `max_chars` characters, appending a marker and recording
+   /// the original length when anything was cut. Only the stored copy is affected; the text
+   /// sent to the embedding model is built before truncation.
+   pub fn truncated(mut self, max_chars: usize) -> Self {
+      let length = self.content.chars().count();
+      if length <= max_chars {
+         return self;
+      }
+
+      let cut = self
+         .content
+         .char_indices()
+         .nth(max_chars)
+         .map_or(self.content.len(), |(i, _)| i);
+      self.content.truncate(cut);
+      self.content.push_str(Self::TRUNCATION_MARKER);
+      self.content_length = Some(length);
+      self
+   }
+}
+
+/// A chunk returned from a similarity search together with its score
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+   pub score: f32,
+   pub chunk: ChunkPayload,
+}
+
+/// A chunk read back from a collection, optionally with its stored vector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredChunk {
+   #[serde(flatten)]
+   pub chunk: ChunkPayload,
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub vector: Option<Vec<f32>>,
+}
 
 /// Metadata stored with each embedding collection
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,18 +438,161 @@ pub struct EmbeddingMetadata {
    pub embedded_at: DateTime<Utc>,
    pub embedding_model: String,
    pub doc_count: usize,
+   /// Mean of the repository's chunk embeddings, used to find similar repositories. Missing
+   /// for collections embedded before it was recorded.
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub centroid: Option<Vec<f32>>,
+   /// Language most of the repository's source files are written in
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub primary_language: Option<String>,
+   /// Free-form key/value tags used to organize embedded repositories
+   #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+   pub labels: BTreeMap<String, String>,
+   /// Short name the repository can be queried by instead of its URL
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub alias: Option<String>,
+   /// Prefix prepended to chunks when they were embedded
+   #[serde(default, skip_serializing_if = "String::is_empty")]
+   pub document_prefix: String,
+   /// Prefix queries need so they match how the chunks were embedded
+   #[serde(default, skip_serializing_if = "String::is_empty")]
+   pub query_prefix: String,
+   /// Commit the repository was embedded at, compared to the remote HEAD to tell whether
+   /// re-embedding would change anything. Unknown after updates of single files or diffs.
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub commit: Option<String>,
+   /// Whether the collection stores its vectors on disk, set with `QDRANT_ON_DISK`
+   #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+   pub on_disk: bool,
+   /// When the repository was last queried, recorded only while a collection limit is set
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub last_queried_at: Option<DateTime<Utc>>,
+}
+
+impl EmbeddingMetadata {
+   /// Merges labels into the existing ones, overwriting values of existing keys. A label
+   /// with an empty value removes that key.
+   pub fn merge_labels(&mut self, labels: BTreeMap<String, String>) {
+      for (key, value) in labels {
+         if value.is_empty() {
+            self.labels.remove(&key);
+         } else {
+            self.labels.insert(key, value);
+         }
+      }
+   }
+
+   /// When the repository was last used: its last query, or its embedding when it has not
+   /// been queried since
+   pub fn last_used(&self) -> DateTime<Utc> {
+      self.last_queried_at.unwrap_or(self.embedded_at)
+   }
+}
+
+/// Maps an alias to the URL recorded in the metadata of the collection it points to, read
+/// with `collection_metadata`. `aliases` pairs alias names with collection names. The
+/// collection name is not enough since a renamed repository keeps the collection of its old
+/// URL. Returns `None` for unknown aliases.
+pub async fn aliased_repo_url<F, Fut>(
+   alias: &str,
+   aliases: impl IntoIterator<Item = (String, String)>,
+   collection_metadata: F,
+) -> Result<Option<String>>
+where
+   F: FnOnce(String) -> Fut,
+   Fut: Future<Output = Result<Option<EmbeddingMetadata>>>,
+{
+   let Some((_, collection_name)) = aliases.into_iter().find(|(name, _)| name == alias) else {
+      return Ok(None);
+   };
+
+   let metadata = collection_metadata(collection_name.clone())
+      .await?
+      .with_context(|| format!("Collection {collection_name} of alias {alias} has no metadata"))?;
+   Ok(Some(metadata.repo_url))
+}
+
+/// Picks the collections to delete so that at most `max_collections` remain, least recently
+/// used first. `keep` is never picked, so a collection that was just embedded survives even
+/// though it has not been queried yet.
+pub fn least_recently_used_collections(
+   mut collections: Vec<(String, DateTime<Utc>)>,
+   max_collections: usize,
+   keep: &str,
+) -> Vec<String> {
+   let excess = collections.len().saturating_sub(max_collections);
+   collections.retain(|(name, _)| name != keep);
+   collections.sort_by_key(|(_, last_used)| *last_used);
+
+   collections
+      .into_iter()
+      .take(excess)
+      .map(|(name, _)| name)
+      .collect()
+}
+
+/// Finds the collection a repository renamed from the collection name `old_name` to
+/// `new_name` keeps using: `old_name` itself, or the collection it is an alias of when the
+/// repository was renamed before. `aliases` are `(alias, collection)` pairs. Fails when
+/// `old_name` names nothing, or when `new_name` is a collection of its own whose embeddings
+/// an alias would hide.
+pub fn renamed_collection(
+   old_name: &str,
+   new_name: &str,
+   collections: &[String],
+   aliases: &[(String, String)],
+) -> Result<String> {
+   let alias_target = |name: &str| {
+      aliases
+         .iter()
+         .find(|(alias, _)| alias == name)
+         .map(|(_, collection)| collection.clone())
+   };
+
+   let target = if collections.iter().any(|name| name == old_name) {
+      old_name.to_string()
+   } else {
+      alias_target(old_name).with_context(|| format!("no collection found for {old_name}"))?
+   };
+   if new_name != target && collections.iter().any(|name| name == new_name) {
+      bail!("{new_name} already has its own collection, delete it before renaming onto it");
+   }
+
+   Ok(target)
 }
 
 pub struct DataStore {
    pub qdrant_client: Qdrant,
    repo_url: String,
    collection_name: String,
+   /// Whether newly created collections use the hybrid dense and sparse layout
+   hybrid: bool,
+   /// Whether newly created collections get a full-text index on chunk content
+   content_text_index: bool,
+   /// Whether newly created collections store their vectors and HNSW index on disk
+   on_disk: bool,
+   /// Vector schema of the collection, known once it exists
+   layout: OnceCell<CollectionLayout>,
 }
 
 impl DataStore {
-   /// Creates a Qdrant collection for storing repository embeddings with deterministic
-   /// naming to enable consistent retrieval across sessions
+   /// Connects to the collection of an embedded repository for reading. Fails when the
+   /// repository has no collection rather than creating an empty one.
    pub async fn new(repo_url: &str) -> Result<Self> {
+      let data_store = Self::connect(repo_url)?;
+      data_store.layout().await?;
+
+      Ok(data_store)
+   }
+
+   /// Connects to Qdrant without requiring the collection to exist. The collection is created
+   /// when the first embedding is stored, using that vector's length as its dimension, so the
+   /// store adapts to whichever embedding model produced the vectors.
+   pub fn new_deferred(repo_url: &str) -> Result<Self> {
+      Self::connect(repo_url)
+   }
+
+   fn connect(repo_url: &str) -> Result<Self> {
       let qdrant_url = dotenvy::var("QDRANT_URL").context("QDRANT_URL not set")?;
       let qdrant_api_key = dotenvy::var("QDRANT_API_KEY").ok();
 
@@ -38,117 +600,670 @@ impl DataStore {
          .api_key(qdrant_api_key)
          .build()?;
 
+      Self::with_client(qdrant_client, repo_url, &EmbeddingConfig::from_env())
+   }
+
+   /// Uses an already connected client without requiring the collection to exist, like
+   /// `new_deferred`. Collections it creates are laid out as `config` says.
+   pub fn with_client(
+      qdrant_client: Qdrant,
+      repo_url: &str,
+      config: &EmbeddingConfig,
+   ) -> Result<Self> {
       // Generate deterministic names
       let collection_name = gen_table_name_for_repo(repo_url)?;
 
-      // setup qdrant collection - only create if it doesn't exist
-      let collection_exists = qdrant_client.collection_exists(&collection_name).await?;
-      if !collection_exists {
-         let embedding_config = EmbeddingConfig::default();
-         let collection = CreateCollectionBuilder::new(&collection_name).vectors_config(
-            VectorParamsBuilder::new(embedding_config.vector_size, Distance::Cosine),
-         );
-
-         let res = qdrant_client.create_collection(collection).await?;
-         assert!(res.result, "collection could not be created");
-      }
-
       Ok(Self {
          qdrant_client,
          repo_url: repo_url.to_string(),
          collection_name,
+         hybrid: config.hybrid_search,
+         content_text_index: config.content_text_index,
+         on_disk: config.on_disk,
+         layout: OnceCell::new(),
       })
    }
 
-   /// Clears existing embeddings to allow fresh re-indexing when repository content
-   /// changes
-   pub async fn reset(&self) -> Result<()> {
-      self
+   /// Layout of the existing collection, see `open_collection`. Queries, including those of
+   /// read-only servers, only go through here and never create a collection.
+   async fn layout(&self) -> Result<CollectionLayout> {
+      let layout = self
+         .layout
+         .get_or_try_init(|| open_collection(&self.collection_name, || self.existing_layout()))
+         .await?;
+
+      Ok(*layout)
+   }
+
+   /// Creates the collection with the dimension of the first embedding stored, `vector_size`,
+   /// if it doesn't exist yet and returns the layout of the collection. Existing collections
+   /// keep the layout they were created with.
+   async fn ensure_collection(&self, vector_size: u64) -> Result<CollectionLayout> {
+      let layout = self
+         .layout
+         .get_or_try_init(|| async {
+            let layout = CollectionLayout {
+               vector_size,
+               hybrid: self.hybrid,
+               on_disk: self.on_disk,
+            };
+            let (layout, created) = open_or_create_collection(
+               &self.collection_name,
+               layout,
+               || async {
+                  let res = self
+                     .qdrant_client
+                     .create_collection(collection_request(&self.collection_name, layout))
+                     .await?;
+                  Ok(res.result)
+               },
+               || self.existing_layout(),
+            )
+            .await?;
+
+            if created && self.content_text_index {
+               // creating an index that already exists is a no-op, so a concurrent creator
+               // reusing the collection is fine
+               self
+                  .qdrant_client
+                  .create_field_index(content_index_request(&self.collection_name))
+                  .await
+                  .context("Failed to create the content text index")?;
+            }
+            Ok::<CollectionLayout, anyhow::Error>(layout)
+         })
+         .await?;
+
+      Ok(*layout)
+   }
+
+   /// Reads the vector schema configured on the collection, `None` when it does not exist
+   async fn existing_layout(&self) -> Result<Option<CollectionLayout>> {
+      if !self
+         .qdrant_client
+         .collection_exists(&self.collection_name)
+         .await?
+      {
+         return Ok(None);
+      }
+
+      let info = self
          .qdrant_client
-         .delete_collection(&self.collection_name)
+         .collection_info(&self.collection_name)
          .await?;
 
-      let embedding_config = EmbeddingConfig::default();
-      let collection = CreateCollectionBuilder::new(&self.collection_name).vectors_config(
-         VectorParamsBuilder::new(embedding_config.vector_size, Distance::Cosine),
-      );
+      let layout =
+         info
+            .result
+            .and_then(|info| info.config)
+            .and_then(|config| config.params)
+            .and_then(|params| params.vectors_config)
+            .and_then(|vectors_config| vectors_config.config)
+            .and_then(|config| match config {
+               vectors_config::Config::Params(params) => Some(CollectionLayout {
+                  vector_size: params.size,
+                  hybrid: false,
+                  on_disk: params.on_disk.unwrap_or(false),
+               }),
+               vectors_config::Config::ParamsMap(params_map) => params_map
+                  .map
+                  .get(DENSE_VECTOR)
+                  .map(|params| CollectionLayout {
+                     vector_size: params.size,
+                     hybrid: true,
+                     on_disk: params.on_disk.unwrap_or(false),
+                  }),
+            })
+            .with_context(|| {
+               format!(
+                  "Collection {} has no vector config this server understands",
+                  self.collection_name
+               )
+            })?;
 
-      _ = self.qdrant_client.create_collection(collection).await?;
+      Ok(Some(layout))
+   }
+
+   /// Clears existing embeddings to allow fresh re-indexing when repository content
+   /// changes
+   pub async fn reset(&mut self) -> Result<()> {
+      // a renamed repository's collection name is an alias of the collection it still uses
+      if let Some(target) =
+         Self::alias_collection(&self.qdrant_client, &self.collection_name).await?
+      {
+         self
+            .qdrant_client
+            .delete_alias(&self.collection_name)
+            .await?;
+         self.qdrant_client.delete_collection(target).await?;
+      } else if self
+         .qdrant_client
+         .collection_exists(&self.collection_name)
+         .await?
+      {
+         self
+            .qdrant_client
+            .delete_collection(&self.collection_name)
+            .await?;
+      }
+      // the next embedding stored creates the collection again
+      self.layout = OnceCell::new();
 
       Ok(())
    }
 
-   /// Stores vector embeddings with their source content for semantic search retrieval
-   pub async fn add_embedding_with_content(&self, content: &str, vector: Vec<f32>) -> Result<u64> {
-      // generate a unique id based on timestamp and random value
-      let id = std::time::SystemTime::now()
-         .duration_since(std::time::UNIX_EPOCH)?
-         .as_nanos() as u64;
+   /// Stores vector embeddings with their source content and location for semantic search
+   /// retrieval. Points are sent in requests of `UPSERT_BATCH_SIZE`, each waiting until
+   /// Qdrant has applied it so a slow server holds back the next request instead of queueing
+   /// them. Returns the ids of the stored points.
+   pub async fn add_embeddings_batch(
+      &self,
+      items: Vec<(ChunkPayload, Vec<f32>)>,
+   ) -> Result<Vec<u64>> {
+      let Some((_, first_vector)) = items.first() else {
+         return Ok(Vec::new());
+      };
+      let layout = self.ensure_collection(first_vector.len() as u64).await?;
 
-      // create payload with the content
-      let payload = Payload::try_from(json!({
-         "content": content
-      }))?;
+      let mut ids = Vec::with_capacity(items.len());
+      let mut items = items.into_iter().peekable();
+      while items.peek().is_some() {
+         // ids are based on the timestamp, offset within the request so they stay unique
+         let base_id = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_nanos() as u64;
 
-      // add vector and content to qdrant
-      let points = vec![PointStruct::new(id, vector, payload)];
-      let req = UpsertPointsBuilder::new(&self.collection_name, points);
-      self.qdrant_client.upsert_points(req).await?;
+         let mut points = Vec::with_capacity(UPSERT_BATCH_SIZE);
+         for (offset, (chunk, vector)) in items.by_ref().take(UPSERT_BATCH_SIZE).enumerate() {
+            let id = base_id + offset as u64;
+            points.push(chunk_point(id, &chunk, vector, layout)?);
+            ids.push(id);
+         }
+
+         let req = UpsertPointsBuilder::new(&self.collection_name, points).wait(true);
+         self.qdrant_client.upsert_points(req).await?;
+      }
 
-      Ok(id)
+      Ok(ids)
    }
 
    /// Performs cosine similarity search to find most relevant code/docs for a given
-   /// query
+   /// query. `search_ef` overrides the HNSW `ef` of the search, see `search_request`.
    pub async fn query_with_content(
       &self,
       query_vector: Vec<f32>,
       max_results: u64,
-   ) -> Result<Vec<(f32, String)>> {
-      let search_req = SearchPointsBuilder::new(&self.collection_name, query_vector, max_results)
-         .with_payload(true);
+      filter: &ChunkFilter,
+      search_ef: Option<u64>,
+   ) -> Result<Vec<SearchResult>> {
+      let layout = self.layout().await?;
+
+      let search_req = search_request(
+         &self.collection_name,
+         query_vector,
+         max_results,
+         layout,
+         filter,
+         search_ef,
+      );
       let search_res = self.qdrant_client.search_points(search_req).await?;
 
-      let mut results = Vec::new();
+      to_search_results(search_res.result)
+   }
 
-      for result in search_res.result {
-         let score = result.score;
+   /// URL of the repository whose embeddings the store holds
+   pub fn repo_url(&self) -> &str {
+      &self.repo_url
+   }
 
-         let Some(content) = result.payload.get("content") else {
-            trace!("skipping result that does not have a content field (probably metadata)");
-            continue;
-         };
-         let content = content
-            .as_str()
-            .context("could not convert the content `Value` into a `String`")?
-            .to_owned();
+   /// Dimension of the collection's vectors, which query vectors must match
+   pub async fn vector_size(&self) -> Result<u64> {
+      let layout = self.layout().await?;
+
+      Ok(layout.vector_size)
+   }
+
+   /// Searches with both the query embedding and the query's keywords when the collection is
+   /// hybrid, fusing the two rankings with reciprocal rank fusion. Fused scores reflect rank
+   /// rather than cosine similarity. Falls back to dense search for other collections. Only
+   /// chunks passing `filter` are searched, with `search_ef` applied to the dense search.
+   pub async fn query_with_text(
+      &self,
+      query: &str,
+      query_vector: Vec<f32>,
+      max_results: u64,
+      filter: &ChunkFilter,
+      search_ef: Option<u64>,
+   ) -> Result<Vec<SearchResult>> {
+      let layout = self.layout().await?;
+      let sparse = SparseVector::from_text(query);
+      if !layout.hybrid || sparse.is_empty() {
+         return self
+            .query_with_content(query_vector, max_results, filter, search_ef)
+            .await;
+      }
+
+      // each ranking contributes more candidates than requested so fusion has room to reorder
+      let prefetch_limit = max_results.saturating_mul(2);
+      let mut dense = PrefetchQueryBuilder::default()
+         .query(Query::new_nearest(query_vector))
+         .using(DENSE_VECTOR)
+         .limit(prefetch_limit);
+      if let Some(ef) = search_ef {
+         dense = dense.params(SearchParamsBuilder::default().hnsw_ef(ef.min(MAX_SEARCH_EF)));
+      }
+      let mut keywords = PrefetchQueryBuilder::default()
+         .query(Query::new_nearest(VectorInput::new_sparse(
+            sparse.indices,
+            sparse.values,
+         )))
+         .using(SPARSE_VECTOR)
+         .limit(prefetch_limit);
+      if let Some(filter) = filter.filter() {
+         dense = dense.filter(filter.clone());
+         keywords = keywords.filter(filter);
+      }
+      let query_req = QueryPointsBuilder::new(&self.collection_name)
+         .add_prefetch(dense)
+         .add_prefetch(keywords)
+         .query(Query::new_fusion(Fusion::Rrf))
+         .limit(max_results)
+         .with_payload(true);
+      let query_res = self.qdrant_client.query(query_req).await?;
+
+      to_search_results(query_res.result)
+   }
+
+   /// Reads every chunk stored in the collection, skipping the metadata point. Vectors are
+   /// only fetched when `with_vectors` is set since they dominate the response size.
+   pub async fn scroll_chunks(&self, with_vectors: bool) -> Result<Vec<StoredChunk>> {
+      let points = self.scroll(None, with_vectors).await?;
+      Ok(points.into_iter().map(|(_, stored)| stored).collect())
+   }
+
+   /// Reads the chunks found in any of the given source files with the ids of their points:
+   /// chunks extracted from them and deduplicated chunks listing them among their sources
+   pub async fn file_points(&self, file_paths: &[String]) -> Result<Vec<(u64, ChunkPayload)>> {
+      if file_paths.is_empty() {
+         return Ok(Vec::new());
+      }
+
+      let points = self
+         .scroll(Some(file_sources_filter(file_paths)), false)
+         .await?;
+      Ok(points
+         .into_iter()
+         .map(|(id, stored)| (id, stored.chunk))
+         .collect())
+   }
+
+   /// Reads the chunks of the given source files, without their vectors
+   pub async fn file_chunks(&self, file_paths: &[String]) -> Result<Vec<ChunkPayload>> {
+      if file_paths.is_empty() {
+         return Ok(Vec::new());
+      }
+
+      let chunks = self
+         .scroll(Some(file_path_filter(file_paths)), false)
+         .await?;
+      Ok(chunks.into_iter().map(|(_, stored)| stored.chunk).collect())
+   }
+
+   /// Reads every chunk matching `filter`, or all chunks without one, page by page, with the
+   /// ids of their points
+   async fn scroll(
+      &self,
+      filter: Option<Filter>,
+      with_vectors: bool,
+   ) -> Result<Vec<(u64, StoredChunk)>> {
+      const PAGE_SIZE: u32 = 256;
+
+      let mut chunks = Vec::new();
+      let mut offset: Option<PointId> = None;
+
+      loop {
+         let mut scroll_req = ScrollPointsBuilder::new(&self.collection_name)
+            .limit(PAGE_SIZE)
+            .with_payload(true)
+            .with_vectors(with_vectors);
+         if let Some(filter) = &filter {
+            scroll_req = scroll_req.filter(filter.clone());
+         }
+         if let Some(offset) = offset.take() {
+            scroll_req = scroll_req.offset(offset);
+         }
+
+         let scroll_res = self.qdrant_client.scroll(scroll_req).await?;
+
+         for point in scroll_res.result {
+            if !point.payload.contains_key("content") {
+               trace!("skipping point that does not have a content field (probably metadata)");
+               continue;
+            }
+            let Some(PointIdOptions::Num(id)) = point.id.and_then(|id| id.point_id_options) else {
+               bail!("chunk point without a numeric id");
+            };
+            let payload = serde_json::Value::from(Payload::from(point.payload));
+            let chunk: ChunkPayload = serde_json::from_value(payload)
+               .context("could not convert the point payload into a `ChunkPayload`")?;
+
+            let vector = point
+               .vectors
+               .and_then(|vectors| vectors.vectors_options)
+               .and_then(|options| match options {
+                  VectorsOptions::Vector(vector) => Some(vector.data),
+                  // hybrid collections only export the dense embedding; the sparse vector is
+                  // derived from the content again on import
+                  VectorsOptions::Vectors(mut named) => {
+                     named.vectors.remove(DENSE_VECTOR).map(|vector| vector.data)
+                  }
+               });
+
+            chunks.push((id, StoredChunk { chunk, vector }));
+         }
 
-         results.push((score, content));
+         match scroll_res.next_page_offset {
+            Some(next) => offset = Some(next),
+            None => break,
+         }
       }
 
-      Ok(results)
+      debug!(
+         "Scrolled {} chunks from collection {}",
+         chunks.len(),
+         self.collection_name
+      );
+
+      Ok(chunks)
    }
 
-   /// Persists collection metadata to track when and how the repository was indexed
-   pub async fn store_metadata(&self, doc_count: usize) -> Result<()> {
+   /// Deletes the points with the given ids, leaving the rest of the collection untouched
+   pub async fn delete_points(&self, ids: &[u64]) -> Result<()> {
+      if ids.is_empty() {
+         return Ok(());
+      }
+
+      let req = DeletePointsBuilder::new(&self.collection_name)
+         .points(PointsIdsList {
+            ids: ids.iter().map(|&id| id.into()).collect(),
+         })
+         .wait(true);
+      self.qdrant_client.delete_points(req).await?;
+
+      debug!(
+         "Deleted {} chunks from collection {}",
+         ids.len(),
+         self.collection_name
+      );
+
+      Ok(())
+   }
+
+   /// Replaces the payloads of stored chunks, keeping their vectors since their content is
+   /// unchanged
+   pub async fn overwrite_chunks(&self, chunks: &[(u64, ChunkPayload)]) -> Result<()> {
+      for (id, chunk) in chunks {
+         let payload = Payload::try_from(serde_json::to_value(chunk)?)?;
+         let req = SetPayloadPointsBuilder::new(&self.collection_name, payload)
+            .points_selector(PointsIdsList {
+               ids: vec![(*id).into()],
+            })
+            .wait(true);
+         self.qdrant_client.overwrite_payload(req).await?;
+      }
+
+      Ok(())
+   }
+
+   /// Counts the chunks stored in the collection, excluding the metadata point
+   pub async fn count_chunks(&self) -> Result<usize> {
+      let req = CountPointsBuilder::new(&self.collection_name)
+         .filter(Filter::must_not([Condition::has_id([0])]))
+         .exact(true);
+      let res = self.qdrant_client.count(req).await?;
+
+      Ok(res.result.map_or(0, |result| result.count as usize))
+   }
+
+   /// Persists collection metadata to track when and how the repository was indexed,
+   /// including the commit and the prefixes of `config` the chunks were embedded with
+   pub async fn store_metadata(
+      &self,
+      doc_count: usize,
+      centroid: Option<Vec<f32>>,
+      primary_language: Option<&str>,
+      commit: Option<&str>,
+      labels: BTreeMap<String, String>,
+      config: &EmbeddingConfig,
+   ) -> Result<()> {
+      // a store that only wrote metadata has not resolved its layout yet
+      let layout = self.layout().await?;
       let metadata = EmbeddingMetadata {
          repo_url: self.repo_url.clone(),
          embedded_at: Utc::now(),
-         embedding_model: "text-embedding-3-small".to_string(),
+         embedding_model: EMBEDDING_MODEL.to_string(),
          doc_count,
+         centroid,
+         primary_language: primary_language.map(str::to_string),
+         labels,
+         alias: None,
+         document_prefix: config.document_prefix.clone(),
+         query_prefix: config.query_prefix.clone(),
+         commit: commit.map(str::to_string),
+         on_disk: layout.on_disk,
+         last_queried_at: None,
       };
 
-      debug!("Storing metadata: {:?}", metadata);
+      self.write_metadata(&metadata).await
+   }
+
+   /// Records a partial update in the collection's `metadata`: its new `doc_count` and time,
+   /// and no commit since the chunks no longer match a single one. Everything else, the
+   /// prefixes the chunks were embedded with and the last query time included, is kept.
+   pub async fn store_updated_metadata(
+      &self,
+      mut metadata: EmbeddingMetadata,
+      doc_count: usize,
+   ) -> Result<()> {
+      metadata.embedded_at = Utc::now();
+      metadata.doc_count = doc_count;
+      metadata.commit = None;
+
+      self.write_metadata(&metadata).await
+   }
+
+   /// Records in the metadata that the repository was just queried, which decides the
+   /// collections evicted by `evict_least_recently_used`. Only `last_queried_at` is set, so
+   /// concurrent queries and metadata updates do not overwrite each other.
+   pub async fn record_query(&self) -> Result<()> {
+      let payload = Payload::try_from(json!({ "last_queried_at": Utc::now() }))?;
+      let req = SetPayloadPointsBuilder::new(&self.collection_name, payload)
+         .points_selector(PointsIdsList {
+            ids: vec![0.into()],
+         })
+         .key("metadata");
+      self.qdrant_client.set_payload(req).await?;
+
+      Ok(())
+   }
+
+   /// Deletes the least recently used repository collections until at most `max_collections`
+   /// remain, sparing `keep`. Collections without metadata were not created by this server and
+   /// are left alone. Returns the names of the deleted collections.
+   pub async fn evict_least_recently_used(
+      qdrant_client: &Qdrant,
+      max_collections: usize,
+      keep: &str,
+   ) -> Result<Vec<String>> {
+      let collections = qdrant_client.list_collections().await?.collections;
+
+      let mut last_used = Vec::new();
+      for collection in collections {
+         if let Some(metadata) = Self::collection_metadata(qdrant_client, &collection.name).await? {
+            last_used.push((collection.name, metadata.last_used()));
+         }
+      }
+
+      let evicted = least_recently_used_collections(last_used, max_collections, keep);
+      for collection_name in &evicted {
+         info!(
+            "Evicting collection {collection_name}, the least recently used of more than \
+             {max_collections}"
+         );
+         qdrant_client.delete_collection(collection_name).await?;
+      }
+
+      Ok(evicted)
+   }
+
+   /// Merges labels into the stored metadata without touching any chunks and returns the
+   /// updated metadata. Only the metadata point is rewritten, so no embeddings are needed.
+   pub async fn update_labels(
+      &self,
+      labels: BTreeMap<String, String>,
+   ) -> Result<EmbeddingMetadata> {
+      let mut metadata = Self::get_metadata(&self.qdrant_client, &self.repo_url)
+         .await?
+         .with_context(|| format!("no metadata found for {}", self.repo_url))?;
+
+      metadata.merge_labels(labels);
+      self.write_metadata(&metadata).await?;
+
+      Ok(metadata)
+   }
+
+   /// Points `alias` at the repository's collection and records it in the metadata. Qdrant
+   /// persists the alias→collection index and drops an alias along with its collection, so
+   /// the alias has to be set again after the collection is recreated.
+   pub async fn set_alias(&self, alias: &str) -> Result<()> {
+      let current = Self::alias_collection(&self.qdrant_client, alias).await?;
+      if current.as_deref() != Some(self.collection_name.as_str()) {
+         if current.is_some() {
+            self.qdrant_client.delete_alias(alias).await?;
+         }
+         self
+            .qdrant_client
+            .create_alias(CreateAliasBuilder::new(&self.collection_name, alias))
+            .await?;
+      }
+
+      let mut metadata = Self::get_metadata(&self.qdrant_client, &self.repo_url)
+         .await?
+         .with_context(|| format!("no metadata found for {}", self.repo_url))?;
+      // a repository has a single alias, so a previous one stops resolving to it
+      if let Some(previous) = metadata.alias.replace(alias.to_string())
+         && previous != alias
+      {
+         self.qdrant_client.delete_alias(previous).await?;
+      }
+      self.write_metadata(&metadata).await
+   }
+
+   /// Makes the embeddings of `old_url` answer under this store's repository URL, for a
+   /// repository that was renamed or transferred. An alias named after this store's collection
+   /// points at the existing collection, so nothing is re-embedded, and the metadata records
+   /// the new URL. The old URL keeps answering too. Returns the updated metadata.
+   pub async fn adopt_renamed(&self, old_url: &str) -> Result<EmbeddingMetadata> {
+      let collections: Vec<String> = self
+         .qdrant_client
+         .list_collections()
+         .await?
+         .collections
+         .into_iter()
+         .map(|collection| collection.name)
+         .collect();
+      let aliases: Vec<(String, String)> = self
+         .qdrant_client
+         .list_aliases()
+         .await?
+         .aliases
+         .into_iter()
+         .map(|description| (description.alias_name, description.collection_name))
+         .collect();
+
+      let old_name = gen_table_name_for_repo(old_url)?;
+      let target = renamed_collection(&old_name, &self.collection_name, &collections, &aliases)?;
+      let mut metadata = Self::collection_metadata(&self.qdrant_client, &target)
+         .await?
+         .with_context(|| format!("no metadata found for {old_url}"))?;
+
+      if target != self.collection_name {
+         let current = aliases
+            .iter()
+            .find(|(alias, _)| *alias == self.collection_name);
+         if current.is_some_and(|(_, collection)| *collection != target) {
+            self
+               .qdrant_client
+               .delete_alias(&self.collection_name)
+               .await?;
+         }
+         if current.is_none_or(|(_, collection)| *collection != target) {
+            info!("Aliasing {} to collection {target}", self.collection_name);
+            self
+               .qdrant_client
+               .create_alias(CreateAliasBuilder::new(&target, &self.collection_name))
+               .await?;
+         }
+      }
+
+      metadata.repo_url = self.repo_url.clone();
+      self.write_metadata(&metadata).await?;
+      Ok(metadata)
+   }
+
+   /// Looks up the URL of the repository an alias was given to, see `aliased_repo_url`
+   pub async fn resolve_alias(qdrant_client: &Qdrant, alias: &str) -> Result<Option<String>> {
+      let aliases = qdrant_client
+         .list_aliases()
+         .await?
+         .aliases
+         .into_iter()
+         .map(|description| (description.alias_name, description.collection_name));
+
+      aliased_repo_url(alias, aliases, |collection_name| async move {
+         Self::collection_metadata(qdrant_client, &collection_name).await
+      })
+      .await
+   }
+
+   /// Name of the collection an alias points to
+   async fn alias_collection(qdrant_client: &Qdrant, alias: &str) -> Result<Option<String>> {
+      let aliases = qdrant_client.list_aliases().await?;
+
+      Ok(aliases
+         .aliases
+         .into_iter()
+         .find(|description| description.alias_name == alias)
+         .map(|description| description.collection_name))
+   }
+
+   /// Upserts the metadata point of the collection
+   async fn write_metadata(&self, metadata: &EmbeddingMetadata) -> Result<()> {
+      debug!(
+         "Storing metadata for {} ({} docs, embedded at {})",
+         metadata.repo_url, metadata.doc_count, metadata.embedded_at
+      );
 
       // Store metadata as a special point with ID 0
       let payload = Payload::try_from(json!({
-         "metadata": serde_json::to_value(&metadata)?,
+         "metadata": serde_json::to_value(metadata)?,
          "is_metadata": true
       }))?;
 
       debug!("Storing metadata in collection: {}", self.collection_name);
 
-      let points = vec![PointStruct::new(0, vec![0.0; 1536], payload)];
+      // the metadata point uses a dummy vector that must match the collection dimension, so it
+      // is only written once embeddings have created the collection
+      let layout = self.layout().await?;
+      let dummy_vector = vec![0.0; layout.vector_size as usize];
+      let vectors: Vectors = if layout.hybrid {
+         NamedVectors::default()
+            .add_vector(DENSE_VECTOR, dummy_vector)
+            .into()
+      } else {
+         dummy_vector.into()
+      };
+      let points = vec![PointStruct::new(0, vectors, payload)];
       let req = UpsertPointsBuilder::new(&self.collection_name, points);
       self.qdrant_client.upsert_points(req).await?;
 
@@ -167,8 +1282,16 @@ impl DataStore {
          collection_name, repo_url
       );
 
+      Self::collection_metadata(qdrant_client, &collection_name).await
+   }
+
+   /// Reads the metadata point of a collection by its name
+   async fn collection_metadata(
+      qdrant_client: &Qdrant,
+      collection_name: &str,
+   ) -> Result<Option<EmbeddingMetadata>> {
       // Try to get the metadata point (ID 0)
-      let get_points = GetPointsBuilder::new(collection_name.clone(), vec![0.into()])
+      let get_points = GetPointsBuilder::new(collection_name, vec![0.into()])
          .with_payload(true)
          .build();
 
@@ -202,3 +1325,557 @@ impl DataStore {
       }
    }
 }
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::mock_qdrant::MockQdrant;
+   use qdrant_client::qdrant::PayloadSchemaType;
+   use std::sync::{Arc, Mutex};
+   use tokio::sync::Barrier;
+
+   #[tokio::test]
+   async fn test_concurrent_collection_creation_both_succeed() -> Result<()> {
+      // stands in for Qdrant: creating an existing collection fails
+      let collection: Arc<Mutex<Option<CollectionLayout>>> = Arc::default();
+      // both creators pass their existence check before either creates the collection
+      let barrier = Arc::new(Barrier::new(2));
+      let layout = CollectionLayout {
+         vector_size: 1536,
+         hybrid: false,
+         on_disk: false,
+      };
+
+      let try_new = || {
+         let collection = collection.clone();
+         let barrier = barrier.clone();
+         async move {
+            assert!(collection.lock().unwrap().is_none());
+            barrier.wait().await;
+            create_or_reuse_collection(
+               "owner__repo",
+               layout,
+               || async {
+                  let mut collection = collection.lock().unwrap();
+                  if collection.is_some() {
+                     bail!("Collection `owner__repo` already exists!");
+                  }
+                  *collection = Some(layout);
+                  Ok(true)
+               },
+               || async { Ok(*collection.lock().unwrap()) },
+            )
+            .await
+         }
+      };
+
+      let (first, second) = tokio::join!(tokio::spawn(try_new()), tokio::spawn(try_new()));
+      assert_eq!(first??, layout);
+      assert_eq!(second??, layout);
+      Ok(())
+   }
+
+   #[tokio::test]
+   async fn test_concurrent_collection_with_other_dimension_is_an_error() {
+      let layout = CollectionLayout {
+         vector_size: 1536,
+         hybrid: false,
+         on_disk: false,
+      };
+      let existing = CollectionLayout {
+         vector_size: 3072,
+         ..layout
+      };
+
+      let result = create_or_reuse_collection(
+         "owner__repo",
+         layout,
+         || async { bail!("Collection `owner__repo` already exists!") },
+         || async { Ok(Some(existing)) },
+      )
+      .await;
+
+      assert!(result.unwrap_err().to_string().contains("dimension 3072"));
+   }
+
+   #[tokio::test]
+   async fn test_first_embedding_creates_collection_with_its_dimension() -> Result<()> {
+      let collection = Mutex::new(None::<CollectionLayout>);
+      let first_vector = vec![0.5; 768];
+      let layout = CollectionLayout {
+         vector_size: first_vector.len() as u64,
+         hybrid: false,
+         on_disk: false,
+      };
+      let open = |layout: CollectionLayout| {
+         // stands in for Qdrant, holding the layout of the collection once created
+         let collection = &collection;
+         open_or_create_collection(
+            "owner__repo",
+            layout,
+            move || async move {
+               *collection.lock().unwrap() = Some(layout);
+               Ok(true)
+            },
+            move || async move { Ok(*collection.lock().unwrap()) },
+         )
+      };
+
+      let (created, was_created) = open(layout).await?;
+      assert!(was_created);
+      assert_eq!(created.vector_size, 768);
+      assert_eq!(collection.lock().unwrap().map(|c| c.vector_size), Some(768));
+
+      // later embeddings reuse the collection as it was created
+      let (reopened, was_created) = open(CollectionLayout {
+         vector_size: 1536,
+         ..layout
+      })
+      .await?;
+      assert!(!was_created);
+      assert_eq!(reopened.vector_size, 768);
+      Ok(())
+   }
+
+   #[tokio::test]
+   async fn test_storing_embeddings_creates_collection_with_their_dimension() -> Result<()> {
+      let qdrant = MockQdrant::start().await?;
+      let config = EmbeddingConfig::default();
+      let repo_url = "https://github.com/owner/repo";
+      let data_store = DataStore::with_client(qdrant.client(), repo_url, &config)?;
+
+      let missing = data_store.vector_size().await.unwrap_err();
+      assert!(missing.to_string().contains("has not been embedded"));
+      assert!(!qdrant.client().collection_exists("owner__repo").await?);
+
+      data_store
+         .add_embeddings_batch(vec![(chunk("struct Parser;"), vec![0.5; 768])])
+         .await?;
+      let reopened = DataStore::with_client(qdrant.client(), repo_url, &config)?;
+      assert_eq!(reopened.vector_size().await?, 768);
+      let results = reopened
+         .query_with_content(vec![0.5; 768], 1, &ChunkFilter::default(), None)
+         .await?;
+      assert_eq!(results[0].chunk.content, "struct Parser;");
+      Ok(())
+   }
+
+   #[tokio::test]
+   async fn test_queries_never_create_the_collection() -> Result<()> {
+      // stands in for Qdrant, where the collection disappeared after the embed check
+      let collection = Mutex::new(None::<CollectionLayout>);
+      let open = || open_collection("owner__repo", || async { Ok(*collection.lock().unwrap()) });
+
+      let missing = open().await.unwrap_err();
+      assert!(missing.to_string().contains("has not been embedded"));
+      assert!(collection.lock().unwrap().is_none());
+
+      let layout = CollectionLayout {
+         vector_size: 768,
+         hybrid: false,
+         on_disk: false,
+      };
+      *collection.lock().unwrap() = Some(layout);
+      assert_eq!(open().await?.vector_size, 768);
+      Ok(())
+   }
+
+   fn chunk(content: &str) -> ChunkPayload {
+      ChunkPayload {
+         content: content.to_string(),
+         file_path: Some("src/generated.rs".to_string()),
+         start_line: Some(1),
+         end_line: Some(1),
+         kind: Some(ChunkKind::Struct),
+         ..Default::default()
+      }
+   }
+
+   #[test]
+   fn test_truncated_caps_stored_content() {
+      let short = chunk("struct A;");
+      assert_eq!(short.clone().truncated(100), short);
+
+      let long = chunk("struct Ünïcode { field: u8 }").truncated(10);
+      assert_eq!(
+         long.content,
+         format!("struct Ünï{}", ChunkPayload::TRUNCATION_MARKER)
+      );
+      assert_eq!(long.content_length, Some(28));
+   }
+
+   #[test]
+   fn test_file_path_filter_matches_any_listed_file() {
+      let filter = file_path_filter(&["src/lib.rs".to_string(), "README.md".to_string()]);
+
+      assert_eq!(
+         filter,
+         Filter::must([Condition::matches(
+            "file_path",
+            vec!["src/lib.rs".to_string(), "README.md".to_string()]
+         )])
+      );
+   }
+
+   #[test]
+   fn test_text_filter_matches_content_words() {
+      let filter = ChunkFilter {
+         granularity: Some(Granularity::File),
+         text: Some("spawn_blocking".to_string()),
+         ..ChunkFilter::default()
+      };
+
+      assert_eq!(
+         filter.filter(),
+         Some(Filter::must([
+            Condition::matches("granularity", "file".to_string()),
+            Condition::matches_text("content", "spawn_blocking"),
+         ]))
+      );
+      assert_eq!(ChunkFilter::default().filter(), None);
+
+      let index = content_index_request("owner__repo").build();
+      assert_eq!(index.field_name, "content");
+      assert_eq!(index.field_type, Some(FieldType::Text as i32));
+   }
+
+   #[tokio::test]
+   async fn test_text_filter_returns_only_chunks_containing_the_words() -> Result<()> {
+      let qdrant = MockQdrant::start().await?;
+      let config = EmbeddingConfig {
+         content_text_index: true,
+         ..EmbeddingConfig::default()
+      };
+      let data_store =
+         DataStore::with_client(qdrant.client(), "https://github.com/owner/repo", &config)?;
+      data_store
+         .add_embeddings_batch(vec![
+            (chunk("tokio::task::spawn_blocking(work)"), vec![1.0, 0.0]),
+            (chunk("tokio::spawn(work)"), vec![0.9, 0.1]),
+            (chunk("std::thread::spawn(work)"), vec![0.8, 0.2]),
+         ])
+         .await?;
+
+      let info = qdrant.client().collection_info("owner__repo").await?;
+      let index = &info.result.unwrap().payload_schema["content"];
+      assert_eq!(index.data_type, PayloadSchemaType::Text as i32);
+
+      let query = |text: &str| {
+         let filter = ChunkFilter {
+            text: Some(text.to_string()),
+            ..ChunkFilter::default()
+         };
+         let data_store = &data_store;
+         async move {
+            let results = data_store
+               .query_with_content(vec![1.0, 0.0], 10, &filter, None)
+               .await?;
+            anyhow::Ok(
+               results
+                  .into_iter()
+                  .map(|result| result.chunk.content)
+                  .collect::<Vec<_>>(),
+            )
+         }
+      };
+      assert_eq!(
+         query("spawn_blocking").await?,
+         ["tokio::task::spawn_blocking(work)"]
+      );
+      assert_eq!(
+         query("tokio spawn").await?,
+         ["tokio::task::spawn_blocking(work)", "tokio::spawn(work)"]
+      );
+      assert!(query("block_on").await?.is_empty());
+      Ok(())
+   }
+
+   #[test]
+   fn test_search_request_sets_clamped_ef() {
+      let layout = CollectionLayout {
+         vector_size: 2,
+         hybrid: false,
+         on_disk: false,
+      };
+      let search = |search_ef| {
+         search_request(
+            "owner__repo",
+            vec![0.1, 0.2],
+            10,
+            layout,
+            &ChunkFilter::default(),
+            search_ef,
+         )
+         .build()
+      };
+
+      assert!(search(None).params.is_none());
+      let hnsw_ef = |search_ef| {
+         search(Some(search_ef))
+            .params
+            .and_then(|params| params.hnsw_ef)
+      };
+      assert_eq!(hnsw_ef(256), Some(256));
+      assert_eq!(hnsw_ef(100_000), Some(MAX_SEARCH_EF));
+   }
+
+   #[test]
+   fn test_collection_request_uses_vector_dimension() {
+      let layout = CollectionLayout {
+         vector_size: 768,
+         hybrid: false,
+         on_disk: false,
+      };
+      let request = collection_request("owner__repo", layout).build();
+
+      assert_eq!(request.collection_name, "owner__repo");
+      let Some(vectors_config::Config::Params(params)) = request
+         .vectors_config
+         .and_then(|vectors_config| vectors_config.config)
+      else {
+         panic!("expected single vector params");
+      };
+      assert_eq!(params.size, 768);
+      assert_eq!(params.distance, Distance::Cosine as i32);
+   }
+
+   #[test]
+   fn test_on_disk_collection_request_stores_vectors_and_index_on_disk() {
+      let layout = CollectionLayout {
+         vector_size: 1536,
+         hybrid: false,
+         on_disk: true,
+      };
+      let request = collection_request("owner__repo", layout).build();
+
+      let Some(vectors_config::Config::Params(params)) = request
+         .vectors_config
+         .and_then(|vectors_config| vectors_config.config)
+      else {
+         panic!("expected single vector params");
+      };
+      assert_eq!(params.on_disk, Some(true));
+      assert_eq!(
+         request
+            .hnsw_config
+            .and_then(|hnsw_config| hnsw_config.on_disk),
+         Some(true)
+      );
+   }
+
+   #[test]
+   fn test_hybrid_collection_request_has_named_vectors() {
+      let layout = CollectionLayout {
+         vector_size: 1536,
+         hybrid: true,
+         on_disk: false,
+      };
+      let request = collection_request("owner__repo", layout).build();
+
+      let Some(vectors_config::Config::ParamsMap(params_map)) = request
+         .vectors_config
+         .and_then(|vectors_config| vectors_config.config)
+      else {
+         panic!("expected named vector params");
+      };
+      assert_eq!(params_map.map[DENSE_VECTOR].size, 1536);
+
+      let sparse = request
+         .sparse_vectors_config
+         .expect("sparse vectors config");
+      assert_eq!(
+         sparse.map[SPARSE_VECTOR].modifier,
+         Some(Modifier::Idf as i32)
+      );
+   }
+
+   #[test]
+   fn test_embed_beyond_collection_limit_evicts_least_recently_queried() {
+      let now = Utc::now();
+      let hours_ago = |hours| now - chrono::Duration::hours(hours);
+      let metadata = |embedded_at, last_queried_at| EmbeddingMetadata {
+         repo_url: String::new(),
+         embedded_at,
+         embedding_model: EMBEDDING_MODEL.to_string(),
+         doc_count: 1,
+         centroid: None,
+         primary_language: None,
+         labels: BTreeMap::new(),
+         alias: None,
+         document_prefix: String::new(),
+         query_prefix: String::new(),
+         commit: None,
+         on_disk: false,
+         last_queried_at,
+      };
+      let collections = vec![
+         // embedded first but queried an hour ago
+         (
+            "tokio-rs__tokio".to_string(),
+            metadata(hours_ago(72), Some(hours_ago(1))).last_used(),
+         ),
+         // last queried a day ago
+         (
+            "serde-rs__serde".to_string(),
+            metadata(hours_ago(48), Some(hours_ago(24))).last_used(),
+         ),
+         (
+            "hyperium__hyper".to_string(),
+            metadata(hours_ago(12), None).last_used(),
+         ),
+         // just embedded, so never queried
+         (
+            "rust-lang__regex".to_string(),
+            metadata(now, None).last_used(),
+         ),
+      ];
+
+      assert_eq!(
+         least_recently_used_collections(collections.clone(), 2, "rust-lang__regex"),
+         vec!["serde-rs__serde", "hyperium__hyper"]
+      );
+      assert!(least_recently_used_collections(collections, 4, "rust-lang__regex").is_empty());
+   }
+
+   #[tokio::test]
+   async fn test_eviction_deletes_least_recently_used_collections() -> Result<()> {
+      let qdrant = MockQdrant::start().await?;
+      let config = EmbeddingConfig::default();
+      let store = |repo: &str| {
+         let repo_url = format!("https://github.com/{repo}");
+         DataStore::with_client(qdrant.client(), &repo_url, &config)
+      };
+      for repo in [
+         "tokio-rs/tokio",
+         "serde-rs/serde",
+         "hyperium/hyper",
+         "rust-lang/regex",
+      ] {
+         let data_store = store(repo)?;
+         data_store
+            .add_embeddings_batch(vec![(chunk("struct Parser;"), vec![1.0, 0.0])])
+            .await?;
+         data_store
+            .store_metadata(1, None, None, None, BTreeMap::new(), &config)
+            .await?;
+      }
+      // embedded first but queried since
+      store("tokio-rs/tokio")?.record_query().await?;
+      // not created by this server, so it has no metadata
+      qdrant
+         .client()
+         .create_collection(CreateCollectionBuilder::new("foreign"))
+         .await?;
+
+      let client = qdrant.client();
+      let evicted = DataStore::evict_least_recently_used(&client, 2, "rust-lang__regex").await?;
+      assert_eq!(evicted, ["serde-rs__serde", "hyperium__hyper"]);
+
+      let mut remaining: Vec<String> = client
+         .list_collections()
+         .await?
+         .collections
+         .into_iter()
+         .map(|collection| collection.name)
+         .collect();
+      remaining.sort();
+      assert_eq!(
+         remaining,
+         ["foreign", "rust-lang__regex", "tokio-rs__tokio"]
+      );
+      let missing = store("serde-rs/serde")?.vector_size().await.unwrap_err();
+      assert!(missing.to_string().contains("has not been embedded"));
+
+      // within the limit nothing more is deleted
+      assert!(
+         DataStore::evict_least_recently_used(&client, 2, "rust-lang__regex")
+            .await?
+            .is_empty()
+      );
+      Ok(())
+   }
+
+   #[test]
+   fn test_merge_labels_overwrites_and_removes() {
+      let mut metadata = EmbeddingMetadata {
+         repo_url: "https://github.com/owner/repo".to_string(),
+         embedded_at: Utc::now(),
+         embedding_model: EMBEDDING_MODEL.to_string(),
+         doc_count: 3,
+         centroid: None,
+         primary_language: None,
+         labels: BTreeMap::from([
+            ("team".to_string(), "a".to_string()),
+            ("tier".to_string(), "1".to_string()),
+         ]),
+         alias: None,
+         document_prefix: String::new(),
+         query_prefix: String::new(),
+         commit: None,
+         on_disk: false,
+         last_queried_at: None,
+      };
+
+      metadata.merge_labels(BTreeMap::from([
+         ("team".to_string(), "b".to_string()),
+         ("tier".to_string(), String::new()),
+         ("owner".to_string(), "infra".to_string()),
+      ]));
+
+      assert_eq!(
+         metadata.labels,
+         BTreeMap::from([
+            ("owner".to_string(), "infra".to_string()),
+            ("team".to_string(), "b".to_string()),
+         ])
+      );
+   }
+
+   #[tokio::test]
+   async fn test_query_under_renamed_url_hits_old_collection() -> Result<()> {
+      let qdrant = MockQdrant::start().await?;
+      let config = EmbeddingConfig::default();
+      let store = |repo_url: &str| DataStore::with_client(qdrant.client(), repo_url, &config);
+      let old_url = "https://github.com/owner/old-name";
+      let other_url = "https://github.com/other/repo";
+      for (repo_url, content) in [(old_url, "struct Parser;"), (other_url, "struct Other;")] {
+         let data_store = store(repo_url)?;
+         data_store
+            .add_embeddings_batch(vec![(chunk(content), vec![1.0, 0.0])])
+            .await?;
+         data_store
+            .store_metadata(1, None, None, None, BTreeMap::new(), &config)
+            .await?;
+      }
+      // each query opens the collection afresh, as the query tools do
+      let query = |repo_url: &str| {
+         let data_store = store(repo_url);
+         async move {
+            let results = data_store?
+               .query_with_content(vec![1.0, 0.0], 1, &ChunkFilter::default(), None)
+               .await?;
+            anyhow::Ok(results[0].chunk.content.clone())
+         }
+      };
+
+      let new_url = "https://github.com/new-owner/new-name";
+      assert!(query(new_url).await.is_err());
+      let metadata = store(new_url)?.adopt_renamed(old_url).await?;
+      assert_eq!(metadata.repo_url, new_url);
+      assert_eq!(query(new_url).await?, "struct Parser;");
+      assert_eq!(query(old_url).await?, "struct Parser;");
+
+      // renaming again follows the alias to the same collection
+      let newest_url = "https://github.com/new-owner/newest";
+      store(newest_url)?.adopt_renamed(new_url).await?;
+      assert_eq!(query(newest_url).await?, "struct Parser;");
+
+      // an existing collection under the new name would be hidden by the alias
+      let err = store(other_url)?.adopt_renamed(old_url).await.unwrap_err();
+      assert!(err.to_string().contains("already has its own collection"));
+      assert_eq!(query(other_url).await?, "struct Other;");
+      let gone_url = "https://github.com/owner/gone";
+      assert!(store(newest_url)?.adopt_renamed(gone_url).await.is_err());
+      Ok(())
+   }
+}
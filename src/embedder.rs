@@ -0,0 +1,482 @@
+use crate::config::EmbeddingConfig;
+use anyhow::{Context, Result, bail, ensure};
+use async_openai::types::{
+   CreateBase64EmbeddingResponse, CreateEmbeddingRequestArgs, CreateEmbeddingResponse,
+   EncodingFormat,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use reqwest::header::HeaderMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
+use tracing::{debug, warn};
+
+/// OpenAI API endpoint used for embedding requests
+const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
+
+/// Embedding model used for both documents and queries
+pub const EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+/// OpenAI API key a caller embeds with instead of the server's `OPENAI_API_KEY`, so usage is
+/// billed to their account. Redacted from `Debug` output so it never reaches the logs.
+#[derive(Clone, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct ApiKey(String);
+
+impl ApiKey {
+   pub fn new(key: impl Into<String>) -> Self {
+      Self(key.into())
+   }
+}
+
+impl fmt::Debug for ApiKey {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      f.write_str("ApiKey(<redacted>)")
+   }
+}
+
+/// The key OpenAI requests are made with: the caller's when given, the server's
+/// `OPENAI_API_KEY` otherwise. Blank caller keys count as not given.
+pub fn resolve_api_key(api_key: Option<&ApiKey>) -> Result<String> {
+   match api_key.filter(|key| !key.0.trim().is_empty()) {
+      Some(key) => Ok(key.0.trim().to_string()),
+      None => dotenvy::var("OPENAI_API_KEY").context("OPENAI_API_KEY not set"),
+   }
+}
+
+/// An embedding provider the server can embed with, and the models it offers
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProviderInfo {
+   pub name: &'static str,
+   /// Whether the credentials the provider needs are set
+   pub configured: bool,
+   pub models: Vec<ModelInfo>,
+}
+
+/// An embedding model and the dimension of the vectors it produces
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelInfo {
+   pub name: &'static str,
+   pub dimension: u64,
+}
+
+/// Lists the embedding providers and models the server supports. Only OpenAI's
+/// `EMBEDDING_MODEL` is available, reported as configured when `OPENAI_API_KEY` is set.
+pub fn available_providers() -> Vec<ProviderInfo> {
+   vec![ProviderInfo {
+      name: "openai",
+      configured: dotenvy::var("OPENAI_API_KEY").is_ok(),
+      models: vec![ModelInfo {
+         name: EMBEDDING_MODEL,
+         dimension: EmbeddingConfig::default().vector_size,
+      }],
+   }]
+}
+
+/// Timeout applied to each OpenAI request unless `OPENAI_TIMEOUT_SECS` is set
+const DEFAULT_OPENAI_TIMEOUT_SECS: u64 = 60;
+
+/// Attempts made for an embedding request that times out
+const TIMEOUT_ATTEMPTS: u32 = 3;
+
+/// Per-request timeout for OpenAI calls, read from `OPENAI_TIMEOUT_SECS`. Kept separate from
+/// the Qdrant client's timeout since embedding a large batch takes far longer than a search.
+pub fn openai_timeout() -> Duration {
+   let secs = dotenvy::var("OPENAI_TIMEOUT_SECS")
+      .ok()
+      .and_then(|value| value.parse().ok())
+      .unwrap_or(DEFAULT_OPENAI_TIMEOUT_SECS);
+
+   Duration::from_secs(secs)
+}
+
+/// Builds the HTTP client used for OpenAI requests, so a stalled request fails after
+/// `timeout` instead of hanging its batch
+pub fn openai_http_client(timeout: Duration) -> Result<reqwest::Client> {
+   reqwest::Client::builder()
+      .timeout(timeout)
+      .build()
+      .context("Failed to build OpenAI HTTP client")
+}
+
+/// Remaining capacity reported by OpenAI's `x-ratelimit-*` response headers
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateLimit {
+   pub limit_requests: Option<u64>,
+   pub remaining_requests: Option<u64>,
+   pub limit_tokens: Option<u64>,
+   pub remaining_tokens: Option<u64>,
+}
+
+impl RateLimit {
+   /// Reads the rate-limit headers from a response, leaving missing or malformed ones unset
+   pub fn from_headers(headers: &HeaderMap) -> Self {
+      let header = |name: &str| {
+         headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+      };
+
+      Self {
+         limit_requests: header("x-ratelimit-limit-requests"),
+         remaining_requests: header("x-ratelimit-remaining-requests"),
+         limit_tokens: header("x-ratelimit-limit-tokens"),
+         remaining_tokens: header("x-ratelimit-remaining-tokens"),
+      }
+   }
+
+   /// Fraction of the tighter of the request and token budgets that is still available, or
+   /// `None` when the headers were absent
+   pub fn remaining_fraction(&self) -> Option<f64> {
+      let fraction = |remaining: Option<u64>, limit: Option<u64>| match (remaining, limit) {
+         (Some(remaining), Some(limit)) if limit > 0 => Some(remaining as f64 / limit as f64),
+         _ => None,
+      };
+
+      let requests = fraction(self.remaining_requests, self.limit_requests);
+      let tokens = fraction(self.remaining_tokens, self.limit_tokens);
+
+      match (requests, tokens) {
+         (Some(requests), Some(tokens)) => Some(requests.min(tokens)),
+         (requests, tokens) => requests.or(tokens),
+      }
+   }
+}
+
+/// Embeddings for a batch of inputs together with the rate-limit state after the request
+#[derive(Debug)]
+pub struct EmbeddingBatch {
+   pub embeddings: Vec<Vec<f32>>,
+   pub rate_limit: RateLimit,
+}
+
+/// Error of an embedding request that still timed out after `TIMEOUT_ATTEMPTS` attempts, which
+/// lets an embed keep the batches that succeeded
+#[derive(Debug, thiserror::Error)]
+#[error("embedding request timed out after {TIMEOUT_ATTEMPTS} attempts")]
+pub struct EmbeddingTimedOut;
+
+/// Calls the OpenAI embeddings endpoint directly rather than through `async_openai`'s client,
+/// since the client does not expose the response headers needed to track rate limits
+pub struct Embedder {
+   http_client: reqwest::Client,
+   api_key: String,
+   encoding_format: EncodingFormat,
+}
+
+impl Embedder {
+   /// Creates an embedder using the caller's key set in `config`, or the server's
+   /// `OPENAI_API_KEY` when there is none
+   pub fn new(config: &EmbeddingConfig) -> Result<Self> {
+      let api_key = resolve_api_key(config.api_key.as_ref())?;
+
+      // base64 responses are roughly half the size of JSON float arrays
+      let encoding_format = if config.base64_encoding {
+         EncodingFormat::Base64
+      } else {
+         EncodingFormat::Float
+      };
+
+      Ok(Self {
+         http_client: openai_http_client(openai_timeout())?,
+         api_key,
+         encoding_format,
+      })
+   }
+
+   /// Embeds a batch of inputs, returning one vector per input in input order. Requests that
+   /// time out are retried a bounded number of times before failing with `EmbeddingTimedOut`.
+   pub async fn embed(&self, inputs: Vec<String>) -> Result<EmbeddingBatch> {
+      let request = CreateEmbeddingRequestArgs::default()
+         .model(EMBEDDING_MODEL)
+         .input(inputs)
+         .encoding_format(self.encoding_format.clone())
+         .build()?;
+
+      let mut attempt = 1;
+      let response = loop {
+         let result = self
+            .http_client
+            .post(format!("{OPENAI_API_BASE}/embeddings"))
+            .bearer_auth(&self.api_key)
+            .json(&request)
+            .send()
+            .await;
+
+         match result {
+            Ok(response) => break response,
+            Err(e) if e.is_timeout() && attempt < TIMEOUT_ATTEMPTS => {
+               warn!(
+                  "Embedding request timed out (attempt {attempt}/{TIMEOUT_ATTEMPTS}), retrying"
+               );
+               attempt += 1;
+            }
+            Err(e) if e.is_timeout() => return Err(EmbeddingTimedOut.into()),
+            Err(e) => return Err(e).context("Failed to send embedding request"),
+         }
+      };
+
+      let rate_limit = RateLimit::from_headers(response.headers());
+      debug!("Embedding rate limit: {:?}", rate_limit);
+
+      let status = response.status();
+      if !status.is_success() {
+         let body = response.text().await.unwrap_or_default();
+         bail!("Embedding request failed with status {status}: {body}");
+      }
+
+      let embeddings = match self.encoding_format {
+         EncodingFormat::Float => {
+            let mut response: CreateEmbeddingResponse = response
+               .json()
+               .await
+               .context("Failed to parse embedding response")?;
+            response.data.sort_by_key(|embedding| embedding.index);
+
+            response
+               .data
+               .into_iter()
+               .map(|embedding| embedding.embedding)
+               .collect()
+         }
+         EncodingFormat::Base64 => {
+            let mut response: CreateBase64EmbeddingResponse = response
+               .json()
+               .await
+               .context("Failed to parse embedding response")?;
+            response.data.sort_by_key(|embedding| embedding.index);
+
+            response
+               .data
+               .into_iter()
+               .map(|embedding| decode_base64_embedding(&embedding.embedding.0))
+               .collect::<Result<_>>()?
+         }
+      };
+
+      Ok(EmbeddingBatch {
+         embeddings,
+         rate_limit,
+      })
+   }
+}
+
+/// Decodes an embedding returned with `encoding_format: base64`, which is the vector's
+/// little-endian float32 bytes
+pub fn decode_base64_embedding(encoded: &str) -> Result<Vec<f32>> {
+   let bytes = STANDARD
+      .decode(encoded)
+      .context("Invalid base64 in embedding response")?;
+   ensure!(
+      bytes.len() % 4 == 0,
+      "Base64 embedding has {} bytes, which is not a whole number of float32 values",
+      bytes.len()
+   );
+
+   Ok(bytes
+      .chunks_exact(4)
+      .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+      .collect())
+}
+
+/// Adjusts how many embedding batches run at once from the rate-limit headers: concurrency is
+/// halved when the remaining capacity runs low and grows back one step at a time as it
+/// recovers, so bursty workloads back off before hitting 429s without staying throttled.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyController {
+   current: usize,
+   max: usize,
+}
+
+impl ConcurrencyController {
+   /// Remaining capacity below which concurrency is reduced
+   const LOW_WATERMARK: f64 = 0.1;
+   /// Remaining capacity above which concurrency is increased again
+   const HIGH_WATERMARK: f64 = 0.5;
+
+   pub fn new(max: usize) -> Self {
+      let max = max.max(1);
+      Self { current: max, max }
+   }
+
+   pub fn current(&self) -> usize {
+      self.current
+   }
+
+   /// Updates the concurrency from the rate-limit state of a completed request
+   pub fn observe(&mut self, rate_limit: &RateLimit) {
+      let Some(remaining) = rate_limit.remaining_fraction() else {
+         return;
+      };
+
+      let previous = self.current;
+      if remaining < Self::LOW_WATERMARK {
+         self.current = (self.current / 2).max(1);
+      } else if remaining > Self::HIGH_WATERMARK {
+         self.current = (self.current + 1).min(self.max);
+      }
+
+      if self.current != previous {
+         debug!(
+            "Adjusted embedding concurrency from {} to {} ({:.0}% rate limit remaining)",
+            previous,
+            self.current,
+            remaining * 100.0
+         );
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use reqwest::header::HeaderValue;
+
+   fn rate_limit(remaining_requests: u64, remaining_tokens: u64) -> RateLimit {
+      RateLimit {
+         limit_requests: Some(100),
+         remaining_requests: Some(remaining_requests),
+         limit_tokens: Some(1000),
+         remaining_tokens: Some(remaining_tokens),
+      }
+   }
+
+   #[test]
+   fn test_available_providers_describe_the_embedding_model() {
+      let providers = available_providers();
+
+      assert_eq!(providers.len(), 1);
+      assert_eq!(providers[0].name, "openai");
+      assert_eq!(
+         providers[0].models,
+         vec![ModelInfo {
+            name: EMBEDDING_MODEL,
+            dimension: 1536,
+         }]
+      );
+   }
+
+   #[test]
+   fn test_rate_limit_from_headers() {
+      let mut headers = HeaderMap::new();
+      headers.insert(
+         "x-ratelimit-limit-requests",
+         HeaderValue::from_static("100"),
+      );
+      headers.insert(
+         "x-ratelimit-remaining-requests",
+         HeaderValue::from_static("40"),
+      );
+      headers.insert("x-ratelimit-limit-tokens", HeaderValue::from_static("1000"));
+      headers.insert(
+         "x-ratelimit-remaining-tokens",
+         HeaderValue::from_static("100"),
+      );
+
+      let rate_limit = RateLimit::from_headers(&headers);
+      assert_eq!(rate_limit, self::rate_limit(40, 100));
+      // tokens are the tighter budget
+      assert_eq!(rate_limit.remaining_fraction(), Some(0.1));
+
+      assert_eq!(
+         RateLimit::from_headers(&HeaderMap::new()).remaining_fraction(),
+         None
+      );
+   }
+
+   #[test]
+   fn test_decode_base64_embedding() -> Result<()> {
+      // little-endian float32 bytes of [1.0, -2.0, 0.5]
+      assert_eq!(
+         decode_base64_embedding("AACAPwAAAMAAAAA/")?,
+         vec![1.0, -2.0, 0.5]
+      );
+
+      assert!(decode_base64_embedding("AACAPwAA").is_err());
+      assert!(decode_base64_embedding("not base64!").is_err());
+      Ok(())
+   }
+
+   #[test]
+   fn test_base64_embedding_round_trip() -> Result<()> {
+      let embedding: Vec<f32> = (0..1536).map(|i| (i as f32 * 0.37).sin() / 7.0).collect();
+      let bytes: Vec<u8> = embedding
+         .iter()
+         .flat_map(|value| value.to_le_bytes())
+         .collect();
+
+      assert_eq!(decode_base64_embedding(&STANDARD.encode(bytes))?, embedding);
+      Ok(())
+   }
+
+   #[tokio::test]
+   async fn test_openai_http_client_applies_timeout() -> Result<()> {
+      // a server that accepts connections but never responds
+      let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+      let addr = listener.local_addr()?;
+      let server = tokio::spawn(async move {
+         let mut connections = Vec::new();
+         while let Ok((stream, _)) = listener.accept().await {
+            connections.push(stream);
+         }
+      });
+
+      let client = openai_http_client(Duration::from_millis(100))?;
+      let started = std::time::Instant::now();
+      let err = client
+         .get(format!("http://{addr}/v1/embeddings"))
+         .send()
+         .await
+         .unwrap_err();
+
+      assert!(err.is_timeout());
+      assert!(started.elapsed() < Duration::from_secs(5));
+
+      server.abort();
+      Ok(())
+   }
+
+   #[test]
+   fn test_concurrency_controller() {
+      let mut controller = ConcurrencyController::new(5);
+      assert_eq!(controller.current(), 5);
+
+      // low capacity halves concurrency down to a minimum of one
+      controller.observe(&rate_limit(5, 1000));
+      assert_eq!(controller.current(), 2);
+      controller.observe(&rate_limit(100, 10));
+      assert_eq!(controller.current(), 1);
+      controller.observe(&rate_limit(1, 1));
+      assert_eq!(controller.current(), 1);
+
+      // moderate capacity holds steady
+      controller.observe(&rate_limit(30, 300));
+      assert_eq!(controller.current(), 1);
+
+      // recovered capacity grows back up to the maximum
+      for _ in 0..10 {
+         controller.observe(&rate_limit(90, 900));
+      }
+      assert_eq!(controller.current(), 5);
+
+      // missing headers leave concurrency unchanged
+      controller.observe(&RateLimit::default());
+      assert_eq!(controller.current(), 5);
+   }
+
+   #[test]
+   fn test_caller_api_key_is_used_and_never_printed() -> Result<()> {
+      let key: ApiKey = serde_json::from_value(serde_json::json!("sk-tenant-123"))?;
+
+      assert_eq!(resolve_api_key(Some(&key))?, "sk-tenant-123");
+      assert!(!format!("{key:?}").contains("sk-tenant"));
+
+      let config = EmbeddingConfig {
+         api_key: Some(key),
+         ..Default::default()
+      };
+      assert!(!format!("{config:?}").contains("sk-tenant"));
+      Ok(())
+   }
+}
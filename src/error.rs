@@ -1,6 +1,12 @@
-use rmcp::Error as McpError;
+use qdrant_client::QdrantError;
+use rmcp::{Error as McpError, model::ErrorCode};
+use serde_json::json;
 use thiserror::Error;
 
+/// gRPC status codes Qdrant reports when it cannot be reached or does not answer in time
+const GRPC_DEADLINE_EXCEEDED: i32 = 4;
+const GRPC_UNAVAILABLE: i32 = 14;
+
 #[derive(Error, Debug)]
 pub enum BackendError {
    #[error("could not resolve latest crate version for '{0}'")]
@@ -18,14 +24,50 @@ pub enum BackendError {
    #[error("no embedding operation found with ID: {0}")]
    OperationNotFound(String),
 
+   #[error("vector store unavailable, retry later: {0}")]
+   VectorStoreUnavailable(String),
+
    #[error("internal error: {0}")]
-   Internal(#[from] anyhow::Error),
+   Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for BackendError {
+   /// Distinguishes Qdrant being unreachable, which is transient, from other internal errors
+   fn from(err: anyhow::Error) -> Self {
+      if is_vector_store_unavailable(&err) {
+         return BackendError::VectorStoreUnavailable(format!("{err:#}"));
+      }
+      BackendError::Internal(err)
+   }
+}
+
+/// Whether an error was caused by failing to reach Qdrant, as opposed to Qdrant rejecting
+/// the request
+fn is_vector_store_unavailable(err: &anyhow::Error) -> bool {
+   err.chain()
+      .any(|cause| match cause.downcast_ref::<QdrantError>() {
+         Some(QdrantError::ResponseError { status }) => matches!(
+            i32::from(status.code()),
+            GRPC_UNAVAILABLE | GRPC_DEADLINE_EXCEEDED
+         ),
+         Some(QdrantError::Io(_)) => true,
+         _ => false,
+      })
 }
 
 impl From<BackendError> for McpError {
    fn from(err: BackendError) -> Self {
       use BackendError::*;
       match err {
+         // transient infrastructure errors - the client should retry later
+         VectorStoreUnavailable(_) => {
+            tracing::warn!("{}", err);
+            McpError::new(
+               ErrorCode::INTERNAL_ERROR,
+               err.to_string(),
+               Some(json!({ "retryable": true })),
+            )
+         }
          // internal errors
          Internal(_) => {
             tracing::error!("Internal error: {:?}", err);
@@ -36,3 +78,29 @@ impl From<BackendError> for McpError {
       }
    }
 }
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use anyhow::Context;
+
+   #[test]
+   fn test_unreachable_qdrant_is_retryable() {
+      let io_error = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+      let err: anyhow::Result<()> =
+         Err(QdrantError::Io(io_error)).context("failed to list collections from Qdrant");
+
+      let err = BackendError::from(err.unwrap_err());
+      assert!(matches!(err, BackendError::VectorStoreUnavailable(_)));
+
+      let mcp_error = McpError::from(err);
+      assert_eq!(mcp_error.data, Some(json!({ "retryable": true })));
+      assert!(mcp_error.message.contains("retry later"));
+   }
+
+   #[test]
+   fn test_other_errors_stay_internal() {
+      let err = BackendError::from(anyhow::anyhow!("payload could not be parsed"));
+      assert!(matches!(err, BackendError::Internal(_)));
+   }
+}
@@ -0,0 +1,420 @@
+use crate::{
+   config::EmbeddingConfig,
+   data_store::{ChunkPayload, DataStore, StoredChunk},
+   github_processor::embed_chunks,
+   metrics::Metrics,
+   similarity::Centroid,
+};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+   collections::BTreeMap,
+   path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// Counts reported after importing a JSONL export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportSummary {
+   /// Chunks stored with the vectors included in the export
+   pub with_vectors: usize,
+   /// Chunks that had no vector in the export and were embedded again
+   pub re_embedded: usize,
+}
+
+/// Prefixes the chunks of an export were embedded with, which imports record in place of the
+/// importing server's so queries keep matching the exported vectors
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportPrefixes {
+   #[serde(default)]
+   pub document_prefix: String,
+   #[serde(default)]
+   pub query_prefix: String,
+}
+
+/// First line of an export, telling it apart from the chunk records that follow
+#[derive(Serialize, Deserialize)]
+struct ExportHeader {
+   prefixes: ExportPrefixes,
+}
+
+/// Serializes stored chunks as JSON Lines, one chunk per line, after a header line with the
+/// prefixes they were embedded with
+pub fn to_jsonl(prefixes: &ExportPrefixes, chunks: &[StoredChunk]) -> Result<String> {
+   let header = ExportHeader {
+      prefixes: prefixes.clone(),
+   };
+   let mut output = serde_json::to_string(&header)?;
+   output.push('\n');
+
+   for chunk in chunks {
+      output.push_str(&serde_json::to_string(chunk)?);
+      output.push('\n');
+   }
+
+   Ok(output)
+}
+
+/// Exports the chunks of a repository's collection as JSON Lines, see `to_jsonl`, with their
+/// vectors when `include_vectors` is set
+pub async fn export_jsonl(
+   data_store: &DataStore,
+   repo_url: &str,
+   include_vectors: bool,
+) -> Result<(String, usize)> {
+   let chunks = data_store
+      .scroll_chunks(include_vectors)
+      .await
+      .context("failed to read chunks from collection")?;
+   let metadata = DataStore::get_metadata(&data_store.qdrant_client, repo_url).await?;
+   let prefixes = metadata.map_or_else(ExportPrefixes::default, |metadata| ExportPrefixes {
+      document_prefix: metadata.document_prefix,
+      query_prefix: metadata.query_prefix,
+   });
+
+   Ok((to_jsonl(&prefixes, &chunks)?, chunks.len()))
+}
+
+/// Parses JSON Lines produced by `to_jsonl`, reporting the line number of any invalid record.
+/// Exports written before the header was added have no prefixes.
+pub fn parse_jsonl(input: &str) -> Result<(Option<ExportPrefixes>, Vec<StoredChunk>)> {
+   let mut lines = input
+      .lines()
+      .enumerate()
+      .filter(|(_, line)| !line.trim().is_empty())
+      .peekable();
+
+   let prefixes = lines
+      .peek()
+      .and_then(|(_, line)| serde_json::from_str::<ExportHeader>(line).ok())
+      .map(|header| header.prefixes);
+   if prefixes.is_some() {
+      lines.next();
+   }
+
+   let chunks = lines
+      .map(|(i, line)| {
+         serde_json::from_str(line).with_context(|| format!("invalid record on line {}", i + 1))
+      })
+      .collect::<Result<_>>()?;
+
+   Ok((prefixes, chunks))
+}
+
+/// Checks that every vector in an export has the same dimension, returning that dimension
+/// (or `None` when the export contains no vectors)
+pub fn validate_vector_dimensions(chunks: &[StoredChunk]) -> Result<Option<usize>> {
+   let mut dimension = None;
+
+   for (i, chunk) in chunks.iter().enumerate() {
+      let Some(vector) = &chunk.vector else {
+         continue;
+      };
+      match dimension {
+         None => dimension = Some(vector.len()),
+         Some(expected) if expected != vector.len() => bail!(
+            "record {} has a vector of dimension {} but earlier records have dimension {}",
+            i + 1,
+            vector.len(),
+            expected
+         ),
+         Some(_) => {}
+      }
+   }
+
+   Ok(dimension)
+}
+
+/// Loads exported chunks into the store's collection, emptied first. Chunks exported with
+/// their vectors are stored as-is; the rest are embedded again. The collection is created with
+/// the dimension of the first stored vector. The export's prefixes, when it has them, replace
+/// the configured ones both for re-embedding and in the recorded metadata.
+pub async fn import_chunks(
+   mut data_store: DataStore,
+   prefixes: Option<ExportPrefixes>,
+   chunks: Vec<StoredChunk>,
+   metrics: &Metrics,
+) -> Result<ImportSummary> {
+   validate_vector_dimensions(&chunks)?;
+
+   let mut config = EmbeddingConfig::from_env();
+   if let Some(prefixes) = prefixes {
+      config.document_prefix = prefixes.document_prefix;
+      config.query_prefix = prefixes.query_prefix;
+   }
+   data_store.reset().await?;
+
+   let doc_count = chunks.len();
+   let (with_vectors, without_vectors): (Vec<_>, Vec<_>) =
+      chunks.into_iter().partition(|chunk| chunk.vector.is_some());
+
+   let summary = ImportSummary {
+      with_vectors: with_vectors.len(),
+      re_embedded: without_vectors.len(),
+   };
+
+   let mut centroid = Centroid::default();
+   let items: Vec<(ChunkPayload, Vec<f32>)> = with_vectors
+      .into_iter()
+      .map(|StoredChunk { chunk, vector }| {
+         let vector = vector.expect("partitioned on vector presence");
+         centroid.add(&vector);
+         (chunk, vector)
+      })
+      .collect();
+   data_store.add_embeddings_batch(items).await?;
+
+   if !without_vectors.is_empty() {
+      info!(
+         "Re-embedding {} imported chunks without vectors",
+         without_vectors.len()
+      );
+      let chunks = without_vectors
+         .into_iter()
+         .map(|chunk| chunk.chunk)
+         .collect();
+      centroid.merge(embed_chunks(&data_store, chunks, &config, metrics).await?);
+   }
+
+   data_store
+      .store_metadata(
+         doc_count,
+         centroid.finish(),
+         None,
+         None,
+         BTreeMap::new(),
+         &config,
+      )
+      .await?;
+
+   info!("Imported {doc_count} chunks");
+   Ok(summary)
+}
+
+/// Directories that exports may be written to, read from the comma separated `EXPORT_DIRS`
+/// environment variable. Writing exports to disk is disabled when it is not set.
+pub fn allowed_export_dirs() -> Vec<PathBuf> {
+   dotenvy::var("EXPORT_DIRS")
+      .map(|dirs| {
+         dirs
+            .split(',')
+            .map(str::trim)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .collect()
+      })
+      .unwrap_or_default()
+}
+
+/// Resolves a requested export file path, ensuring it lies within one of the allowed
+/// directories so clients cannot write arbitrary files on the server
+pub fn resolve_export_path(path: &str, allowed_dirs: &[PathBuf]) -> Result<PathBuf> {
+   if allowed_dirs.is_empty() {
+      bail!("Writing exports to disk is disabled. Set EXPORT_DIRS to allow export directories");
+   }
+
+   let path = Path::new(path);
+   let file_name = path.file_name().context("export path must name a file")?;
+   let parent = match path.parent() {
+      Some(parent) if !parent.as_os_str().is_empty() => parent,
+      _ => Path::new("."),
+   };
+   // canonicalize the directory to resolve `..` segments and symlinks
+   let parent = parent
+      .canonicalize()
+      .with_context(|| format!("export directory does not exist: {}", parent.display()))?;
+
+   let allowed = allowed_dirs
+      .iter()
+      .filter_map(|dir| dir.canonicalize().ok())
+      .any(|dir| parent.starts_with(dir));
+   if !allowed {
+      bail!(
+         "export path {} is outside the allowed export directories",
+         path.display()
+      );
+   }
+
+   Ok(parent.join(file_name))
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::{chunks::ChunkKind, data_store::ChunkFilter, mock_qdrant::MockQdrant};
+   use tempfile::TempDir;
+
+   fn stored_chunk(content: &str, vector: Option<Vec<f32>>) -> StoredChunk {
+      StoredChunk {
+         chunk: ChunkPayload {
+            content: content.to_string(),
+            file_path: Some("src/lib.rs".to_string()),
+            start_line: Some(1),
+            end_line: Some(3),
+            kind: Some(ChunkKind::Function),
+            ..Default::default()
+         },
+         vector,
+      }
+   }
+
+   #[test]
+   fn test_jsonl_round_trip() -> Result<()> {
+      let chunks = vec![
+         stored_chunk(
+            "fn main() {\n    println!(\"hi\");\n}",
+            Some(vec![0.1, -0.2, 0.3]),
+         ),
+         stored_chunk("/// docs\nstruct Foo;", None),
+      ];
+
+      let prefixes = ExportPrefixes {
+         document_prefix: "passage: ".to_string(),
+         query_prefix: "query: ".to_string(),
+      };
+
+      let jsonl = to_jsonl(&prefixes, &chunks)?;
+      assert_eq!(jsonl.lines().count(), 3);
+      assert!(!jsonl.lines().nth(2).unwrap().contains("vector"));
+
+      assert_eq!(parse_jsonl(&jsonl)?, (Some(prefixes), chunks));
+      Ok(())
+   }
+
+   /// Chunks stored in a collection with their vectors, in content order
+   async fn sorted_chunks(data_store: &DataStore) -> Result<Vec<StoredChunk>> {
+      let mut chunks = data_store.scroll_chunks(true).await?;
+      chunks.sort_by(|a, b| a.chunk.content.cmp(&b.chunk.content));
+      Ok(chunks)
+   }
+
+   #[tokio::test]
+   async fn test_export_then_import_keeps_payloads_and_vectors() -> Result<()> {
+      let qdrant = MockQdrant::start().await?;
+      let config = EmbeddingConfig {
+         document_prefix: "passage: ".to_string(),
+         query_prefix: "query: ".to_string(),
+         ..EmbeddingConfig::default()
+      };
+      let source_url = "https://github.com/owner/source";
+      let source = DataStore::with_client(qdrant.client(), source_url, &config)?;
+      let mut parse = stored_chunk("fn parse() {}", None).chunk;
+      parse.name = Some("parse".to_string());
+      let render = stored_chunk("fn render() {}", None).chunk;
+      source
+         .add_embeddings_batch(vec![(parse, vec![1.0, 0.0]), (render, vec![0.6, 0.8])])
+         .await?;
+      source
+         .store_metadata(2, None, None, None, BTreeMap::new(), &config)
+         .await?;
+
+      let (jsonl, count) = export_jsonl(&source, source_url, true).await?;
+      assert_eq!(count, 2);
+      let (prefixes, chunks) = parse_jsonl(&jsonl)?;
+      let target_url = "https://github.com/owner/target";
+      let target = DataStore::with_client(qdrant.client(), target_url, &config)?;
+      let summary = import_chunks(target, prefixes, chunks, &Metrics::default()).await?;
+      assert_eq!(summary.with_vectors, 2);
+      assert_eq!(summary.re_embedded, 0);
+
+      let imported = DataStore::with_client(qdrant.client(), target_url, &config)?;
+      assert_eq!(
+         sorted_chunks(&imported).await?,
+         sorted_chunks(&source).await?
+      );
+      let metadata = DataStore::get_metadata(&qdrant.client(), target_url)
+         .await?
+         .unwrap();
+      assert_eq!(metadata.doc_count, 2);
+      assert_eq!(metadata.document_prefix, "passage: ");
+      assert_eq!(metadata.query_prefix, "query: ");
+      Ok(())
+   }
+
+   #[tokio::test]
+   async fn test_imported_collection_is_queryable() -> Result<()> {
+      let qdrant = MockQdrant::start().await?;
+      let repo_url = "https://github.com/owner/repo";
+      let config = EmbeddingConfig::default();
+      let jsonl = to_jsonl(
+         &ExportPrefixes::default(),
+         &[
+            stored_chunk("fn parse() {}", Some(vec![1.0, 0.0])),
+            stored_chunk("fn render() {}", Some(vec![0.0, 1.0])),
+         ],
+      )?;
+
+      let (prefixes, chunks) = parse_jsonl(&jsonl)?;
+      let data_store = DataStore::with_client(qdrant.client(), repo_url, &config)?;
+      import_chunks(data_store, prefixes, chunks, &Metrics::default()).await?;
+
+      // queries open the collection afresh, as the query tools do
+      let data_store = DataStore::with_client(qdrant.client(), repo_url, &config)?;
+      assert_eq!(data_store.vector_size().await?, 2);
+      let results = data_store
+         .query_with_content(vec![0.1, 0.9], 1, &ChunkFilter::default(), None)
+         .await?;
+      assert_eq!(results.len(), 1);
+      assert_eq!(results[0].chunk.content, "fn render() {}");
+      Ok(())
+   }
+
+   #[test]
+   fn test_parse_jsonl_without_header() -> Result<()> {
+      let (prefixes, chunks) = parse_jsonl("{\"content\": \"ok\"}\n")?;
+      assert_eq!(prefixes, None);
+      assert_eq!(chunks.len(), 1);
+      Ok(())
+   }
+
+   #[test]
+   fn test_parse_jsonl_reports_invalid_line() {
+      let err = parse_jsonl("{\"content\": \"ok\"}\n{\"no_content\": 1}\n").unwrap_err();
+      assert!(err.to_string().contains("line 2"));
+   }
+
+   #[test]
+   fn test_validate_vector_dimensions() -> Result<()> {
+      assert_eq!(validate_vector_dimensions(&[])?, None);
+      assert_eq!(
+         validate_vector_dimensions(&[
+            stored_chunk("a", Some(vec![0.0; 4])),
+            stored_chunk("b", None),
+            stored_chunk("c", Some(vec![1.0; 4])),
+         ])?,
+         Some(4)
+      );
+
+      let err = validate_vector_dimensions(&[
+         stored_chunk("a", Some(vec![0.0; 4])),
+         stored_chunk("b", Some(vec![0.0; 3])),
+      ])
+      .unwrap_err();
+      assert!(err.to_string().contains("record 2"));
+      Ok(())
+   }
+
+   #[test]
+   fn test_resolve_export_path() -> Result<()> {
+      let allowed = TempDir::new()?;
+      let other = TempDir::new()?;
+      let allowed_dirs = vec![allowed.path().to_path_buf()];
+
+      let inside = allowed.path().join("export.jsonl");
+      assert_eq!(
+         resolve_export_path(inside.to_str().unwrap(), &allowed_dirs)?,
+         allowed.path().canonicalize()?.join("export.jsonl")
+      );
+
+      let outside = other.path().join("export.jsonl");
+      assert!(resolve_export_path(outside.to_str().unwrap(), &allowed_dirs).is_err());
+
+      // `..` segments cannot escape the allowed directory
+      let escape = allowed.path().join("..").join("export.jsonl");
+      assert!(resolve_export_path(escape.to_str().unwrap(), &allowed_dirs).is_err());
+
+      // exports are disabled without an allow-list
+      assert!(resolve_export_path(inside.to_str().unwrap(), &[]).is_err());
+      Ok(())
+   }
+}
@@ -0,0 +1,253 @@
+use crate::chunks::{Chunk, ChunkKind, CommitInfo};
+use anyhow::Result;
+use chrono::DateTime;
+use git2::{DiffFormat, Repository, Sort};
+use std::{
+   collections::{HashMap, HashSet},
+   path::Path,
+};
+
+/// Path under which commit chunks are stored, since they belong to no file of the repository
+pub const GIT_HISTORY_PATH: &str = "<git history>";
+
+/// Characters of a commit's diff kept in its chunk, so large commits stay embeddable
+const MAX_DIFF_CHARS: usize = 4000;
+
+/// Extracts the messages of up to `max_commits` commits reachable from HEAD, newest first, as
+/// `ChunkKind::Commit` chunks named after the commit SHA and carrying its author and time.
+/// Merge commits of pull requests carry the PR title and description in their message, so
+/// those are embedded too. The `diff_commits` most recent commits also include their
+/// (truncated) diff. The repository needs its history fetched, a shallow clone only yields the
+/// commits it contains.
+pub fn commit_chunks(
+   repo_path: &Path,
+   max_commits: usize,
+   diff_commits: usize,
+) -> Result<Vec<Chunk>> {
+   let repo = Repository::open(repo_path)?;
+   let mut revwalk = repo.revwalk()?;
+   revwalk.set_sorting(Sort::TIME)?;
+   revwalk.push_head()?;
+
+   let mut chunks = Vec::new();
+   for (i, oid) in revwalk.take(max_commits).enumerate() {
+      let commit = repo.find_commit(oid?)?;
+      let message = commit.message().unwrap_or_default().trim();
+      let date = DateTime::from_timestamp(commit.time().seconds(), 0)
+         .map(|date| date.to_rfc3339())
+         .unwrap_or_default();
+      let author = commit.author();
+
+      let mut content = format!(
+         "commit {}\nAuthor: {}\nDate: {date}\n\n{message}",
+         commit.id(),
+         author.name().unwrap_or_default()
+      );
+      if i < diff_commits
+         && let Some(diff) = commit_diff(&repo, &commit)?
+      {
+         content.push_str("\n\n");
+         content.push_str(&diff);
+      }
+
+      chunks.push(Chunk {
+         kind: ChunkKind::Commit,
+         name: Some(commit.id().to_string()),
+         commit: Some(CommitInfo {
+            author: author.name().unwrap_or_default().to_string(),
+            time: commit.time().seconds(),
+         }),
+         start_line: 1,
+         end_line: content.lines().count(),
+         content,
+         ..Default::default()
+      });
+   }
+
+   Ok(chunks)
+}
+
+/// Finds the Unix time of the last commit changing each of `file_paths`, walking the history
+/// from HEAD newest first until every file is found. Each commit is compared to its first
+/// parent, so changes brought in by a merge are dated at the merge. Files no walked commit
+/// touches, as in a shallow clone, are missing from the result.
+pub fn file_modification_times(
+   repo_path: &Path,
+   file_paths: &[&str],
+) -> Result<HashMap<String, i64>> {
+   let repo = Repository::open(repo_path)?;
+   let mut revwalk = repo.revwalk()?;
+   revwalk.set_sorting(Sort::TIME)?;
+   revwalk.push_head()?;
+
+   let mut pending: HashSet<&str> = file_paths.iter().copied().collect();
+   let mut times = HashMap::new();
+   for oid in revwalk {
+      if pending.is_empty() {
+         break;
+      }
+      let commit = repo.find_commit(oid?)?;
+      let parent_tree = match commit.parents().next() {
+         Some(parent) => Some(parent.tree()?),
+         None => None,
+      };
+      let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+
+      for delta in diff.deltas() {
+         let Some(path) = delta.new_file().path().and_then(Path::to_str) else {
+            continue;
+         };
+         if pending.remove(path) {
+            times.insert(path.to_string(), commit.time().seconds());
+         }
+      }
+   }
+
+   Ok(times)
+}
+
+/// Renders the patch a commit introduces over its first parent, truncated to
+/// `MAX_DIFF_CHARS`. Returns `None` for commits without changes.
+fn commit_diff(repo: &Repository, commit: &git2::Commit) -> Result<Option<String>> {
+   let parent_tree = match commit.parents().next() {
+      Some(parent) => Some(parent.tree()?),
+      None => None,
+   };
+   let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+
+   let mut patch = String::new();
+   diff
+      .print(DiffFormat::Patch, |_, _, line| {
+         if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin());
+         }
+         patch.push_str(&String::from_utf8_lossy(line.content()));
+         patch.chars().count() < MAX_DIFF_CHARS
+      })
+      .or_else(|e| {
+         // returning false from the callback stops printing, which git2 reports as an error
+         if e.code() == git2::ErrorCode::User {
+            Ok(())
+         } else {
+            Err(e)
+         }
+      })?;
+
+   if patch.is_empty() {
+      return Ok(None);
+   }
+   Ok(Some(patch.chars().take(MAX_DIFF_CHARS).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use git2::Signature;
+
+   /// Creates a repository with one commit per message, each changing `notes.md`
+   fn fixture_repo(messages: &[&str]) -> Result<tempfile::TempDir> {
+      let files: Vec<(&str, &str)> = messages
+         .iter()
+         .map(|message| ("notes.md", *message))
+         .collect();
+      fixture_repo_with_files(&files)
+   }
+
+   /// Creates a repository with one commit per `(file, message)` pair, each changing the file,
+   /// one minute apart
+   fn fixture_repo_with_files(commits: &[(&str, &str)]) -> Result<tempfile::TempDir> {
+      let dir = tempfile::tempdir()?;
+      let repo = Repository::init(dir.path())?;
+
+      for (i, (file, message)) in commits.iter().enumerate() {
+         std::fs::write(dir.path().join(file), format!("revision {i}\n"))?;
+         let mut index = repo.index()?;
+         index.add_path(Path::new(file))?;
+         index.write()?;
+         let tree = repo.find_tree(index.write_tree()?)?;
+
+         let time = git2::Time::new(1_700_000_000 + i as i64 * 60, 0);
+         let signature = Signature::new("Ada", "ada@example.com", &time)?;
+         let parent = repo
+            .head()
+            .ok()
+            .map(|head| head.peel_to_commit())
+            .transpose()?;
+         let parents: Vec<&git2::Commit> = parent.iter().collect();
+         repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+         )?;
+      }
+
+      Ok(dir)
+   }
+
+   #[test]
+   fn test_commit_messages_become_chunks() -> Result<()> {
+      let repo_dir = fixture_repo(&[
+         "Add release notes",
+         "Fix typo in notes\n\nThe version number was wrong.",
+      ])?;
+
+      let chunks = commit_chunks(repo_dir.path(), 100, 1)?;
+
+      assert_eq!(chunks.len(), 2);
+      assert!(chunks.iter().all(|chunk| chunk.kind == ChunkKind::Commit));
+
+      // newest first, with the SHA as name and the date in the content
+      let head = Repository::open(repo_dir.path())?
+         .head()?
+         .peel_to_commit()?
+         .id()
+         .to_string();
+      assert_eq!(chunks[0].name.as_deref(), Some(head.as_str()));
+      assert!(chunks[0].content.contains("Fix typo in notes"));
+      assert!(chunks[0].content.contains("The version number was wrong."));
+      assert!(
+         chunks[0]
+            .content
+            .contains("Date: 2023-11-14T22:14:20+00:00")
+      );
+      assert!(chunks[1].content.contains("Add release notes"));
+      let commit = chunks[0].commit.as_ref().unwrap();
+      assert_eq!(commit.author, "Ada");
+      assert_eq!(commit.time, 1_700_000_060);
+
+      // only the most recent commit includes its diff
+      assert!(chunks[0].content.contains("+revision 1"));
+      assert!(!chunks[1].content.contains("+revision 0"));
+      Ok(())
+   }
+
+   #[test]
+   fn test_commit_chunks_are_capped() -> Result<()> {
+      let repo_dir = fixture_repo(&["First", "Second", "Third"])?;
+
+      let chunks = commit_chunks(repo_dir.path(), 2, 0)?;
+
+      assert_eq!(chunks.len(), 2);
+      assert!(chunks[0].content.contains("Third"));
+      Ok(())
+   }
+
+   #[test]
+   fn test_file_modification_times_use_last_changing_commit() -> Result<()> {
+      let repo_dir = fixture_repo_with_files(&[
+         ("lib.rs", "Add library"),
+         ("notes.md", "Add notes"),
+         ("lib.rs", "Fix library"),
+      ])?;
+
+      let times = file_modification_times(repo_dir.path(), &["lib.rs", "notes.md", "gone.rs"])?;
+
+      assert_eq!(times["lib.rs"], 1_700_000_120);
+      assert_eq!(times["notes.md"], 1_700_000_060);
+      assert!(!times.contains_key("gone.rs"));
+      Ok(())
+   }
+}
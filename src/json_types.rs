@@ -18,17 +18,64 @@ pub struct Item {
    pub name: Option<String>,
    pub docs: Option<String>,
    pub span: Option<Span>,
-   pub inner: HashMap<String, Value>,
+   /// Kind-specific fields, see `item_type` for the shapes rustdoc has used
+   #[serde(default)]
+   pub inner: Value,
    // Skip all other fields we don't need
    #[serde(flatten)]
    _other: HashMap<String, Value>,
 }
 
 impl Item {
-   /// Extracts the Rust item type (e.g., "struct", "function") from the inner field
-   /// to categorize documentation entries for better search relevance
+   /// Extracts the Rust item type (e.g., "struct", "function") to categorize documentation
+   /// entries for better search relevance. Depending on the format version, rustdoc JSON
+   /// names it as the single key of `inner` (`{"struct": {...}}`), as a `kind` tag inside
+   /// `inner` (`{"kind": "struct", ...}`), or as a `kind` field of the item itself next to
+   /// an untagged `inner`; fieldless kinds may be a bare string.
    pub fn item_type(&self) -> Option<&str> {
-      self.inner.keys().next().map(|s| s.as_str())
+      if let Some(kind) = self._other.get("kind").and_then(Value::as_str) {
+         return Some(kind);
+      }
+
+      match &self.inner {
+         Value::String(kind) => Some(kind),
+         Value::Object(inner) => match inner.get("kind").and_then(Value::as_str) {
+            Some(kind) => Some(kind),
+            // any other key would be one of the kind's fields, not the kind
+            None if inner.len() == 1 => inner.keys().next().map(String::as_str),
+            None => None,
+         },
+         _ => None,
+      }
+   }
+
+   /// Fields describing the item's kind, whichever shape `inner` has
+   fn kind_fields(&self) -> &Value {
+      match (self.item_type(), self.inner.as_object()) {
+         (Some(kind), Some(inner)) if inner.len() == 1 && inner.contains_key(kind) => &inner[kind],
+         _ => &self.inner,
+      }
+   }
+
+   /// Whether this is the crate's root module, whose docs are the crate-level overview
+   pub fn is_crate_root(&self) -> bool {
+      self.item_type() == Some("module")
+         && self
+            .kind_fields()
+            .get("is_crate")
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+   }
+
+   /// Whether the item is part of the public API. Impl blocks and trait items have the
+   /// `default` visibility, which follows that of their parent, so they count as public;
+   /// `crate` and `pub(in ...)` restricted items, the latter given as a
+   /// `{"restricted": {...}}` object, do not. Items without a visibility count as public.
+   pub fn is_public(&self) -> bool {
+      match self._other.get("visibility") {
+         None => true,
+         Some(visibility) => matches!(visibility.as_str(), Some("public") | Some("default")),
+      }
    }
 }
 
@@ -42,3 +89,57 @@ pub struct Span {
    #[serde(flatten)]
    _other: HashMap<String, Value>,
 }
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn item(json: &str) -> Item {
+      serde_json::from_str(json).expect("valid item")
+   }
+
+   #[test]
+   fn test_item_type_from_externally_tagged_inner() {
+      let function = item(r#"{"crate_id": 0, "inner": {"function": {"has_body": true}}}"#);
+      assert_eq!(function.item_type(), Some("function"));
+
+      let root = item(r#"{"crate_id": 0, "inner": {"module": {"is_crate": true, "items": []}}}"#);
+      assert_eq!(root.item_type(), Some("module"));
+      assert!(root.is_crate_root());
+   }
+
+   #[test]
+   fn test_item_type_from_internally_tagged_inner() {
+      let function = item(
+         r#"{"crate_id": 0, "inner": {"kind": "function", "has_body": true, "generics": {}}}"#,
+      );
+      assert_eq!(function.item_type(), Some("function"));
+
+      let root = item(r#"{"crate_id": 0, "inner": {"kind": "module", "is_crate": true}}"#);
+      assert_eq!(root.item_type(), Some("module"));
+      assert!(root.is_crate_root());
+   }
+
+   #[test]
+   fn test_item_type_from_item_kind() {
+      // older formats tag the item itself; `inner` holds only the kind's fields, whose
+      // single key must not be taken for the kind
+      let root = item(r#"{"crate_id": 0, "kind": "module", "inner": {"is_crate": true}}"#);
+      assert_eq!(root.item_type(), Some("module"));
+      assert!(root.is_crate_root());
+
+      let unit = item(r#"{"crate_id": 0, "inner": "extern_type"}"#);
+      assert_eq!(unit.item_type(), Some("extern_type"));
+      assert!(!unit.is_crate_root());
+   }
+
+   #[test]
+   fn test_item_type_unknown_for_ambiguous_inner() {
+      let ambiguous = item(r#"{"crate_id": 0, "inner": {"is_crate": true, "items": []}}"#);
+      assert_eq!(ambiguous.item_type(), None);
+      assert!(!ambiguous.is_crate_root());
+
+      let missing = item(r#"{"crate_id": 0}"#);
+      assert_eq!(missing.item_type(), None);
+   }
+}
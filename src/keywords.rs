@@ -0,0 +1,248 @@
+use crate::sparse::tokenize;
+use std::collections::{HashMap, HashSet};
+
+/// Terms shorter than this are ignored, which drops loop variables and most noise
+const MIN_TERM_LEN: usize = 3;
+
+/// Rust and TypeScript keywords, primitive types and common English words that appear in
+/// nearly every repository and say nothing about what it does
+const STOPWORDS: &[&str] = &[
+   // Rust
+   "as",
+   "async",
+   "await",
+   "box",
+   "break",
+   "const",
+   "continue",
+   "crate",
+   "dyn",
+   "else",
+   "enum",
+   "extern",
+   "false",
+   "impl",
+   "let",
+   "loop",
+   "match",
+   "mod",
+   "move",
+   "mut",
+   "pub",
+   "ref",
+   "return",
+   "self",
+   "static",
+   "struct",
+   "super",
+   "trait",
+   "true",
+   "type",
+   "unsafe",
+   "use",
+   "where",
+   "while",
+   "bool",
+   "char",
+   "str",
+   "string",
+   "u8",
+   "u16",
+   "u32",
+   "u64",
+   "usize",
+   "i32",
+   "i64",
+   "f32",
+   "f64",
+   "vec",
+   "option",
+   "some",
+   "none",
+   "result",
+   "err",
+   "new",
+   "clone",
+   "into",
+   "from",
+   "unwrap",
+   "default",
+   "derive",
+   "debug",
+   "test",
+   "tests",
+   "assert",
+   "assert_eq",
+   // TypeScript
+   "class",
+   "export",
+   "function",
+   "import",
+   "interface",
+   "null",
+   "number",
+   "private",
+   "public",
+   "readonly",
+   "this",
+   "undefined",
+   "var",
+   "void",
+   "extends",
+   "implements",
+   "any",
+   "unknown",
+   // English
+   "the",
+   "and",
+   "for",
+   "with",
+   "that",
+   "are",
+   "not",
+   "can",
+   "you",
+   "your",
+   "will",
+   "all",
+   "its",
+   "has",
+   "have",
+   "was",
+   "but",
+   "which",
+   "when",
+   "then",
+   "than",
+   "also",
+   "our",
+   "used",
+   "using",
+   "see",
+   "more",
+   "only",
+   "other",
+   "should",
+   "must",
+   "may",
+   "each",
+];
+
+/// Ranks the terms of a collection by TF-IDF summed over its chunks, so terms that are
+/// frequent in the repository but concentrated in some chunks rank above ubiquitous ones.
+/// Returns at most `limit` terms with their scores, highest first.
+pub fn top_terms<'a>(
+   documents: impl IntoIterator<Item = &'a str>,
+   limit: usize,
+) -> Vec<(String, f32)> {
+   let stopwords: HashSet<&str> = STOPWORDS.iter().copied().collect();
+   let mut term_frequencies: Vec<HashMap<String, usize>> = Vec::new();
+   let mut document_frequencies: HashMap<String, usize> = HashMap::new();
+
+   for document in documents {
+      let mut frequencies: HashMap<String, usize> = HashMap::new();
+      for term in tokenize(document) {
+         if is_keyword_candidate(&term, &stopwords) {
+            *frequencies.entry(term).or_default() += 1;
+         }
+      }
+      for term in frequencies.keys() {
+         *document_frequencies.entry(term.clone()).or_default() += 1;
+      }
+      term_frequencies.push(frequencies);
+   }
+
+   let document_count = term_frequencies.len() as f32;
+   let mut scores: HashMap<String, f32> = HashMap::new();
+   for frequencies in term_frequencies {
+      for (term, frequency) in frequencies {
+         // smoothed so terms present in every chunk still score above zero
+         let idf = ((document_count + 1.0) / (document_frequencies[&term] as f32 + 1.0)).ln() + 1.0;
+         *scores.entry(term).or_default() += frequency as f32 * idf;
+      }
+   }
+
+   let mut ranked: Vec<(String, f32)> = scores.into_iter().collect();
+   ranked.sort_by(|(term_a, a), (term_b, b)| b.total_cmp(a).then_with(|| term_a.cmp(term_b)));
+   ranked.truncate(limit);
+   ranked
+}
+
+/// Marks with `**` the lines of a result that contain a term of the query, so the reader can
+/// tell which part of a large chunk matched. Stopwords and short terms are ignored. Content
+/// matched on meaning alone, without any line containing a query term, is returned unmarked.
+pub fn highlight_matching_lines(content: &str, query: &str) -> String {
+   let stopwords: HashSet<&str> = STOPWORDS.iter().copied().collect();
+   let terms: HashSet<String> = tokenize(query)
+      .into_iter()
+      .filter(|term| is_keyword_candidate(term, &stopwords))
+      .collect();
+   if terms.is_empty() {
+      return content.to_string();
+   }
+
+   content
+      .split_inclusive('\n')
+      .map(|line| {
+         let text = line.trim_end();
+         if !tokenize(text).iter().any(|token| terms.contains(token)) {
+            return line.to_string();
+         }
+         let body = text.trim_start();
+         let indent = &text[..text.len() - body.len()];
+         format!("{indent}**{body}**{}", &line[text.len()..])
+      })
+      .collect()
+}
+
+fn is_keyword_candidate(term: &str, stopwords: &HashSet<&str>) -> bool {
+   term.len() >= MIN_TERM_LEN
+      && !stopwords.contains(term)
+      && !term.chars().all(|c| c.is_ascii_digit() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_top_terms_rank_domain_identifiers() {
+      let chunks = [
+         "pub struct HttpClient { timeout: Duration }",
+         "impl HttpClient {\n    pub fn send_request(&self, request: Request) -> Response {}\n}",
+         "/// Retries a request with exponential backoff\npub fn retry_request(request: Request) \
+          {}",
+         "pub enum Response { Ok(String), Error(String) }",
+      ];
+
+      let terms = top_terms(chunks, 5);
+      let names: Vec<&str> = terms.iter().map(|(term, _)| term.as_str()).collect();
+
+      assert_eq!(names[0], "request");
+      assert!(names.contains(&"httpclient"));
+      assert!(names.contains(&"response"));
+      // keywords and primitive types never rank
+      assert!(
+         !names
+            .iter()
+            .any(|term| ["pub", "fn", "impl", "string", "self"].contains(term))
+      );
+   }
+
+   #[test]
+   fn test_highlight_marks_lines_containing_query_terms() {
+      let chunk = "impl Client {\n    pub fn send_request(&self) -> Response {\n        \
+                   self.retry()\n    }\n}\n";
+
+      assert_eq!(
+         highlight_matching_lines(chunk, "how to send a request"),
+         "impl Client {\n    **pub fn send_request(&self) -> Response {**\n        \
+          self.retry()\n    }\n}\n"
+      );
+      // a purely semantic match leaves the chunk unmarked
+      assert_eq!(
+         highlight_matching_lines(chunk, "HTTP backoff policy"),
+         chunk
+      );
+   }
+}
@@ -1,6 +1,12 @@
-use crate::{backend::Backend, logging::CustomFormatter};
+use crate::{
+   backend::{Backend, EmbedOperations},
+   logging::CustomFormatter,
+   metrics::Metrics,
+   query::{QueryEmbeddingCache, QueryLimiter},
+};
 use anyhow::Result;
 use rmcp::transport::sse_server::{SseServer, SseServerConfig};
+use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 use tracing_subscriber::{self, EnvFilter};
 
@@ -9,12 +15,23 @@ pub mod chunk_repo;
 pub mod chunks;
 pub mod config;
 pub mod data_store;
+pub mod embedder;
 pub mod error;
+pub mod export;
+pub mod git_history;
 pub mod github_processor;
 pub mod json_types;
+pub mod keywords;
 pub mod logging;
+pub mod metrics;
+#[cfg(test)]
+mod mock_qdrant;
 pub mod my_types;
+pub mod prompts;
 pub mod query;
+pub mod redact;
+pub mod similarity;
+pub mod sparse;
 pub mod utils;
 
 #[tokio::main]
@@ -29,6 +46,11 @@ async fn main() -> Result<()> {
 
    tracing::info!("Starting MCP SSE server");
 
+   let removed = chunk_repo::remove_stale_clone_dirs(chunk_repo::STALE_CLONE_DIR_AGE);
+   if removed > 0 {
+      tracing::info!("Removed {removed} clone directories left behind by a previous run");
+   }
+
    let port = std::env::var("PORT").unwrap_or("8080".to_string());
    let bind_addr = format!("0.0.0.0:{port}");
 
@@ -42,6 +64,16 @@ async fn main() -> Result<()> {
 
    let (sse_server, router) = SseServer::new(config);
 
+   // every MCP session gets its own backend, so they share one metrics registry
+   let metrics = Arc::new(Metrics::new());
+   let router = router.route(
+      "/metrics",
+      axum::routing::get({
+         let metrics = metrics.clone();
+         move || async move { metrics.render() }
+      }),
+   );
+
    let listener = tokio::net::TcpListener::bind(sse_server.config.bind).await?;
    let server_address = sse_server.config.bind;
 
@@ -59,7 +91,21 @@ async fn main() -> Result<()> {
    });
 
    let server_ct = sse_server.config.ct.clone();
-   let ct = sse_server.with_service(move || Backend::new(server_ct.clone()));
+   // as are cached query embeddings, so a query repeated in another session skips the API
+   let query_cache = Arc::new(QueryEmbeddingCache::default());
+   // and the query limit holds for the server, not per session
+   let query_limiter = Arc::new(QueryLimiter::default());
+   // and embeds in flight, so a repository embedded from two sessions is embedded once
+   let embed_operations = EmbedOperations::default();
+   let ct = sse_server.with_service(move || {
+      Backend::new(
+         embed_operations.clone(),
+         server_ct.clone(),
+         metrics.clone(),
+         query_cache.clone(),
+         query_limiter.clone(),
+      )
+   });
 
    tracing::info!("Server running at http://{server_address}");
 
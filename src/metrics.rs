@@ -0,0 +1,177 @@
+use prometheus::{
+   Histogram, HistogramOpts, HistogramTimer, HistogramVec, IntCounter, IntCounterVec, Opts,
+   Registry, TextEncoder,
+};
+use std::time::Duration;
+
+/// Histogram buckets in seconds for embeds, which take from seconds to tens of minutes
+const EMBED_DURATION_BUCKETS: &[f64] = &[1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0];
+
+/// Prometheus metrics of the server, rendered by the `/metrics` route. Shared by all MCP
+/// sessions, so counts cover the whole process.
+pub struct Metrics {
+   registry: Registry,
+   embeds_started: IntCounter,
+   embeds_completed: IntCounter,
+   embeds_failed: IntCounter,
+   embed_duration: Histogram,
+   chunks_embedded: IntCounter,
+   queries: IntCounterVec,
+   query_duration: HistogramVec,
+   provider_requests: IntCounterVec,
+   provider_request_duration: HistogramVec,
+}
+
+impl Default for Metrics {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
+impl Metrics {
+   pub fn new() -> Self {
+      let registry = Registry::new();
+
+      let embeds_started = IntCounter::new("embeds_started_total", "Repository embeds started")
+         .expect("valid metric");
+      let embeds_completed =
+         IntCounter::new("embeds_completed_total", "Repository embeds completed")
+            .expect("valid metric");
+      let embeds_failed = IntCounter::new("embeds_failed_total", "Repository embeds that failed")
+         .expect("valid metric");
+      let embed_duration = Histogram::with_opts(
+         HistogramOpts::new(
+            "embed_duration_seconds",
+            "Time taken by completed repository embeds",
+         )
+         .buckets(EMBED_DURATION_BUCKETS.to_vec()),
+      )
+      .expect("valid metric");
+      let chunks_embedded = IntCounter::new("chunks_embedded_total", "Chunks embedded and stored")
+         .expect("valid metric");
+      let queries = IntCounterVec::new(
+         Opts::new("queries_total", "Queries served, by tool"),
+         &["tool"],
+      )
+      .expect("valid metric");
+      let query_duration = HistogramVec::new(
+         HistogramOpts::new(
+            "query_duration_seconds",
+            "Time taken to serve queries, by tool",
+         ),
+         &["tool"],
+      )
+      .expect("valid metric");
+      let provider_requests = IntCounterVec::new(
+         Opts::new(
+            "provider_requests_total",
+            "Requests made to the embedding provider, by kind (documents or query)",
+         ),
+         &["kind"],
+      )
+      .expect("valid metric");
+      let provider_request_duration = HistogramVec::new(
+         HistogramOpts::new(
+            "provider_request_duration_seconds",
+            "Latency of requests made to the embedding provider, by kind",
+         ),
+         &["kind"],
+      )
+      .expect("valid metric");
+
+      for collector in [
+         Box::new(embeds_started.clone()) as Box<dyn prometheus::core::Collector>,
+         Box::new(embeds_completed.clone()),
+         Box::new(embeds_failed.clone()),
+         Box::new(embed_duration.clone()),
+         Box::new(chunks_embedded.clone()),
+         Box::new(queries.clone()),
+         Box::new(query_duration.clone()),
+         Box::new(provider_requests.clone()),
+         Box::new(provider_request_duration.clone()),
+      ] {
+         registry
+            .register(collector)
+            .expect("metric names are unique");
+      }
+
+      Self {
+         registry,
+         embeds_started,
+         embeds_completed,
+         embeds_failed,
+         embed_duration,
+         chunks_embedded,
+         queries,
+         query_duration,
+         provider_requests,
+         provider_request_duration,
+      }
+   }
+
+   pub fn embed_started(&self) {
+      self.embeds_started.inc();
+   }
+
+   pub fn embed_completed(&self, duration: Duration) {
+      self.embeds_completed.inc();
+      self.embed_duration.observe(duration.as_secs_f64());
+   }
+
+   pub fn embed_failed(&self) {
+      self.embeds_failed.inc();
+   }
+
+   pub fn chunks_embedded(&self, count: usize) {
+      self.chunks_embedded.inc_by(count as u64);
+   }
+
+   /// Counts a query served by `tool`; its latency is recorded when the returned timer drops
+   pub fn query_started(&self, tool: &str) -> HistogramTimer {
+      self.queries.with_label_values(&[tool]).inc();
+      self.query_duration.with_label_values(&[tool]).start_timer()
+   }
+
+   /// Counts a request to the embedding provider; its latency is recorded when the returned
+   /// timer drops
+   pub fn provider_request_started(&self, kind: &str) -> HistogramTimer {
+      self.provider_requests.with_label_values(&[kind]).inc();
+      self
+         .provider_request_duration
+         .with_label_values(&[kind])
+         .start_timer()
+   }
+
+   /// Renders all metrics in the Prometheus text exposition format
+   pub fn render(&self) -> String {
+      TextEncoder::new()
+         .encode_to_string(&self.registry.gather())
+         .unwrap_or_else(|e| {
+            tracing::error!("Failed to encode metrics: {e}");
+            String::new()
+         })
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_render_exposes_counters() {
+      let metrics = Metrics::new();
+      metrics.embed_started();
+      metrics.embed_completed(Duration::from_secs(3));
+      metrics.chunks_embedded(42);
+      drop(metrics.query_started("query_embeddings"));
+
+      let rendered = metrics.render();
+
+      assert!(rendered.contains("embeds_started_total 1"));
+      assert!(rendered.contains("embeds_completed_total 1"));
+      assert!(rendered.contains("embeds_failed_total 0"));
+      assert!(rendered.contains("chunks_embedded_total 42"));
+      assert!(rendered.contains("queries_total{tool=\"query_embeddings\"} 1"));
+      assert!(rendered.contains("embed_duration_seconds_count 1"));
+   }
+}
@@ -0,0 +1,911 @@
+//! In-memory Qdrant served over gRPC, so tests run the data store through the real client.
+//! Collections, aliases and points with dense vectors are supported along with the filters
+//! this server builds; anything else, hybrid queries included, is answered as unimplemented.
+
+// handlers answer with tonic's `Status`, which the helpers pass along as is
+#![allow(clippy::result_large_err)]
+
+use anyhow::{Result, anyhow};
+use qdrant_client::{
+   Payload, Qdrant,
+   qdrant::{
+      AliasDescription, ChangeAliases, ClearPayloadPoints, CollectionClusterInfoRequest,
+      CollectionClusterInfoResponse, CollectionConfig, CollectionDescription, CollectionExists,
+      CollectionExistsRequest, CollectionExistsResponse, CollectionInfo,
+      CollectionOperationResponse, CollectionParams, Condition, CountPoints, CountResponse,
+      CountResult, CreateCollection, CreateFieldIndexCollection, CreateShardKeyRequest,
+      CreateShardKeyResponse, DeleteCollection, DeleteFieldIndexCollection, DeletePayloadPoints,
+      DeletePointVectors, DeletePoints, DeleteShardKeyRequest, DeleteShardKeyResponse, DenseVector,
+      DiscoverBatchPoints, DiscoverBatchResponse, DiscoverPoints, DiscoverResponse, FacetCounts,
+      FacetResponse, FieldCondition, Filter, GetCollectionInfoRequest, GetCollectionInfoResponse,
+      GetPoints, GetResponse, ListAliasesRequest, ListAliasesResponse,
+      ListCollectionAliasesRequest, ListCollectionsRequest, ListCollectionsResponse, NamedVectors,
+      NamedVectorsOutput, PayloadSchemaInfo, PointId, PointStruct, PointsOperationResponse,
+      PointsSelector, QueryBatchPoints, QueryBatchResponse, QueryGroupsResponse, QueryPointGroups,
+      QueryPoints, QueryResponse, RecommendBatchPoints, RecommendBatchResponse,
+      RecommendGroupsResponse, RecommendPointGroups, RecommendPoints, RecommendResponse,
+      RetrievedPoint, ScoredPoint, ScrollPoints, ScrollResponse, SearchBatchPoints,
+      SearchBatchResponse, SearchGroupsResponse, SearchMatrixOffsetsResponse,
+      SearchMatrixPairsResponse, SearchMatrixPoints, SearchPointGroups, SearchPoints,
+      SearchResponse, SetPayloadPoints, SparseVectorConfig, UpdateBatchPoints, UpdateBatchResponse,
+      UpdateCollection, UpdateCollectionClusterSetupRequest, UpdateCollectionClusterSetupResponse,
+      UpdatePointVectors, UpdateResult, UpdateStatus, UpsertPoints, Vector, VectorOutput, Vectors,
+      VectorsConfig, VectorsOutput, WithPayloadSelector, WithVectorsSelector,
+      alias_operations::Action,
+      collections_server::{Collections, CollectionsServer},
+      condition::ConditionOneOf,
+      r#match::MatchValue,
+      point_id::PointIdOptions,
+      points_selector::PointsSelectorOneOf,
+      points_server::{Points, PointsServer},
+      vector, vector_output, vectors, vectors_output, with_payload_selector, with_vectors_selector,
+   },
+};
+use serde_json::{Map, Value as JsonValue};
+use std::{
+   collections::{BTreeMap, HashMap},
+   sync::{Arc, Mutex},
+};
+use tonic::{
+   Request, Response, Status,
+   transport::{Server, server::TcpIncoming},
+};
+
+/// A Qdrant server running in the background of the test
+pub struct MockQdrant {
+   url: String,
+}
+
+impl MockQdrant {
+   /// Starts an empty server on a free local port
+   pub async fn start() -> Result<Self> {
+      let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+      let url = format!("http://{}", listener.local_addr()?);
+      let incoming = TcpIncoming::from_listener(listener, true, None).map_err(|e| anyhow!(e))?;
+
+      let service = MockService::default();
+      tokio::spawn(
+         Server::builder()
+            .add_service(CollectionsServer::new(service.clone()))
+            .add_service(PointsServer::new(service))
+            .serve_with_incoming(incoming),
+      );
+
+      Ok(Self { url })
+   }
+
+   /// A new client of the server; clients share everything stored
+   pub fn client(&self) -> Qdrant {
+      Qdrant::from_url(&self.url)
+         .skip_compatibility_check()
+         .build()
+         .expect("the mock server URL is valid")
+   }
+}
+
+#[derive(Default)]
+struct State {
+   collections: HashMap<String, Collection>,
+   /// Alias name to collection name
+   aliases: BTreeMap<String, String>,
+}
+
+struct Collection {
+   vectors_config: Option<VectorsConfig>,
+   sparse_vectors_config: Option<SparseVectorConfig>,
+   /// Points by id, in the order Qdrant scrolls them
+   points: BTreeMap<u64, Point>,
+   /// Indexed payload fields
+   payload_schema: HashMap<String, PayloadSchemaInfo>,
+}
+
+struct Point {
+   payload: Map<String, JsonValue>,
+   vectors: Option<Vectors>,
+}
+
+impl State {
+   /// Name of the collection `name` refers to, following aliases like Qdrant does
+   fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+      self.aliases.get(name).map_or(name, String::as_str)
+   }
+
+   fn collection(&self, name: &str) -> Result<&Collection, Status> {
+      self
+         .collections
+         .get(self.resolve(name))
+         .ok_or_else(|| not_found(name))
+   }
+
+   fn collection_mut(&mut self, name: &str) -> Result<&mut Collection, Status> {
+      let name = self.resolve(name).to_string();
+      self
+         .collections
+         .get_mut(&name)
+         .ok_or_else(|| not_found(&name))
+   }
+}
+
+impl Collection {
+   /// Ids of the points a selector picks, existing or not for id lists
+   fn selected(&self, selector: Option<&PointsSelector>) -> Result<Vec<u64>, Status> {
+      match selector.and_then(|selector| selector.points_selector_one_of.as_ref()) {
+         Some(PointsSelectorOneOf::Points(list)) => list.ids.iter().map(point_id).collect(),
+         Some(PointsSelectorOneOf::Filter(filter)) => self.matching(Some(filter)),
+         None => Err(Status::invalid_argument("no points selected")),
+      }
+   }
+
+   /// Ids of the points passing `filter`, all of them without one
+   fn matching(&self, filter: Option<&Filter>) -> Result<Vec<u64>, Status> {
+      let mut ids = Vec::new();
+      for (&id, point) in &self.points {
+         if filter.map_or(Ok(true), |filter| passes(filter, id, &point.payload))? {
+            ids.push(id);
+         }
+      }
+      Ok(ids)
+   }
+}
+
+fn not_found(name: &str) -> Status {
+   Status::not_found(format!("Collection `{name}` doesn't exist!"))
+}
+
+fn unsupported(what: impl std::fmt::Debug) -> Status {
+   Status::unimplemented(format!("the mock Qdrant does not support {what:?}"))
+}
+
+fn point_id(id: &PointId) -> Result<u64, Status> {
+   match id.point_id_options {
+      Some(PointIdOptions::Num(id)) => Ok(id),
+      _ => Err(unsupported(id)),
+   }
+}
+
+fn passes(filter: &Filter, id: u64, payload: &Map<String, JsonValue>) -> Result<bool, Status> {
+   let any = |conditions: &[Condition]| -> Result<bool, Status> {
+      for condition in conditions {
+         if holds(condition, id, payload)? {
+            return Ok(true);
+         }
+      }
+      Ok(false)
+   };
+   let mut must = true;
+   for condition in &filter.must {
+      must &= holds(condition, id, payload)?;
+   }
+
+   Ok(must && !any(&filter.must_not)? && (filter.should.is_empty() || any(&filter.should)?))
+}
+
+fn holds(condition: &Condition, id: u64, payload: &Map<String, JsonValue>) -> Result<bool, Status> {
+   match &condition.condition_one_of {
+      Some(ConditionOneOf::Field(field)) => field_matches(field, payload),
+      Some(ConditionOneOf::HasId(has_id)) => Ok(has_id
+         .has_id
+         .iter()
+         .any(|point| point_id(point).is_ok_and(|point| point == id))),
+      Some(ConditionOneOf::Filter(filter)) => passes(filter, id, payload),
+      _ => Err(unsupported(condition)),
+   }
+}
+
+/// Whether a payload field matches, any element matching for arrays. Text matches need all the
+/// words of the text, split and lowercased like Qdrant's word tokenizer does.
+fn field_matches(field: &FieldCondition, payload: &Map<String, JsonValue>) -> Result<bool, Status> {
+   let Some(match_value) = field.r#match.as_ref().and_then(|m| m.match_value.as_ref()) else {
+      return Err(unsupported(field));
+   };
+   let mut values = Vec::new();
+   if let Some(value) = lookup(payload, &field.key) {
+      match value {
+         JsonValue::Array(items) => values.extend(items),
+         value => values.push(value),
+      }
+   }
+   let strings = || values.iter().filter_map(|value| value.as_str());
+
+   Ok(match match_value {
+      MatchValue::Keyword(keyword) => strings().any(|value| value == keyword),
+      MatchValue::Keywords(keywords) => {
+         strings().any(|value| keywords.strings.iter().any(|k| k == value))
+      }
+      MatchValue::Integer(integer) => values.iter().any(|value| value.as_i64() == Some(*integer)),
+      MatchValue::Boolean(boolean) => values.iter().any(|value| value.as_bool() == Some(*boolean)),
+      MatchValue::Text(text) => strings().any(|value| {
+         let words = words(value);
+         self::words(text).iter().all(|word| words.contains(word))
+      }),
+      other => return Err(unsupported(other)),
+   })
+}
+
+/// Value at a dotted payload path
+fn lookup<'a>(payload: &'a Map<String, JsonValue>, key: &str) -> Option<&'a JsonValue> {
+   let (parents, last) = match key.rsplit_once('.') {
+      Some((parents, last)) => (Some(parents), last),
+      None => (None, key),
+   };
+   let object = match parents {
+      Some(parents) => parents
+         .split('.')
+         .try_fold(payload, |object, key| object.get(key)?.as_object())?,
+      None => payload,
+   };
+   object.get(last)
+}
+
+fn words(text: &str) -> Vec<String> {
+   text
+      .split(|c: char| !c.is_alphanumeric())
+      .filter(|word| !word.is_empty())
+      .map(str::to_lowercase)
+      .collect()
+}
+
+/// The dense vector `name` of stored vectors, or the unnamed one
+fn dense_vector<'a>(vectors: &'a Vectors, name: Option<&str>) -> Option<&'a [f32]> {
+   let vector = match (vectors.vectors_options.as_ref()?, name) {
+      (vectors::VectorsOptions::Vector(vector), None) => vector,
+      (vectors::VectorsOptions::Vectors(named), Some(name)) => named.vectors.get(name)?,
+      _ => return None,
+   };
+   match &vector.vector {
+      Some(vector::Vector::Dense(dense)) => Some(&dense.data),
+      None if vector.indices.is_none() => Some(&vector.data),
+      _ => None,
+   }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+   let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+   let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+   let norms = norm(a) * norm(b);
+   if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+fn vector_output(vector: &Vector) -> VectorOutput {
+   match &vector.vector {
+      Some(vector::Vector::Dense(dense)) => VectorOutput {
+         data: dense.data.clone(),
+         vector: Some(vector_output::Vector::Dense(dense.clone())),
+         ..Default::default()
+      },
+      Some(vector::Vector::Sparse(sparse)) => VectorOutput {
+         vector: Some(vector_output::Vector::Sparse(sparse.clone())),
+         ..Default::default()
+      },
+      _ => VectorOutput {
+         data: vector.data.clone(),
+         vector: Some(vector_output::Vector::Dense(DenseVector {
+            data: vector.data.clone(),
+         })),
+         ..Default::default()
+      },
+   }
+}
+
+fn vectors_output(vectors: &Vectors) -> VectorsOutput {
+   let vectors_options = vectors
+      .vectors_options
+      .as_ref()
+      .map(|options| match options {
+         vectors::VectorsOptions::Vector(vector) => {
+            vectors_output::VectorsOptions::Vector(vector_output(vector))
+         }
+         vectors::VectorsOptions::Vectors(NamedVectors { vectors }) => {
+            vectors_output::VectorsOptions::Vectors(NamedVectorsOutput {
+               vectors: vectors
+                  .iter()
+                  .map(|(name, vector)| (name.clone(), vector_output(vector)))
+                  .collect(),
+            })
+         }
+      });
+   VectorsOutput { vectors_options }
+}
+
+fn with_payload(selector: Option<&WithPayloadSelector>) -> bool {
+   matches!(
+      selector.and_then(|selector| selector.selector_options.as_ref()),
+      Some(with_payload_selector::SelectorOptions::Enable(true))
+   )
+}
+
+fn with_vectors(selector: Option<&WithVectorsSelector>) -> bool {
+   matches!(
+      selector.and_then(|selector| selector.selector_options.as_ref()),
+      Some(with_vectors_selector::SelectorOptions::Enable(true))
+   )
+}
+
+fn retrieved(id: u64, point: &Point, payload: bool, vectors: bool) -> RetrievedPoint {
+   RetrievedPoint {
+      id: Some(id.into()),
+      payload: if payload {
+         Payload::from(point.payload.clone()).into()
+      } else {
+         HashMap::new()
+      },
+      vectors: point
+         .vectors
+         .as_ref()
+         .filter(|_| vectors)
+         .map(vectors_output),
+      ..Default::default()
+   }
+}
+
+fn collection_done() -> Response<CollectionOperationResponse> {
+   Response::new(CollectionOperationResponse {
+      result: true,
+      time: 0.0,
+   })
+}
+
+fn points_done() -> Response<PointsOperationResponse> {
+   Response::new(PointsOperationResponse {
+      result: Some(UpdateResult {
+         operation_id: None,
+         status: UpdateStatus::Completed.into(),
+      }),
+      ..Default::default()
+   })
+}
+
+/// Both gRPC services, sharing the stored collections
+#[derive(Clone, Default)]
+struct MockService {
+   state: Arc<Mutex<State>>,
+}
+
+impl MockService {
+   fn state(&self) -> std::sync::MutexGuard<'_, State> {
+      self.state.lock().unwrap()
+   }
+
+   fn set_payload(&self, request: SetPayloadPoints, overwrite: bool) -> Result<(), Status> {
+      let mut state = self.state();
+      let collection = state.collection_mut(&request.collection_name)?;
+      let payload = Map::from(Payload::from(request.payload));
+      for id in collection.selected(request.points_selector.as_ref())? {
+         let Some(point) = collection.points.get_mut(&id) else {
+            continue;
+         };
+         let target = match &request.key {
+            Some(key) => {
+               let nested = point
+                  .payload
+                  .entry(key.clone())
+                  .or_insert_with(|| JsonValue::Object(Map::new()));
+               match nested {
+                  JsonValue::Object(nested) => nested,
+                  _ => return Err(unsupported(key)),
+               }
+            }
+            None => &mut point.payload,
+         };
+         if overwrite {
+            target.clear();
+         }
+         target.extend(payload.clone());
+      }
+      Ok(())
+   }
+}
+
+#[tonic::async_trait]
+impl Collections for MockService {
+   async fn get(
+      &self,
+      request: Request<GetCollectionInfoRequest>,
+   ) -> Result<Response<GetCollectionInfoResponse>, Status> {
+      let state = self.state();
+      let collection = state.collection(&request.get_ref().collection_name)?;
+      let info = CollectionInfo {
+         config: Some(CollectionConfig {
+            params: Some(CollectionParams {
+               vectors_config: collection.vectors_config.clone(),
+               sparse_vectors_config: collection.sparse_vectors_config.clone(),
+               ..Default::default()
+            }),
+            ..Default::default()
+         }),
+         points_count: Some(collection.points.len() as u64),
+         payload_schema: collection.payload_schema.clone(),
+         ..Default::default()
+      };
+      Ok(Response::new(GetCollectionInfoResponse {
+         result: Some(info),
+         time: 0.0,
+      }))
+   }
+
+   async fn list(
+      &self,
+      _request: Request<ListCollectionsRequest>,
+   ) -> Result<Response<ListCollectionsResponse>, Status> {
+      let mut names: Vec<String> = self.state().collections.keys().cloned().collect();
+      names.sort();
+      Ok(Response::new(ListCollectionsResponse {
+         collections: names
+            .into_iter()
+            .map(|name| CollectionDescription { name })
+            .collect(),
+         time: 0.0,
+      }))
+   }
+
+   async fn create(
+      &self,
+      request: Request<CreateCollection>,
+   ) -> Result<Response<CollectionOperationResponse>, Status> {
+      let request = request.into_inner();
+      let mut state = self.state();
+      let name = request.collection_name;
+      if state.collections.contains_key(&name) || state.aliases.contains_key(&name) {
+         return Err(Status::already_exists(format!(
+            "Collection `{name}` already exists!"
+         )));
+      }
+      state.collections.insert(
+         name,
+         Collection {
+            vectors_config: request.vectors_config,
+            sparse_vectors_config: request.sparse_vectors_config,
+            points: BTreeMap::new(),
+            payload_schema: HashMap::new(),
+         },
+      );
+      Ok(collection_done())
+   }
+
+   async fn update(
+      &self,
+      request: Request<UpdateCollection>,
+   ) -> Result<Response<CollectionOperationResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn delete(
+      &self,
+      request: Request<DeleteCollection>,
+   ) -> Result<Response<CollectionOperationResponse>, Status> {
+      let mut state = self.state();
+      let name = request.into_inner().collection_name;
+      let deleted = state.collections.remove(&name).is_some();
+      // aliases go along with their collection
+      state.aliases.retain(|_, collection| *collection != name);
+      Ok(Response::new(CollectionOperationResponse {
+         result: deleted,
+         time: 0.0,
+      }))
+   }
+
+   async fn update_aliases(
+      &self,
+      request: Request<ChangeAliases>,
+   ) -> Result<Response<CollectionOperationResponse>, Status> {
+      let mut state = self.state();
+      for action in request.into_inner().actions {
+         match action.action {
+            Some(Action::CreateAlias(create)) => {
+               if !state.collections.contains_key(&create.collection_name) {
+                  return Err(not_found(&create.collection_name));
+               }
+               if state.collections.contains_key(&create.alias_name) {
+                  return Err(Status::already_exists(format!(
+                     "Collection `{}` already exists!",
+                     create.alias_name
+                  )));
+               }
+               state
+                  .aliases
+                  .insert(create.alias_name, create.collection_name);
+            }
+            Some(Action::DeleteAlias(delete)) => {
+               if state.aliases.remove(&delete.alias_name).is_none() {
+                  return Err(Status::not_found(format!(
+                     "Alias {} does not exist!",
+                     delete.alias_name
+                  )));
+               }
+            }
+            Some(Action::RenameAlias(rename)) => {
+               let collection = state
+                  .aliases
+                  .remove(&rename.old_alias_name)
+                  .ok_or_else(|| Status::not_found(rename.old_alias_name.clone()))?;
+               state.aliases.insert(rename.new_alias_name, collection);
+            }
+            None => return Err(Status::invalid_argument("empty alias action")),
+         }
+      }
+      Ok(collection_done())
+   }
+
+   async fn list_collection_aliases(
+      &self,
+      request: Request<ListCollectionAliasesRequest>,
+   ) -> Result<Response<ListAliasesResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn list_aliases(
+      &self,
+      _request: Request<ListAliasesRequest>,
+   ) -> Result<Response<ListAliasesResponse>, Status> {
+      let aliases = self
+         .state()
+         .aliases
+         .iter()
+         .map(|(alias_name, collection_name)| AliasDescription {
+            alias_name: alias_name.clone(),
+            collection_name: collection_name.clone(),
+         })
+         .collect();
+      Ok(Response::new(ListAliasesResponse { aliases, time: 0.0 }))
+   }
+
+   async fn collection_cluster_info(
+      &self,
+      request: Request<CollectionClusterInfoRequest>,
+   ) -> Result<Response<CollectionClusterInfoResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn collection_exists(
+      &self,
+      request: Request<CollectionExistsRequest>,
+   ) -> Result<Response<CollectionExistsResponse>, Status> {
+      let exists = self
+         .state()
+         .collection(&request.get_ref().collection_name)
+         .is_ok();
+      Ok(Response::new(CollectionExistsResponse {
+         result: Some(CollectionExists { exists }),
+         time: 0.0,
+      }))
+   }
+
+   async fn update_collection_cluster_setup(
+      &self,
+      request: Request<UpdateCollectionClusterSetupRequest>,
+   ) -> Result<Response<UpdateCollectionClusterSetupResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn create_shard_key(
+      &self,
+      request: Request<CreateShardKeyRequest>,
+   ) -> Result<Response<CreateShardKeyResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn delete_shard_key(
+      &self,
+      request: Request<DeleteShardKeyRequest>,
+   ) -> Result<Response<DeleteShardKeyResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+}
+
+#[tonic::async_trait]
+impl Points for MockService {
+   async fn upsert(
+      &self,
+      request: Request<UpsertPoints>,
+   ) -> Result<Response<PointsOperationResponse>, Status> {
+      let request = request.into_inner();
+      let mut state = self.state();
+      let collection = state.collection_mut(&request.collection_name)?;
+      for PointStruct {
+         id,
+         payload,
+         vectors,
+      } in request.points
+      {
+         let id = point_id(&id.ok_or_else(|| Status::invalid_argument("point without id"))?)?;
+         let payload = Map::from(Payload::from(payload));
+         collection.points.insert(id, Point { payload, vectors });
+      }
+      Ok(points_done())
+   }
+
+   async fn delete(
+      &self,
+      request: Request<DeletePoints>,
+   ) -> Result<Response<PointsOperationResponse>, Status> {
+      let request = request.into_inner();
+      let mut state = self.state();
+      let collection = state.collection_mut(&request.collection_name)?;
+      for id in collection.selected(request.points.as_ref())? {
+         collection.points.remove(&id);
+      }
+      Ok(points_done())
+   }
+
+   async fn get(&self, request: Request<GetPoints>) -> Result<Response<GetResponse>, Status> {
+      let request = request.into_inner();
+      let state = self.state();
+      let collection = state.collection(&request.collection_name)?;
+      let (payload, vectors) = (
+         with_payload(request.with_payload.as_ref()),
+         with_vectors(request.with_vectors.as_ref()),
+      );
+      let mut result = Vec::new();
+      for id in &request.ids {
+         let id = point_id(id)?;
+         if let Some(point) = collection.points.get(&id) {
+            result.push(retrieved(id, point, payload, vectors));
+         }
+      }
+      Ok(Response::new(GetResponse {
+         result,
+         ..Default::default()
+      }))
+   }
+
+   async fn update_vectors(
+      &self,
+      request: Request<UpdatePointVectors>,
+   ) -> Result<Response<PointsOperationResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn delete_vectors(
+      &self,
+      request: Request<DeletePointVectors>,
+   ) -> Result<Response<PointsOperationResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn set_payload(
+      &self,
+      request: Request<SetPayloadPoints>,
+   ) -> Result<Response<PointsOperationResponse>, Status> {
+      MockService::set_payload(self, request.into_inner(), false)?;
+      Ok(points_done())
+   }
+
+   async fn overwrite_payload(
+      &self,
+      request: Request<SetPayloadPoints>,
+   ) -> Result<Response<PointsOperationResponse>, Status> {
+      MockService::set_payload(self, request.into_inner(), true)?;
+      Ok(points_done())
+   }
+
+   async fn delete_payload(
+      &self,
+      request: Request<DeletePayloadPoints>,
+   ) -> Result<Response<PointsOperationResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn clear_payload(
+      &self,
+      request: Request<ClearPayloadPoints>,
+   ) -> Result<Response<PointsOperationResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn create_field_index(
+      &self,
+      request: Request<CreateFieldIndexCollection>,
+   ) -> Result<Response<PointsOperationResponse>, Status> {
+      // filters are evaluated without indexes, which are only recorded
+      let request = request.into_inner();
+      let mut state = self.state();
+      let collection = state.collection_mut(&request.collection_name)?;
+      // payload schema types count from an unknown type that field types lack
+      let data_type = request.field_type.unwrap_or_default() + 1;
+      collection.payload_schema.insert(
+         request.field_name,
+         PayloadSchemaInfo {
+            data_type,
+            params: request.field_index_params,
+            points: None,
+         },
+      );
+      Ok(points_done())
+   }
+
+   async fn delete_field_index(
+      &self,
+      request: Request<DeleteFieldIndexCollection>,
+   ) -> Result<Response<PointsOperationResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn search(
+      &self,
+      request: Request<SearchPoints>,
+   ) -> Result<Response<SearchResponse>, Status> {
+      let request = request.into_inner();
+      let state = self.state();
+      let collection = state.collection(&request.collection_name)?;
+      let payload = with_payload(request.with_payload.as_ref());
+
+      let mut result = Vec::new();
+      for id in collection.matching(request.filter.as_ref())? {
+         let point = &collection.points[&id];
+         let Some(vector) = point
+            .vectors
+            .as_ref()
+            .and_then(|vectors| dense_vector(vectors, request.vector_name.as_deref()))
+         else {
+            continue;
+         };
+         let score = cosine_similarity(&request.vector, vector);
+         if request
+            .score_threshold
+            .is_some_and(|threshold| score < threshold)
+         {
+            continue;
+         }
+         let point = retrieved(id, point, payload, false);
+         result.push(ScoredPoint {
+            id: point.id,
+            payload: point.payload,
+            score,
+            ..Default::default()
+         });
+      }
+      result.sort_by(|a, b| b.score.total_cmp(&a.score));
+      let offset = request.offset.unwrap_or(0) as usize;
+      let result = result
+         .into_iter()
+         .skip(offset)
+         .take(request.limit as usize)
+         .collect();
+
+      Ok(Response::new(SearchResponse {
+         result,
+         ..Default::default()
+      }))
+   }
+
+   async fn search_batch(
+      &self,
+      request: Request<SearchBatchPoints>,
+   ) -> Result<Response<SearchBatchResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn search_groups(
+      &self,
+      request: Request<SearchPointGroups>,
+   ) -> Result<Response<SearchGroupsResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn scroll(
+      &self,
+      request: Request<ScrollPoints>,
+   ) -> Result<Response<ScrollResponse>, Status> {
+      let request = request.into_inner();
+      let state = self.state();
+      let collection = state.collection(&request.collection_name)?;
+      let (payload, vectors) = (
+         with_payload(request.with_payload.as_ref()),
+         with_vectors(request.with_vectors.as_ref()),
+      );
+      let from = request
+         .offset
+         .as_ref()
+         .map(point_id)
+         .transpose()?
+         .unwrap_or(0);
+      let limit = request.limit.unwrap_or(10) as usize;
+
+      let mut ids = collection
+         .matching(request.filter.as_ref())?
+         .into_iter()
+         .filter(|&id| id >= from);
+      let result = ids
+         .by_ref()
+         .take(limit)
+         .map(|id| retrieved(id, &collection.points[&id], payload, vectors))
+         .collect();
+
+      Ok(Response::new(ScrollResponse {
+         next_page_offset: ids.next().map(PointId::from),
+         result,
+         ..Default::default()
+      }))
+   }
+
+   async fn recommend(
+      &self,
+      request: Request<RecommendPoints>,
+   ) -> Result<Response<RecommendResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn recommend_batch(
+      &self,
+      request: Request<RecommendBatchPoints>,
+   ) -> Result<Response<RecommendBatchResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn recommend_groups(
+      &self,
+      request: Request<RecommendPointGroups>,
+   ) -> Result<Response<RecommendGroupsResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn discover(
+      &self,
+      request: Request<DiscoverPoints>,
+   ) -> Result<Response<DiscoverResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn discover_batch(
+      &self,
+      request: Request<DiscoverBatchPoints>,
+   ) -> Result<Response<DiscoverBatchResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn count(&self, request: Request<CountPoints>) -> Result<Response<CountResponse>, Status> {
+      let request = request.into_inner();
+      let state = self.state();
+      let count = state
+         .collection(&request.collection_name)?
+         .matching(request.filter.as_ref())?
+         .len() as u64;
+      Ok(Response::new(CountResponse {
+         result: Some(CountResult { count }),
+         ..Default::default()
+      }))
+   }
+
+   async fn update_batch(
+      &self,
+      request: Request<UpdateBatchPoints>,
+   ) -> Result<Response<UpdateBatchResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn query(&self, request: Request<QueryPoints>) -> Result<Response<QueryResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn query_batch(
+      &self,
+      request: Request<QueryBatchPoints>,
+   ) -> Result<Response<QueryBatchResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn query_groups(
+      &self,
+      request: Request<QueryPointGroups>,
+   ) -> Result<Response<QueryGroupsResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn facet(&self, request: Request<FacetCounts>) -> Result<Response<FacetResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn search_matrix_pairs(
+      &self,
+      request: Request<SearchMatrixPoints>,
+   ) -> Result<Response<SearchMatrixPairsResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+
+   async fn search_matrix_offsets(
+      &self,
+      request: Request<SearchMatrixPoints>,
+   ) -> Result<Response<SearchMatrixOffsetsResponse>, Status> {
+      Err(unsupported(request.into_inner()))
+   }
+}
@@ -1,4 +1,4 @@
-use crate::json_types::JsonDocs;
+use crate::json_types::{Item, JsonDocs};
 use anyhow::{Context, Result};
 use std::{fmt, fs, path::Path};
 
@@ -9,6 +9,36 @@ pub enum ItemType {
    Function,
    Constant,
    Impl,
+   /// Crate-level overview taken from the root module's docs
+   Module,
+}
+
+/// Selects which rustdoc JSON items become doc items
+#[derive(Debug, Clone)]
+pub struct DocFilter {
+   /// Item types that are kept; the crate overview is kept when `Module` is listed
+   pub item_types: Vec<ItemType>,
+   /// Whether items that are not part of the public API are kept
+   pub include_private: bool,
+   /// Whether the docs of modules other than the crate root become items too
+   pub include_modules: bool,
+}
+
+impl Default for DocFilter {
+   fn default() -> Self {
+      Self {
+         item_types: vec![
+            ItemType::Struct,
+            ItemType::Enum,
+            ItemType::Function,
+            ItemType::Constant,
+            ItemType::Impl,
+            ItemType::Module,
+         ],
+         include_private: true,
+         include_modules: false,
+      }
+   }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -33,21 +63,41 @@ impl fmt::Display for DocItem {
          writeln!(f, "{doc_string}")?;
          writeln!(f)?;
       }
+      // the crate overview is documentation only
+      if self.source_code.is_empty() {
+         return Ok(());
+      }
       writeln!(f, "```rust")?;
       write!(f, "{}", self.source_code)?;
       write!(f, "\n```")
    }
 }
 
-/// Transforms rustdoc JSON output into structured items with source code,
-/// filtering out internal items and preserving only public API elements
-pub fn create_doc_items_with_source(docs: &JsonDocs, temp_dir: &Path) -> Result<Vec<DocItem>> {
+/// Transforms rustdoc JSON output into structured items with source code, keeping the local
+/// crate's items selected by `filter`. The crate root's `//!` docs become an overview item; a
+/// README pulled in with `#![doc = include_str!("../README.md")]` is already part of those
+/// docs.
+pub fn create_doc_items_with_source(
+   docs: &JsonDocs,
+   temp_dir: &Path,
+   filter: &DocFilter,
+) -> Result<Vec<DocItem>> {
    let mut doc_items = Vec::new();
 
    for item in docs.index.values() {
+      if item.crate_id != 0 || (!filter.include_private && !item.is_public()) {
+         continue;
+      }
+
+      if item.is_crate_root() || (filter.include_modules && item.item_type() == Some("module")) {
+         if let Some(doc_item) = module_docs(item) {
+            doc_items.push(doc_item);
+         }
+         continue;
+      }
+
       // Filter criteria
-      if item.crate_id != 0
-         || item.span.is_none()
+      if item.span.is_none()
          || matches!(
             item.item_type(),
             Some("struct_field") | Some("variant") | Some("module")
@@ -141,7 +191,32 @@ pub fn create_doc_items_with_source(docs: &JsonDocs, temp_dir: &Path) -> Result<
    }
 
    // Second pass: filter out functions that are within impl blocks
-   filter_impl_functions(doc_items)
+   // impls are only dropped afterwards, so their methods never pass as free functions
+   let mut doc_items = filter_impl_functions(doc_items)?;
+   doc_items.retain(|item| filter.item_types.contains(&item.r#type));
+   Ok(doc_items)
+}
+
+/// Builds a docs-only item from a module's docs, if it has any. For the crate root module
+/// this is the crate overview.
+fn module_docs(item: &Item) -> Option<DocItem> {
+   let docs = item
+      .docs
+      .as_deref()
+      .filter(|docs| !docs.trim().is_empty())?;
+   let span = item.span.as_ref()?;
+
+   Some(DocItem {
+      name: item.name.clone(),
+      doc_string: Some(docs.to_string()),
+      r#type: ItemType::Module,
+      source_code: String::new(),
+      filename: span.filename.clone(),
+      span: FileRange {
+         start: span.begin,
+         end: span.end,
+      },
+   })
 }
 
 /// Filters out functions that are within impl blocks by comparing spans
@@ -215,6 +290,122 @@ fn find_start_line_with_attributes(lines: &[&str], item_start_line: usize) -> us
 #[cfg(test)]
 mod tests {
    use super::*;
+   use tempfile::TempDir;
+
+   #[test]
+   fn test_crate_root_docs_become_overview() -> Result<()> {
+      let temp_dir = TempDir::new()?;
+      fs::create_dir_all(temp_dir.path().join("src"))?;
+      fs::write(
+         temp_dir.path().join("src/lib.rs"),
+         "//! Parses widgets.\n\n/// Parses one widget.\npub fn parse() {}\n",
+      )?;
+
+      let docs: JsonDocs = serde_json::from_str(
+         r#"{"index": {
+            "0": {"crate_id": 0, "name": "widgets", "docs": "Parses widgets.",
+                  "span": {"filename": "src/lib.rs", "begin": [1, 0], "end": [4, 19]},
+                  "inner": {"module": {"is_crate": true, "items": [1]}}},
+            "1": {"crate_id": 0, "name": "parse", "docs": "Parses one widget.",
+                  "span": {"filename": "src/lib.rs", "begin": [4, 0], "end": [4, 19]},
+                  "inner": {"function": {}}}
+         }}"#,
+      )?;
+
+      let items = create_doc_items_with_source(&docs, temp_dir.path(), &DocFilter::default())?;
+
+      let overview = items
+         .iter()
+         .find(|item| item.r#type == ItemType::Module)
+         .expect("crate overview item");
+      assert_eq!(overview.name.as_deref(), Some("widgets"));
+      assert_eq!(overview.to_string(), "Parses widgets.\n\n");
+
+      assert!(
+         items
+            .iter()
+            .any(|item| item.r#type == ItemType::Function && item.source_code.contains("fn parse"))
+      );
+      Ok(())
+   }
+
+   #[test]
+   fn test_filter_restricted_to_functions() -> Result<()> {
+      let temp_dir = TempDir::new()?;
+      fs::create_dir_all(temp_dir.path().join("src"))?;
+      fs::write(
+         temp_dir.path().join("src/lib.rs"),
+         "//! Parses widgets.\n\npub struct Widget;\n\nimpl Widget {\n    pub fn size(&self) -> \
+          u32 { 1 }\n}\n\npub fn parse() -> Widget { Widget }\n",
+      )?;
+
+      let docs: JsonDocs = serde_json::from_str(
+         r#"{"index": {
+            "0": {"crate_id": 0, "name": "widgets", "docs": "Parses widgets.",
+                  "span": {"filename": "src/lib.rs", "begin": [1, 0], "end": [9, 35]},
+                  "inner": {"module": {"is_crate": true, "items": [1, 2, 4]}}},
+            "1": {"crate_id": 0, "name": "Widget", "docs": null,
+                  "span": {"filename": "src/lib.rs", "begin": [3, 0], "end": [3, 18]},
+                  "inner": {"struct": {}}},
+            "2": {"crate_id": 0, "name": null, "docs": null,
+                  "span": {"filename": "src/lib.rs", "begin": [5, 0], "end": [7, 1]},
+                  "inner": {"impl": {}}},
+            "3": {"crate_id": 0, "name": "size", "docs": null,
+                  "span": {"filename": "src/lib.rs", "begin": [6, 4], "end": [6, 40]},
+                  "inner": {"function": {}}},
+            "4": {"crate_id": 0, "name": "parse", "docs": null,
+                  "span": {"filename": "src/lib.rs", "begin": [9, 0], "end": [9, 35]},
+                  "inner": {"function": {}}}
+         }}"#,
+      )?;
+      let filter = DocFilter {
+         item_types: vec![ItemType::Function],
+         ..Default::default()
+      };
+
+      let items = create_doc_items_with_source(&docs, temp_dir.path(), &filter)?;
+
+      // the method stays part of its impl, which is filtered out
+      let names: Vec<_> = items.iter().map(|item| item.name.as_deref()).collect();
+      assert_eq!(names, vec![Some("parse")]);
+      assert!(items.iter().all(|item| item.r#type == ItemType::Function));
+      Ok(())
+   }
+
+   #[test]
+   fn test_private_items_are_left_out() -> Result<()> {
+      let temp_dir = TempDir::new()?;
+      fs::create_dir_all(temp_dir.path().join("src"))?;
+      fs::write(
+         temp_dir.path().join("src/lib.rs"),
+         "pub fn parse() {}\npub(crate) fn helper() {}\npub(in crate::io) fn read() {}\n",
+      )?;
+
+      let docs: JsonDocs = serde_json::from_str(
+         r#"{"index": {
+            "0": {"crate_id": 0, "name": "parse", "docs": null, "visibility": "public",
+                  "span": {"filename": "src/lib.rs", "begin": [1, 0], "end": [1, 17]},
+                  "inner": {"function": {}}},
+            "1": {"crate_id": 0, "name": "helper", "docs": null, "visibility": "crate",
+                  "span": {"filename": "src/lib.rs", "begin": [2, 0], "end": [2, 25]},
+                  "inner": {"function": {}}},
+            "2": {"crate_id": 0, "name": "read", "docs": null,
+                  "visibility": {"restricted": {"parent": 3, "path": "crate::io"}},
+                  "span": {"filename": "src/lib.rs", "begin": [3, 0], "end": [3, 30]},
+                  "inner": {"function": {}}}
+         }}"#,
+      )?;
+      let filter = DocFilter {
+         include_private: false,
+         ..Default::default()
+      };
+
+      let items = create_doc_items_with_source(&docs, temp_dir.path(), &filter)?;
+
+      let names: Vec<_> = items.iter().map(|item| item.name.as_deref()).collect();
+      assert_eq!(names, vec![Some("parse")]);
+      Ok(())
+   }
 
    #[test]
    fn test_is_span_within() {
@@ -0,0 +1,159 @@
+use rmcp::{
+   Error as McpError,
+   model::{GetPromptResult, JsonObject, Prompt, PromptArgument, PromptMessage, PromptMessageRole},
+};
+
+/// A prompt template and the arguments it is filled with
+struct PromptTemplate {
+   name: &'static str,
+   description: &'static str,
+   /// Name and description of each argument, `repo` being shared by every template
+   arguments: &'static [(&'static str, &'static str)],
+   /// Instructions with `{argument}` placeholders
+   template: &'static str,
+}
+
+const REPO_ARGUMENT: (&str, &str) = (
+   "repo",
+   "Embedded repository to search: a GitHub URL, owner/repo or an alias given at embed time",
+);
+
+const TEMPLATES: &[PromptTemplate] = &[
+   PromptTemplate {
+      name: "explain-symbol",
+      description: "Explain how a function, type or trait works using the embedded code",
+      arguments: &[
+         REPO_ARGUMENT,
+         ("symbol", "Name of the function, type or trait to explain"),
+      ],
+      template: "Explain how `{symbol}` works in {repo}. Call `query_embeddings` with repo_url \
+                 \"{repo}\" and the query \"{symbol} definition and implementation\" to find its \
+                 definition, then query for the items it calls or is built from when the first \
+                 results leave them unclear. Describe its purpose, its inputs and outputs and its \
+                 notable edge cases, citing the permalinks of the chunks you relied on.",
+   },
+   PromptTemplate {
+      name: "find-usage-examples",
+      description: "Find examples of a symbol being used in the embedded code",
+      arguments: &[
+         REPO_ARGUMENT,
+         (
+            "symbol",
+            "Name of the function, type or trait to find usages of",
+         ),
+      ],
+      template: "Find examples of how `{symbol}` is used in {repo}. Call `query_embeddings` with \
+                 repo_url \"{repo}\" and queries such as \"calling {symbol}\" and \"{symbol} \
+                 example\", looking beyond the definition itself: tests, examples and docs are \
+                 good sources. Show the most instructive examples with their permalinks and what \
+                 each one demonstrates.",
+   },
+   PromptTemplate {
+      name: "summarize-module",
+      description: "Summarize what a module or directory of the embedded code is responsible for",
+      arguments: &[
+         REPO_ARGUMENT,
+         (
+            "module",
+            "Module path or directory to summarize, e.g. src/net",
+         ),
+      ],
+      template: "Summarize the `{module}` module of {repo}. Call `query_embeddings` with repo_url \
+                 \"{repo}\" and the query \"{module} module overview\", then query for its main \
+                 types and functions. Explain what the module is responsible for, its key items \
+                 and how they fit together, citing the permalinks of the chunks you relied on.",
+   },
+];
+
+/// Prompts the server offers, each expanding into instructions for searching the embeddings
+pub fn list_prompts() -> Vec<Prompt> {
+   TEMPLATES
+      .iter()
+      .map(|template| Prompt {
+         name: template.name.to_string(),
+         description: Some(template.description.to_string()),
+         arguments: Some(
+            template
+               .arguments
+               .iter()
+               .map(|(name, description)| PromptArgument {
+                  name: name.to_string(),
+                  description: Some(description.to_string()),
+                  required: Some(true),
+               })
+               .collect(),
+         ),
+      })
+      .collect()
+}
+
+/// Fills the prompt `name` with `arguments`, failing for unknown prompts and missing
+/// arguments
+pub fn get_prompt(name: &str, arguments: Option<&JsonObject>) -> Result<GetPromptResult, McpError> {
+   let template = TEMPLATES
+      .iter()
+      .find(|template| template.name == name)
+      .ok_or_else(|| McpError::invalid_params(format!("Unknown prompt: {name}"), None))?;
+
+   let mut text = template.template.to_string();
+   for (argument, _) in template.arguments {
+      let value = arguments
+         .and_then(|arguments| arguments.get(*argument))
+         .and_then(|value| value.as_str())
+         .filter(|value| !value.trim().is_empty())
+         .ok_or_else(|| {
+            McpError::invalid_params(
+               format!("Prompt {name} requires the argument {argument}"),
+               None,
+            )
+         })?;
+      text = text.replace(&format!("{{{argument}}}"), value.trim());
+   }
+
+   Ok(GetPromptResult {
+      description: Some(template.description.to_string()),
+      messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+   })
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_prompts_are_listed_with_their_arguments() {
+      let prompts = list_prompts();
+
+      let names: Vec<&str> = prompts.iter().map(|prompt| prompt.name.as_str()).collect();
+      assert_eq!(
+         names,
+         vec!["explain-symbol", "find-usage-examples", "summarize-module"]
+      );
+      for prompt in &prompts {
+         let arguments = prompt.arguments.as_ref().expect("prompt arguments");
+         assert_eq!(arguments[0].name, "repo");
+         assert!(
+            arguments
+               .iter()
+               .all(|argument| argument.required == Some(true))
+         );
+      }
+   }
+
+   #[test]
+   fn test_prompt_expands_its_arguments() {
+      let arguments = serde_json::json!({"repo": "tokio-rs/tokio", "symbol": "spawn"});
+
+      let result = get_prompt("explain-symbol", arguments.as_object()).unwrap();
+
+      let message = serde_json::to_value(&result.messages[0]).unwrap();
+      let text = message["content"]["text"].as_str().unwrap();
+      assert!(text.starts_with("Explain how `spawn` works in tokio-rs/tokio."));
+      assert!(text.contains("repo_url \"tokio-rs/tokio\""));
+      assert!(!text.contains('{'));
+
+      let missing = serde_json::json!({"repo": "tokio-rs/tokio"});
+      assert!(get_prompt("explain-symbol", missing.as_object()).is_err());
+      assert!(get_prompt("write-poem", arguments.as_object()).is_err());
+   }
+}
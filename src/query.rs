@@ -1,65 +1,730 @@
-use crate::data_store::DataStore;
+use crate::{
+   chunks::ChunkKind,
+   config::EmbeddingConfig,
+   data_store::{
+      ChunkFilter, ChunkPayload, DataStore, EmbeddingMetadata, Granularity, SearchResult,
+   },
+   embedder::{ApiKey, EMBEDDING_MODEL, openai_http_client, openai_timeout, resolve_api_key},
+   metrics::Metrics,
+};
 use anyhow::{Context, Result};
 use async_openai::{Client, config::OpenAIConfig, types::CreateEmbeddingRequestArgs};
-use tracing::info;
+use futures::{StreamExt, stream};
+use std::{
+   collections::{HashMap, HashSet, VecDeque},
+   fmt,
+   future::Future,
+   sync::{Arc, Mutex},
+   time::{Duration, Instant},
+};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::{info, warn};
+
+/// How many candidates are fetched per requested result when deduplicating or weighing
+/// recency, so that enough distinct results remain to fill the limit and recent chunks ranked
+/// just below it can move up
+const OVERFETCH: u64 = 3;
+
+const DEFAULT_QUERY_CONCURRENCY: usize = 8;
+const DEFAULT_QUERY_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_QUERY_CACHE_SIZE: usize = 256;
+const DEFAULT_MAX_CONCURRENT_QUERIES: usize = 16;
+
+/// Maximum number of repositories searched at once by a cross-repository query, read from
+/// `QUERY_CONCURRENCY`
+pub fn query_concurrency() -> usize {
+   dotenvy::var("QUERY_CONCURRENCY")
+      .ok()
+      .and_then(|value| value.parse().ok())
+      .filter(|&concurrency| concurrency > 0)
+      .unwrap_or(DEFAULT_QUERY_CONCURRENCY)
+}
+
+/// Overall deadline for a cross-repository query, read from `QUERY_TIMEOUT_SECS`.
+/// Repositories that have not answered by then are left out of the results.
+pub fn query_timeout() -> Duration {
+   let secs = dotenvy::var("QUERY_TIMEOUT_SECS")
+      .ok()
+      .and_then(|value| value.parse().ok())
+      .unwrap_or(DEFAULT_QUERY_TIMEOUT_SECS);
+
+   Duration::from_secs(secs)
+}
+
+/// Number of query embeddings kept in memory, read from `QUERY_CACHE_SIZE`. Zero disables
+/// the cache.
+pub fn query_cache_size() -> usize {
+   dotenvy::var("QUERY_CACHE_SIZE")
+      .ok()
+      .and_then(|value| value.parse().ok())
+      .unwrap_or(DEFAULT_QUERY_CACHE_SIZE)
+}
+
+/// Maximum number of query tool calls served at once across all sessions, read from
+/// `MAX_CONCURRENT_QUERIES`
+pub fn max_concurrent_queries() -> usize {
+   dotenvy::var("MAX_CONCURRENT_QUERIES")
+      .ok()
+      .and_then(|value| value.parse().ok())
+      .filter(|&limit| limit > 0)
+      .unwrap_or(DEFAULT_MAX_CONCURRENT_QUERIES)
+}
+
+/// Bounds the queries in flight, each embedding its text and searching Qdrant, so that a
+/// burst of tool calls waits for a turn instead of exhausting connections
+pub struct QueryLimiter {
+   permits: Semaphore,
+}
+
+impl Default for QueryLimiter {
+   fn default() -> Self {
+      Self::new(max_concurrent_queries())
+   }
+}
+
+impl QueryLimiter {
+   pub fn new(limit: usize) -> Self {
+      Self {
+         permits: Semaphore::new(limit.max(1)),
+      }
+   }
+
+   /// Waits until fewer than the limit of queries are running; the query holds its turn until
+   /// the returned permit drops
+   pub async fn acquire(&self) -> SemaphorePermit<'_> {
+      self
+         .permits
+         .acquire()
+         .await
+         .expect("query limiter semaphore is never closed")
+   }
+}
+
+/// Least recently used cache of query embeddings keyed by the query text, so that repeated
+/// queries, as sent by agents retrying a call, skip the embedding API
+pub struct QueryEmbeddingCache {
+   capacity: usize,
+   entries: Mutex<CacheEntries>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+   embeddings: HashMap<String, Vec<f32>>,
+   /// Cached queries from least to most recently used
+   recency: VecDeque<String>,
+}
+
+impl Default for QueryEmbeddingCache {
+   fn default() -> Self {
+      Self::new(query_cache_size())
+   }
+}
+
+impl QueryEmbeddingCache {
+   pub fn new(capacity: usize) -> Self {
+      Self {
+         capacity,
+         entries: Mutex::default(),
+      }
+   }
+
+   /// The cached embedding of a query, marking it as the most recently used
+   pub fn get(&self, query: &str) -> Option<Vec<f32>> {
+      let mut entries = self.entries.lock().unwrap();
+      let embedding = entries.embeddings.get(query)?.clone();
+      entries.recency.retain(|cached| cached != query);
+      entries.recency.push_back(query.to_string());
+      Some(embedding)
+   }
+
+   /// Caches the embedding of a query, evicting the least recently used one when full
+   pub fn insert(&self, query: &str, embedding: Vec<f32>) {
+      if self.capacity == 0 {
+         return;
+      }
+      let mut entries = self.entries.lock().unwrap();
+      if entries
+         .embeddings
+         .insert(query.to_string(), embedding)
+         .is_some()
+      {
+         entries.recency.retain(|cached| cached != query);
+      } else if entries.embeddings.len() > self.capacity
+         && let Some(evicted) = entries.recency.pop_front()
+      {
+         entries.embeddings.remove(&evicted);
+      }
+      entries.recency.push_back(query.to_string());
+   }
+}
+
+/// A query vector whose length differs from the dimension of the collection it searches
+#[derive(Debug, thiserror::Error)]
+#[error("query vector has {actual} dimensions, but the repository was embedded with {expected}")]
+pub struct VectorSizeMismatch {
+   pub expected: u64,
+   pub actual: usize,
+}
+
+/// Searches with a vector the caller computed instead of embedding a query, so no embedding
+/// API is involved. The vector must match the dimension of the collection of `data_store`.
+pub async fn search_by_vector(
+   data_store: &DataStore,
+   vector: Vec<f32>,
+   limit: u64,
+) -> Result<Vec<SearchResult>> {
+   let vector_size = data_store.vector_size().await?;
+   if vector.len() as u64 != vector_size {
+      return Err(
+         VectorSizeMismatch {
+            expected: vector_size,
+            actual: vector.len(),
+         }
+         .into(),
+      );
+   }
+
+   data_store
+      .query_with_content(vector, limit, &ChunkFilter::default(), None)
+      .await
+}
+
+/// Text embedded for a query against a collection: the query behind the prefix the collection
+/// was embedded for. Collections without metadata get the configured prefix, while those
+/// embedded before prefixes were recorded were embedded without one.
+pub fn query_input(
+   query: &str,
+   metadata: Option<&EmbeddingMetadata>,
+   config: &EmbeddingConfig,
+) -> String {
+   let prefix = metadata.map_or(config.query_prefix.as_str(), |metadata| {
+      metadata.query_prefix.as_str()
+   });
+   format!("{prefix}{query}")
+}
+
+/// Records that the repository of `data_store` was queried. Last query times decide which
+/// collections are evicted, so they are only tracked under a collection limit, off the
+/// query's path.
+pub fn record_query(data_store: DataStore) {
+   if EmbeddingConfig::from_env().max_collections.is_none() {
+      return;
+   }
+
+   tokio::spawn(async move {
+      if let Err(e) = data_store.record_query().await {
+         warn!("Failed to record query time: {e:#}");
+      }
+   });
+}
+
+/// Where the time of a query went
+#[derive(Debug, Clone, Copy)]
+pub struct QueryTimings {
+   /// Time taken to obtain the query embedding
+   pub embed: Duration,
+   /// Whether the embedding came from the cache rather than the embedding API
+   pub embed_cached: bool,
+   /// Time taken to search the collection
+   pub search: Duration,
+}
+
+impl fmt::Display for QueryTimings {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(
+         f,
+         "embedding {}ms{}, search {}ms",
+         self.embed.as_millis(),
+         if self.embed_cached { " (cached)" } else { "" },
+         self.search.as_millis()
+      )
+   }
+}
+
+/// How a single-repository query fetches and ranks its results
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueryOptions {
+   /// Drop results that repeat the content of a better match, replacing them by further
+   /// candidates
+   pub dedup: bool,
+   /// Share of file recency in the ranking, see `blend_recency`
+   pub recency_weight: f32,
+   /// HNSW `ef` of the search, trading latency for recall. Qdrant's default when `None`.
+   pub search_ef: Option<u64>,
+}
+
+/// Results of a search across several repositories
+#[derive(Debug)]
+pub struct MultiRepoResults {
+   /// Results tagged with the repository they came from, sorted by their score normalized
+   /// within their repository
+   pub results: Vec<(String, SearchResult)>,
+   /// Repositories whose search did not finish before the deadline
+   pub timed_out: Vec<String>,
+   /// Repositories whose search failed, e.g. because their collection was deleted
+   pub failed: Vec<String>,
+}
+
+/// Runs `search` for every repository with at most `concurrency` searches in flight. Searches
+/// still running at `timeout` are abandoned and searches that fail are logged, and the
+/// repositories of both are returned separately, in that order, so a single slow or broken
+/// collection cannot stall or fail the whole query.
+async fn search_repos<T, F, Fut>(
+   repo_urls: &[String],
+   concurrency: usize,
+   timeout: Duration,
+   search: F,
+) -> (Vec<(String, T)>, Vec<String>, Vec<String>)
+where
+   F: Fn(String) -> Fut,
+   Fut: Future<Output = Result<T>>,
+{
+   let deadline = tokio::time::Instant::now() + timeout;
+   let mut searches = stream::iter(repo_urls.iter().cloned())
+      .map(|repo_url| {
+         let search = search(repo_url.clone());
+         async move { (repo_url, search.await) }
+      })
+      .buffer_unordered(concurrency.max(1));
+
+   let mut completed = Vec::new();
+   let mut failed = Vec::new();
+   while let Ok(Some((repo_url, result))) = tokio::time::timeout_at(deadline, searches.next()).await
+   {
+      match result {
+         Ok(result) => completed.push((repo_url, result)),
+         Err(e) => {
+            warn!("search failed for {repo_url}: {e:#}");
+            failed.push(repo_url);
+         }
+      }
+   }
+
+   let timed_out = repo_urls
+      .iter()
+      .filter(|repo_url| {
+         !completed
+            .iter()
+            .any(|(completed, _)| completed == *repo_url)
+            && !failed.contains(repo_url)
+      })
+      .cloned()
+      .collect();
+
+   (completed, timed_out, failed)
+}
+
+/// Merges the results of several repositories into the `limit` best, tagged with their
+/// repository. Scores are normalized per repository first, since those of hybrid collections
+/// are not on the scale of the cosine similarities of dense ones.
+fn merge_repo_results(
+   repo_results: Vec<(String, Vec<SearchResult>)>,
+   limit: u64,
+) -> Vec<(String, SearchResult)> {
+   let mut results: Vec<(String, SearchResult)> = repo_results
+      .into_iter()
+      .flat_map(|(repo_url, mut results)| {
+         normalize_scores(&mut results);
+         results
+            .into_iter()
+            .map(move |result| (repo_url.clone(), result))
+      })
+      .collect();
+   results.sort_by(|(_, a), (_, b)| b.score.total_cmp(&a.score));
+   results.truncate(limit as usize);
+   results
+}
+
+/// Drops results whose content duplicates a higher scoring result, keeping at most `limit`
+pub fn dedup_results(results: Vec<SearchResult>, limit: u64) -> Vec<SearchResult> {
+   let mut seen = HashSet::new();
+
+   results
+      .into_iter()
+      .filter(|result| seen.insert(result.chunk.dedup_key()))
+      .take(limit as usize)
+      .collect()
+}
+
+/// Rescales scores over the results from 0.0 for the lowest to 1.0 for the highest, so that
+/// cosine similarities and the much smaller fusion scores of hybrid collections can be
+/// combined. Equal scores all become 1.0.
+pub fn normalize_scores(results: &mut [SearchResult]) {
+   let scores = results.iter().map(|result| result.score);
+   let (lowest, highest) = scores.fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), score| {
+      (lo.min(score), hi.max(score))
+   });
+
+   for result in results {
+      result.score = if highest > lowest {
+         (result.score - lowest) / (highest - lowest)
+      } else {
+         1.0
+      };
+   }
+}
+
+/// Reranks results by a blend of their score and the recency of their file, both normalized
+/// over the results from 0.0 for the lowest score or oldest file to 1.0 for the highest or
+/// newest. `recency_weight`, clamped to 0.0..=1.0, is the share of recency in the blend.
+/// Results without a modification time, from collections embedded without `file_recency`,
+/// count as the oldest.
+pub fn blend_recency(mut results: Vec<SearchResult>, recency_weight: f32) -> Vec<SearchResult> {
+   let weight = recency_weight.clamp(0.0, 1.0);
+   let times = results.iter().filter_map(|result| result.chunk.modified_at);
+   let (Some(oldest), Some(newest)) = (times.clone().min(), times.max()) else {
+      return results;
+   };
+
+   normalize_scores(&mut results);
+
+   for result in &mut results {
+      let recency = match result.chunk.modified_at {
+         Some(time) if newest > oldest => (time - oldest) as f32 / (newest - oldest) as f32,
+         Some(_) => 1.0,
+         None => 0.0,
+      };
+      result.score = (1.0 - weight) * result.score + weight * recency;
+   }
+   results.sort_by(|a, b| b.score.total_cmp(&a.score));
+   results
+}
+
+/// A chunk as listed in the outline of its file
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineEntry {
+   pub kind: Option<ChunkKind>,
+   pub name: Option<String>,
+   pub start_line: Option<usize>,
+   pub end_line: Option<usize>,
+}
+
+impl fmt::Display for OutlineEntry {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(
+         f,
+         "{}",
+         self.kind.map_or("chunk", |kind| kind.description())
+      )?;
+      if let Some(name) = &self.name {
+         write!(f, " {name}")?;
+      }
+      if let (Some(start), Some(end)) = (self.start_line, self.end_line) {
+         write!(f, " (lines {start}-{end})")?;
+      }
+      Ok(())
+   }
+}
+
+/// Groups chunks by file into outlines listing each chunk's kind, name and line range in
+/// the order they appear in the file. Whole-file and signature chunks are left out.
+pub fn file_outlines(chunks: Vec<ChunkPayload>) -> HashMap<String, Vec<OutlineEntry>> {
+   let mut outlines: HashMap<String, Vec<OutlineEntry>> = HashMap::new();
+   for chunk in chunks {
+      if chunk
+         .granularity
+         .is_some_and(|granularity| granularity != Granularity::Chunk)
+      {
+         continue;
+      }
+      let Some(file_path) = chunk.file_path else {
+         continue;
+      };
+      outlines.entry(file_path).or_default().push(OutlineEntry {
+         kind: chunk.kind,
+         name: chunk.name,
+         start_line: chunk.start_line,
+         end_line: chunk.end_line,
+      });
+   }
+
+   for outline in outlines.values_mut() {
+      outline.sort_by_key(|entry| (entry.start_line, entry.end_line));
+   }
+   outlines
+}
 
 pub struct QueryService {
    client: Client<OpenAIConfig>,
+   metrics: Arc<Metrics>,
+   /// Shared between callers, so absent for a service embedding with a caller's key
+   cache: Option<Arc<QueryEmbeddingCache>>,
 }
 
 impl QueryService {
    /// Initializes OpenAI client for query embedding generation, validating API
-   /// credentials
-   pub fn new() -> Result<Self> {
-      // Check for OpenAI API key
-      dotenvy::var("OPENAI_API_KEY").context("OPENAI_API_KEY not set")?;
+   /// credentials. Query embeddings are looked up in and added to `cache`, unless they are
+   /// embedded with the caller's `api_key` instead of the server's `OPENAI_API_KEY`: the
+   /// cache is skipped then, as its embeddings were paid for by other callers.
+   pub fn with_api_key(
+      metrics: Arc<Metrics>,
+      cache: Arc<QueryEmbeddingCache>,
+      api_key: Option<&ApiKey>,
+   ) -> Result<Self> {
+      let config = OpenAIConfig::new().with_api_key(resolve_api_key(api_key)?);
+      let client =
+         Client::with_config(config).with_http_client(openai_http_client(openai_timeout())?);
 
-      let config = OpenAIConfig::new();
-      let client = Client::with_config(config);
+      let mut service = Self::new_with_client(client, metrics, cache);
+      if api_key.is_some() {
+         service.cache = None;
+      }
+      Ok(service)
+   }
 
-      Ok(Self { client })
+   /// Uses a pre-built client for query embeddings, e.g. one pointed at a local server that
+   /// returns canned embeddings in tests
+   pub fn new_with_client(
+      client: Client<OpenAIConfig>,
+      metrics: Arc<Metrics>,
+      cache: Arc<QueryEmbeddingCache>,
+   ) -> Self {
+      Self {
+         client,
+         metrics,
+         cache: Some(cache),
+      }
    }
 
-   /// Converts natural language queries into embeddings and retrieves semantically
-   /// similar code/documentation from the indexed repository
+   /// Converts natural language queries into embeddings and retrieves semantically similar
+   /// code/documentation from the repository of `data_store`, ranked and fetched as set by
+   /// `options`. Only chunks passing `filter` are returned. The time spent embedding the query
+   /// and searching is returned alongside the results.
    pub async fn query_embeddings(
       &self,
       query: &str,
-      repo_url: &str,
+      data_store: DataStore,
       limit: u64,
-   ) -> Result<Vec<(f32, String)>> {
+      options: QueryOptions,
+      filter: &ChunkFilter,
+   ) -> Result<(Vec<SearchResult>, QueryTimings)> {
+      let repo_url = data_store.repo_url();
       info!("querying for: {query} in repository: {repo_url}");
 
+      let input = self.repo_query_input(&data_store, repo_url, query).await?;
+      let results = self
+         .search_embeddings(
+            query,
+            &input,
+            limit,
+            options,
+            |query_embedding, candidates| {
+               data_store.query_with_text(
+                  query,
+                  query_embedding,
+                  candidates,
+                  filter,
+                  options.search_ef,
+               )
+            },
+         )
+         .await?;
+
+      record_query(data_store);
+
+      Ok(results)
+   }
+
+   /// Embeds `input` and runs `search` with the embedding and the number of candidates to
+   /// fetch, which exceeds `limit` when deduplicating or weighing recency
+   async fn search_embeddings<F, Fut>(
+      &self,
+      query: &str,
+      input: &str,
+      limit: u64,
+      options: QueryOptions,
+      search: F,
+   ) -> Result<(Vec<SearchResult>, QueryTimings)>
+   where
+      F: FnOnce(Vec<f32>, u64) -> Fut,
+      Fut: Future<Output = Result<Vec<SearchResult>>>,
+   {
+      let embed_started = Instant::now();
+      let (query_embedding, embed_cached) = self.cached_embedding(input).await?;
+      let embed = embed_started.elapsed();
+
+      let rerank = options.recency_weight > 0.0;
+      let candidates = if options.dedup || rerank {
+         limit.saturating_mul(OVERFETCH)
+      } else {
+         limit
+      };
+      let search_started = Instant::now();
+      let mut results = search(query_embedding, candidates).await?;
+      if rerank {
+         results = blend_recency(results, options.recency_weight);
+      }
+      if options.dedup {
+         results = dedup_results(results, limit);
+      } else {
+         results.truncate(limit as usize);
+      }
+      let timings = QueryTimings {
+         embed,
+         embed_cached,
+         search: search_started.elapsed(),
+      };
+      info!("query timings for {query}: {timings}");
+
+      if results.is_empty() {
+         info!("no results found for query: {query}");
+         return Ok((vec![], timings));
+      }
+
+      info!("found {} results for query: {}", results.len(), query);
+      Ok((results, timings))
+   }
+
+   /// Outlines of the given files of a repository, read from the chunks stored for them, so
+   /// that results can be placed within the structure of their file
+   pub async fn file_outlines(
+      &self,
+      repo_url: &str,
+      file_paths: &[String],
+   ) -> Result<HashMap<String, Vec<OutlineEntry>>> {
       let data_store = DataStore::new(repo_url).await?;
-      let query_embedding = self.embed_query(query).await?;
+      let chunks = data_store.file_chunks(file_paths).await?;
+
+      Ok(file_outlines(chunks))
+   }
+
+   /// Finds the single closest chunk by embedding similarity alone, so its score stays a
+   /// cosine similarity that can be compared against a threshold even in hybrid collections.
+   /// Whole-file and signature chunks are skipped since an answer cites a complete item.
+   pub async fn best_match(&self, query: &str, repo_url: &str) -> Result<Option<SearchResult>> {
+      info!("finding best match for: {query} in repository: {repo_url}");
 
+      let data_store = DataStore::new(repo_url).await?;
+      let input = self.repo_query_input(&data_store, repo_url, query).await?;
+      let query_embedding = self.embed_query(&input).await?;
       let results = data_store
-         .query_with_content(query_embedding, limit)
+         .query_with_content(
+            query_embedding,
+            1,
+            &ChunkFilter {
+               granularity: Some(Granularity::Chunk),
+               ..ChunkFilter::default()
+            },
+            None,
+         )
          .await?;
+      record_query(data_store);
 
-      if results.is_empty() {
-         info!("no results found for query: {query}");
-         return Ok(vec![]);
+      Ok(results.into_iter().next())
+   }
+
+   /// Searches several repositories with a single query embedding and merges the results
+   /// into one list sorted by score, tagging each result with the repository it came from.
+   /// Repositories are searched concurrently up to `query_concurrency()`, and those that do
+   /// not answer within `query_timeout()` are reported instead of failing the query.
+   pub async fn query_repos(
+      &self,
+      query: &str,
+      repo_urls: &[String],
+      limit: u64,
+   ) -> Result<MultiRepoResults> {
+      info!(
+         "querying for: {query} across {} repositories",
+         repo_urls.len()
+      );
+
+      // repositories share the embedding of the configured prefix, those embedded for another
+      // prefix get their own
+      let default_input = query_input(query, None, &EmbeddingConfig::from_env());
+      let default_embedding = self.embed_query(&default_input).await?;
+
+      let (repo_results, timed_out, failed) = search_repos(
+         repo_urls,
+         query_concurrency(),
+         query_timeout(),
+         |repo_url| {
+            let default_embedding = default_embedding.clone();
+            let default_input = &default_input;
+            async move {
+               let data_store = DataStore::new(&repo_url).await?;
+               let input = self.repo_query_input(&data_store, &repo_url, query).await?;
+               let query_embedding = if input == *default_input {
+                  default_embedding
+               } else {
+                  self.embed_query(&input).await?
+               };
+               let results = data_store
+                  .query_with_text(query, query_embedding, limit, &ChunkFilter::default(), None)
+                  .await?;
+               record_query(data_store);
+               Ok(results)
+            }
+         },
+      )
+      .await;
+
+      if !timed_out.is_empty() {
+         warn!(
+            "search timed out for {} repositories: {}",
+            timed_out.len(),
+            timed_out.join(", ")
+         );
       }
 
+      let results = merge_repo_results(repo_results, limit);
+
       info!("found {} results for query: {}", results.len(), query);
-      Ok(results)
+      Ok(MultiRepoResults {
+         results,
+         timed_out,
+         failed,
+      })
    }
 
    /// Transforms user queries into high-dimensional vectors for similarity comparison
    /// with stored documentation embeddings
    pub async fn embed_query(&self, query: &str) -> Result<Vec<f32>> {
+      Ok(self.cached_embedding(query).await?.0)
+   }
+
+   /// Text to embed for a query against a repository, see `query_input`
+   async fn repo_query_input(
+      &self,
+      data_store: &DataStore,
+      repo_url: &str,
+      query: &str,
+   ) -> Result<String> {
+      let metadata = DataStore::get_metadata(&data_store.qdrant_client, repo_url).await?;
+      Ok(query_input(
+         query,
+         metadata.as_ref(),
+         &EmbeddingConfig::from_env(),
+      ))
+   }
+
+   /// Embeds a query unless its embedding is cached, telling whether it was
+   async fn cached_embedding(&self, query: &str) -> Result<(Vec<f32>, bool)> {
+      let Some(cache) = &self.cache else {
+         return Ok((self.request_embedding(query).await?, false));
+      };
+      if let Some(embedding) = cache.get(query) {
+         return Ok((embedding, true));
+      }
+
+      let embedding = self.request_embedding(query).await?;
+      cache.insert(query, embedding.clone());
+      Ok((embedding, false))
+   }
+
+   async fn request_embedding(&self, query: &str) -> Result<Vec<f32>> {
       let request = CreateEmbeddingRequestArgs::default()
-         .model("text-embedding-3-small")
+         .model(EMBEDDING_MODEL)
          .input(vec![query])
          .build()?;
 
+      let timer = self.metrics.provider_request_started("query");
       let response = self
          .client
          .embeddings()
          .create(request)
          .await
          .context("Failed to create query embedding")?;
+      timer.observe_duration();
 
       anyhow::ensure!(
          !response.data.is_empty(),
@@ -69,3 +734,421 @@ impl QueryService {
       Ok(response.data[0].embedding.clone())
    }
 }
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::mock_qdrant::MockQdrant;
+
+   fn result(score: f32, content: &str) -> SearchResult {
+      SearchResult {
+         score,
+         chunk: ChunkPayload {
+            content: content.to_string(),
+            ..Default::default()
+         },
+      }
+   }
+
+   #[tokio::test]
+   async fn test_search_repos_caps_concurrency_and_skips_slow_repos() -> Result<()> {
+      use std::sync::atomic::{AtomicUsize, Ordering};
+
+      let repo_urls: Vec<String> = ["fast/a", "fast/b", "slow/c", "fast/d", "broken/e", "fast/f"]
+         .map(String::from)
+         .to_vec();
+      let in_flight = AtomicUsize::new(0);
+      let max_in_flight = AtomicUsize::new(0);
+
+      let (completed, timed_out, failed) =
+         search_repos(&repo_urls, 2, Duration::from_millis(300), |repo_url| {
+            let (in_flight, max_in_flight) = (&in_flight, &max_in_flight);
+            async move {
+               let running = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+               max_in_flight.fetch_max(running, Ordering::SeqCst);
+               let delay = if repo_url.starts_with("slow") {
+                  10_000
+               } else {
+                  10
+               };
+               tokio::time::sleep(Duration::from_millis(delay)).await;
+               in_flight.fetch_sub(1, Ordering::SeqCst);
+               if repo_url.starts_with("broken") {
+                  anyhow::bail!("collection {repo_url} not found");
+               }
+               Ok(repo_url.len())
+            }
+         })
+         .await;
+
+      assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+      let mut completed: Vec<_> = completed.into_iter().map(|(repo, _)| repo).collect();
+      completed.sort();
+      // a failing repository leaves the results of the others
+      assert_eq!(completed, vec!["fast/a", "fast/b", "fast/d", "fast/f"]);
+      assert_eq!(timed_out, vec!["slow/c".to_string()]);
+      assert_eq!(failed, vec!["broken/e".to_string()]);
+      Ok(())
+   }
+
+   #[test]
+   fn test_hybrid_repos_rank_alongside_dense_ones() {
+      let repo_results = vec![
+         (
+            "https://github.com/owner/dense".to_string(),
+            vec![
+               result(0.82, "fn dense_best() {}"),
+               result(0.41, "fn dense_worst() {}"),
+            ],
+         ),
+         (
+            "https://github.com/owner/hybrid".to_string(),
+            vec![
+               result(0.033, "fn hybrid_best() {}"),
+               result(0.016, "fn hybrid_worst() {}"),
+            ],
+         ),
+      ];
+
+      let merged = merge_repo_results(repo_results, 2);
+
+      let mut contents: Vec<&str> = merged
+         .iter()
+         .map(|(_, result)| result.chunk.content.as_str())
+         .collect();
+      contents.sort();
+      assert_eq!(contents, vec!["fn dense_best() {}", "fn hybrid_best() {}"]);
+   }
+
+   #[test]
+   fn test_dedup_results_refills_limit() {
+      let results = vec![
+         result(0.9, "fn parse() {}"),
+         result(0.8, "fn  parse() {}\n"),
+         result(0.7, "fn render() {}"),
+         result(0.6, "fn parse() {}"),
+         result(0.5, "fn write() {}"),
+         result(0.4, "fn read() {}"),
+      ];
+
+      let deduped = dedup_results(results, 3);
+      let scores: Vec<f32> = deduped.iter().map(|result| result.score).collect();
+      assert_eq!(scores, vec![0.9, 0.7, 0.5]);
+   }
+
+   #[test]
+   fn test_blend_recency_favors_recent_files() {
+      let dated = |score, content: &str, modified_at| {
+         let mut result = result(score, content);
+         result.chunk.modified_at = modified_at;
+         result
+      };
+      let results = vec![
+         dated(0.90, "fn stale() {}", Some(1_000)),
+         dated(0.85, "fn fresh() {}", Some(2_000)),
+         dated(0.80, "fn undated() {}", None),
+      ];
+
+      let unweighted = blend_recency(results.clone(), 0.0);
+      assert_eq!(unweighted[0].chunk.content, "fn stale() {}");
+
+      let blended = blend_recency(results, 0.5);
+      let contents: Vec<&str> = blended
+         .iter()
+         .map(|result| result.chunk.content.as_str())
+         .collect();
+      assert_eq!(
+         contents,
+         vec!["fn fresh() {}", "fn stale() {}", "fn undated() {}"]
+      );
+      assert!((blended[0].score - 0.75).abs() < 1e-6);
+   }
+
+   #[test]
+   fn test_recency_does_not_swamp_fusion_scores() {
+      // hybrid collections score by reciprocal rank fusion, far below cosine similarities
+      let dated = |score, content: &str, modified_at| {
+         let mut result = result(score, content);
+         result.chunk.modified_at = Some(modified_at);
+         result
+      };
+      let results = vec![
+         dated(0.033, "fn relevant() {}", 1_000),
+         dated(0.016, "fn recent() {}", 2_000),
+      ];
+
+      let blended = blend_recency(results, 0.1);
+
+      assert_eq!(blended[0].chunk.content, "fn relevant() {}");
+   }
+
+   #[test]
+   fn test_file_outlines_list_chunks_in_file_order() {
+      let chunk = |file_path: &str, kind, name: &str, start_line| {
+         let mut chunk = result(0.0, "").chunk;
+         chunk.file_path = Some(file_path.to_string());
+         chunk.kind = Some(kind);
+         chunk.name = Some(name.to_string());
+         chunk.start_line = Some(start_line);
+         chunk.end_line = Some(start_line + 2);
+         chunk
+      };
+
+      let outlines = file_outlines(vec![
+         chunk("src/lib.rs", ChunkKind::Impl, "Parser", 10),
+         chunk("src/main.rs", ChunkKind::Function, "main", 1),
+         chunk("src/lib.rs", ChunkKind::Struct, "Parser", 1),
+      ]);
+
+      let lib: Vec<String> = outlines["src/lib.rs"]
+         .iter()
+         .map(ToString::to_string)
+         .collect();
+      assert_eq!(
+         lib,
+         vec![
+            "struct Parser (lines 1-3)",
+            "impl block Parser (lines 10-12)"
+         ]
+      );
+      assert_eq!(outlines["src/main.rs"].len(), 1);
+   }
+
+   #[test]
+   fn test_query_cache_evicts_least_recently_used() {
+      let cache = QueryEmbeddingCache::new(2);
+      cache.insert("parse json", vec![1.0]);
+      cache.insert("spawn task", vec![2.0]);
+
+      // using the first query makes the second the least recently used
+      assert_eq!(cache.get("parse json"), Some(vec![1.0]));
+      cache.insert("open file", vec![3.0]);
+
+      assert_eq!(cache.get("spawn task"), None);
+      assert_eq!(cache.get("parse json"), Some(vec![1.0]));
+      assert_eq!(cache.get("open file"), Some(vec![3.0]));
+
+      let disabled = QueryEmbeddingCache::new(0);
+      disabled.insert("parse json", vec![1.0]);
+      assert_eq!(disabled.get("parse json"), None);
+   }
+
+   #[tokio::test]
+   async fn test_query_limiter_queues_queries_beyond_the_limit() {
+      use std::sync::atomic::{AtomicUsize, Ordering};
+
+      let limiter = Arc::new(QueryLimiter::new(2));
+      let running = Arc::new(AtomicUsize::new(0));
+      let peak = Arc::new(AtomicUsize::new(0));
+
+      let queries: Vec<_> = (0..8)
+         .map(|i| {
+            let (limiter, running, peak) = (limiter.clone(), running.clone(), peak.clone());
+            tokio::spawn(async move {
+               let _permit = limiter.acquire().await;
+               let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+               peak.fetch_max(now, Ordering::SeqCst);
+               tokio::time::sleep(Duration::from_millis(10)).await;
+               running.fetch_sub(1, Ordering::SeqCst);
+               i
+            })
+         })
+         .collect();
+
+      let mut completed = Vec::new();
+      for query in queries {
+         completed.push(query.await.unwrap());
+      }
+
+      assert_eq!(completed, (0..8).collect::<Vec<_>>());
+      assert_eq!(peak.load(Ordering::SeqCst), 2);
+   }
+
+   #[tokio::test]
+   async fn test_search_by_vector_returns_nearest_chunk() -> Result<()> {
+      let qdrant = MockQdrant::start().await?;
+      let data_store = DataStore::with_client(
+         qdrant.client(),
+         "https://github.com/owner/repo",
+         &EmbeddingConfig::default(),
+      )?;
+      data_store
+         .add_embeddings_batch(vec![
+            (result(0.0, "fn parse() {}").chunk, vec![1.0, 0.0, 0.0]),
+            (result(0.0, "fn spawn() {}").chunk, vec![0.0, 1.0, 0.0]),
+            (
+               result(0.0, "fn spawn_parser() {}").chunk,
+               vec![0.6, 0.8, 0.0],
+            ),
+         ])
+         .await?;
+
+      let results = search_by_vector(&data_store, vec![0.1, 0.9, 0.0], 2).await?;
+      let contents: Vec<_> = results.iter().map(|r| r.chunk.content.as_str()).collect();
+      assert_eq!(contents, ["fn spawn() {}", "fn spawn_parser() {}"]);
+
+      let err = search_by_vector(&data_store, vec![0.0, 1.0], 2)
+         .await
+         .unwrap_err();
+      let mismatch = err
+         .downcast_ref::<VectorSizeMismatch>()
+         .expect("size mismatch");
+      assert_eq!((mismatch.expected, mismatch.actual), (3, 2));
+      Ok(())
+   }
+
+   #[test]
+   fn test_query_prefix_is_prepended_before_embedding() {
+      let config = EmbeddingConfig {
+         query_prefix: "search_query: ".to_string(),
+         ..EmbeddingConfig::default()
+      };
+      let mut metadata: EmbeddingMetadata = serde_json::from_value(serde_json::json!({
+         "repo_url": "https://github.com/owner/repo",
+         "embedded_at": "2025-01-01T00:00:00Z",
+         "embedding_model": "nomic-embed-text",
+         "doc_count": 1
+      }))
+      .unwrap();
+
+      // collections embedded before prefixes were recorded had none
+      assert_eq!(
+         query_input("parse json", Some(&metadata), &config),
+         "parse json"
+      );
+
+      metadata.query_prefix = "query: ".to_string();
+      assert_eq!(
+         query_input("parse json", Some(&metadata), &config),
+         "query: parse json"
+      );
+      assert_eq!(
+         query_input("parse json", None, &config),
+         "search_query: parse json"
+      );
+   }
+
+   /// Serves canned embeddings at `/embeddings` like the OpenAI API, counting the requests
+   async fn mock_embeddings_server(
+      embedding: Vec<f32>,
+   ) -> Result<(String, Arc<std::sync::atomic::AtomicUsize>)> {
+      use std::sync::atomic::{AtomicUsize, Ordering};
+
+      let requests = Arc::new(AtomicUsize::new(0));
+      let counter = requests.clone();
+      let router = axum::Router::new().route(
+         "/embeddings",
+         axum::routing::post(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let embedding = embedding.clone();
+            async move {
+               axum::Json(serde_json::json!({
+                  "object": "list",
+                  "data": [{"object": "embedding", "index": 0, "embedding": embedding}],
+                  "model": EMBEDDING_MODEL,
+                  "usage": {"prompt_tokens": 3, "total_tokens": 3}
+               }))
+            }
+         }),
+      );
+      let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+      let api_base = format!("http://{}", listener.local_addr()?);
+      tokio::spawn(async move { axum::serve(listener, router).await });
+
+      Ok((api_base, requests))
+   }
+
+   #[tokio::test]
+   async fn test_query_embeddings_with_injected_client() -> Result<()> {
+      use std::sync::atomic::Ordering;
+
+      let (api_base, requests) = mock_embeddings_server(vec![0.25, -0.5]).await?;
+      let client = Client::with_config(
+         OpenAIConfig::new()
+            .with_api_key("test")
+            .with_api_base(api_base),
+      );
+      let service = QueryService::new_with_client(
+         client,
+         Arc::new(Metrics::new()),
+         Arc::new(QueryEmbeddingCache::new(8)),
+      );
+
+      let qdrant = MockQdrant::start().await?;
+      let config = EmbeddingConfig::default();
+      let repo_url = "https://github.com/owner/repo";
+      let data_store = DataStore::with_client(qdrant.client(), repo_url, &config)?;
+      let chunk = |file_path: &str, content: &str| ChunkPayload {
+         file_path: Some(file_path.to_string()),
+         ..result(0.0, content).chunk
+      };
+      data_store
+         .add_embeddings_batch(vec![
+            (chunk("src/a.rs", "fn parse() {}"), vec![1.0, -2.0]),
+            (chunk("src/b.rs", "fn parse() {}"), vec![1.0, -1.8]),
+            (chunk("src/c.rs", "fn render() {}"), vec![1.0, -1.0]),
+            (chunk("src/d.rs", "fn write() {}"), vec![1.0, 0.0]),
+         ])
+         .await?;
+      data_store
+         .store_metadata(4, None, None, None, Default::default(), &config)
+         .await?;
+      let dedup = QueryOptions {
+         dedup: true,
+         ..QueryOptions::default()
+      };
+      let query = || async {
+         let data_store = DataStore::with_client(qdrant.client(), repo_url, &config)?;
+         service
+            .query_embeddings("parse", data_store, 2, dedup, &ChunkFilter::default())
+            .await
+      };
+
+      let (results, timings) = query().await?;
+      let contents: Vec<&str> = results
+         .iter()
+         .map(|result| result.chunk.content.as_str())
+         .collect();
+      assert_eq!(contents, vec!["fn parse() {}", "fn render() {}"]);
+      assert_eq!(results[0].chunk.file_path.as_deref(), Some("src/a.rs"));
+      assert!(!timings.embed_cached);
+
+      // the repeated query is answered from the cache
+      let (_, timings) = query().await?;
+      assert!(timings.embed_cached);
+      assert_eq!(requests.load(Ordering::SeqCst), 1);
+      Ok(())
+   }
+
+   #[tokio::test]
+   async fn test_caller_key_queries_skip_the_cache() -> Result<()> {
+      use std::sync::atomic::Ordering;
+
+      let cache = Arc::new(QueryEmbeddingCache::new(8));
+      cache.insert("query: parse", vec![1.0, 0.0]);
+      let metrics = Arc::new(Metrics::new());
+      let service = QueryService::with_api_key(
+         metrics.clone(),
+         cache.clone(),
+         Some(&ApiKey::new("sk-caller")),
+      )?;
+      assert!(service.cache.is_none());
+
+      let (api_base, requests) = mock_embeddings_server(vec![0.25, -0.5]).await?;
+      let service = QueryService {
+         client: Client::with_config(
+            OpenAIConfig::new()
+               .with_api_key("sk-caller")
+               .with_api_base(api_base),
+         ),
+         ..service
+      };
+
+      // the embedding cached for the server's key is neither used nor replaced
+      assert_eq!(service.embed_query("query: parse").await?, vec![0.25, -0.5]);
+      assert_eq!(service.embed_query("query: parse").await?, vec![0.25, -0.5]);
+      assert_eq!(requests.load(Ordering::SeqCst), 2);
+      assert_eq!(cache.get("query: parse"), Some(vec![1.0, 0.0]));
+      Ok(())
+   }
+}
@@ -0,0 +1,200 @@
+/// Running mean of a repository's chunk embeddings, summarizing the whole repository as a
+/// single vector that can be compared against other repositories
+#[derive(Debug, Clone, Default)]
+pub struct Centroid {
+   sum: Vec<f64>,
+   count: usize,
+}
+
+impl Centroid {
+   /// Adds an embedding to the mean. Vectors of a different dimension than the first one
+   /// added are ignored.
+   pub fn add(&mut self, vector: &[f32]) {
+      if self.sum.is_empty() {
+         self.sum = vec![0.0; vector.len()];
+      } else if self.sum.len() != vector.len() {
+         return;
+      }
+
+      for (sum, value) in self.sum.iter_mut().zip(vector) {
+         *sum += f64::from(*value);
+      }
+      self.count += 1;
+   }
+
+   /// Rebuilds a centroid from a stored mean and the number of embeddings it averaged, so
+   /// further embeddings can be added to it
+   pub fn from_mean(mean: &[f32], count: usize) -> Self {
+      if count == 0 {
+         return Self::default();
+      }
+
+      Self {
+         sum: mean
+            .iter()
+            .map(|value| f64::from(*value) * count as f64)
+            .collect(),
+         count,
+      }
+   }
+
+   /// Combines the embeddings accumulated by another centroid into this one
+   pub fn merge(&mut self, other: Centroid) {
+      if other.count == 0 {
+         return;
+      }
+      if self.count == 0 {
+         *self = other;
+         return;
+      }
+      if self.sum.len() != other.sum.len() {
+         return;
+      }
+
+      for (sum, value) in self.sum.iter_mut().zip(other.sum) {
+         *sum += value;
+      }
+      self.count += other.count;
+   }
+
+   /// The mean vector, or `None` when no embeddings were added
+   pub fn finish(self) -> Option<Vec<f32>> {
+      if self.count == 0 {
+         return None;
+      }
+
+      let count = self.count as f64;
+      Some(
+         self
+            .sum
+            .into_iter()
+            .map(|sum| (sum / count) as f32)
+            .collect(),
+      )
+   }
+}
+
+/// Cosine similarity between two vectors, or `None` when their dimensions differ or either
+/// has zero length
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+   if a.len() != b.len() {
+      return None;
+   }
+
+   let (dot, norm_a, norm_b) =
+      a.iter()
+         .zip(b)
+         .fold((0.0f64, 0.0f64, 0.0f64), |(dot, norm_a, norm_b), (a, b)| {
+            let (a, b) = (f64::from(*a), f64::from(*b));
+            (dot + a * b, norm_a + a * a, norm_b + b * b)
+         });
+
+   if norm_a == 0.0 || norm_b == 0.0 {
+      return None;
+   }
+
+   Some((dot / (norm_a.sqrt() * norm_b.sqrt())) as f32)
+}
+
+/// Ranks candidate repositories by the similarity of their centroid to the target centroid,
+/// most similar first. Candidates whose centroid cannot be compared are left out.
+pub fn rank_by_similarity(
+   target: &[f32],
+   candidates: Vec<(String, Vec<f32>)>,
+) -> Vec<(String, f32)> {
+   let mut ranked: Vec<(String, f32)> = candidates
+      .into_iter()
+      .filter_map(|(repo_name, centroid)| {
+         cosine_similarity(target, &centroid).map(|score| (repo_name, score))
+      })
+      .collect();
+
+   ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+   ranked
+}
+
+/// Rescales scores to their relative standing within a result set, mapping the best score to
+/// 1.0 and the worst to 0.0. Raw cosine scores cluster in a narrow band, which makes them hard
+/// to compare at a glance. A set whose scores are all equal maps to 1.0.
+pub fn relative_relevance(scores: &[f32]) -> Vec<f32> {
+   let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+   let min = scores.iter().copied().fold(f32::INFINITY, f32::min);
+   let range = max - min;
+
+   scores
+      .iter()
+      .map(|score| {
+         if range > f32::EPSILON {
+            (score - min) / range
+         } else {
+            1.0
+         }
+      })
+      .collect()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_relative_relevance_spans_result_set() {
+      let relevance = relative_relevance(&[0.58, 0.47, 0.41, 0.36]);
+
+      assert!((relevance[0] - 1.0).abs() < 1e-6);
+      assert!((relevance[3] - 0.0).abs() < 1e-6);
+      assert!(relevance[1] > relevance[2]);
+      assert_eq!(relative_relevance(&[0.5, 0.5]), vec![1.0, 1.0]);
+   }
+
+   fn centroid_of(vectors: &[[f32; 3]]) -> Vec<f32> {
+      let mut centroid = Centroid::default();
+      for vector in vectors {
+         centroid.add(vector);
+      }
+      centroid.finish().unwrap()
+   }
+
+   #[test]
+   fn test_centroid_is_mean_of_vectors() {
+      assert_eq!(
+         centroid_of(&[[1.0, 0.0, 2.0], [3.0, 2.0, 0.0]]),
+         vec![2.0, 1.0, 1.0]
+      );
+      assert_eq!(Centroid::default().finish(), None);
+
+      let mut first = Centroid::default();
+      first.add(&[1.0, 0.0, 2.0]);
+      let mut second = Centroid::default();
+      second.add(&[3.0, 2.0, 0.0]);
+      second.add(&[2.0, 1.0, 1.0]);
+      first.merge(second);
+      assert_eq!(first.finish(), Some(vec![2.0, 1.0, 1.0]));
+   }
+
+   #[test]
+   fn test_rank_similar_repos() {
+      // dimensions loosely stand for "web", "database" and "graphics" content
+      let web_framework = centroid_of(&[[0.9, 0.2, 0.0], [0.8, 0.3, 0.1]]);
+      let http_client = centroid_of(&[[0.95, 0.05, 0.0], [0.7, 0.1, 0.0]]);
+      let orm = centroid_of(&[[0.3, 0.9, 0.0], [0.2, 0.8, 0.1]]);
+      let game_engine = centroid_of(&[[0.0, 0.1, 0.9], [0.1, 0.0, 0.95]]);
+
+      let ranked = rank_by_similarity(
+         &web_framework,
+         vec![
+            ("acme/game-engine".to_string(), game_engine),
+            ("acme/orm".to_string(), orm),
+            ("acme/http-client".to_string(), http_client),
+            ("acme/legacy".to_string(), vec![1.0, 0.0]),
+         ],
+      );
+
+      let names: Vec<&str> = ranked.iter().map(|(name, _)| name.as_str()).collect();
+      assert_eq!(
+         names,
+         vec!["acme/http-client", "acme/orm", "acme/game-engine"]
+      );
+      assert!(ranked[0].1 > 0.95);
+   }
+}
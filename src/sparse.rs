@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+/// A sparse term-frequency vector for keyword matching. Qdrant applies IDF weighting to these
+/// at query time, which together amounts to BM25-style scoring.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseVector {
+   pub indices: Vec<u32>,
+   pub values: Vec<f32>,
+}
+
+impl SparseVector {
+   /// Builds the term-frequency vector of a text. Identifiers are indexed both whole and split
+   /// into their snake_case and camelCase parts, so a query for `parse_url` or `url` both match.
+   pub fn from_text(text: &str) -> Self {
+      let mut frequencies: BTreeMap<u32, f32> = BTreeMap::new();
+
+      for token in tokenize(text) {
+         *frequencies.entry(term_index(&token)).or_default() += 1.0;
+      }
+
+      let (indices, values) = frequencies.into_iter().unzip();
+      Self { indices, values }
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.indices.is_empty()
+   }
+}
+
+/// Splits text into lowercase terms: every identifier-like word, plus the parts of compound
+/// identifiers
+pub(crate) fn tokenize(text: &str) -> Vec<String> {
+   let mut tokens = Vec::new();
+
+   for word in text
+      .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+      .filter(|word| !word.is_empty())
+   {
+      let parts = identifier_parts(word);
+      if parts.len() > 1 {
+         tokens.extend(parts.iter().map(|part| part.to_lowercase()));
+      }
+      tokens.push(word.to_lowercase());
+   }
+
+   tokens
+}
+
+/// Splits an identifier on underscores and lowercase-to-uppercase boundaries
+fn identifier_parts(word: &str) -> Vec<&str> {
+   let mut parts = Vec::new();
+
+   for segment in word.split('_').filter(|segment| !segment.is_empty()) {
+      let mut start = 0;
+      let mut previous: Option<char> = None;
+      for (i, c) in segment.char_indices() {
+         if let Some(previous) = previous
+            && previous.is_lowercase()
+            && c.is_uppercase()
+         {
+            parts.push(&segment[start..i]);
+            start = i;
+         }
+         previous = Some(c);
+      }
+      parts.push(&segment[start..]);
+   }
+
+   parts
+}
+
+/// Maps a term to its sparse vector dimension with 32-bit FNV-1a
+fn term_index(term: &str) -> u32 {
+   const FNV_OFFSET_BASIS: u32 = 0x811c9dc5;
+   const FNV_PRIME: u32 = 0x01000193;
+
+   term.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+      (hash ^ u32::from(byte)).wrapping_mul(FNV_PRIME)
+   })
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_tokenize_splits_identifiers() {
+      assert_eq!(
+         tokenize("fn parseUrl(raw_input)"),
+         vec![
+            "fn",
+            "parse",
+            "url",
+            "parseurl",
+            "raw",
+            "input",
+            "raw_input"
+         ]
+      );
+   }
+
+   #[test]
+   fn test_sparse_vector_counts_terms() {
+      let vector = SparseVector::from_text("retry retry backoff");
+
+      assert_eq!(vector.indices.len(), 2);
+      let retry = vector
+         .indices
+         .iter()
+         .position(|index| *index == term_index("retry"))
+         .unwrap();
+      assert_eq!(vector.values[retry], 2.0);
+      // indices are sorted and unique as Qdrant requires
+      assert!(vector.indices.windows(2).all(|pair| pair[0] < pair[1]));
+
+      assert!(SparseVector::from_text("  ... ").is_empty());
+   }
+}
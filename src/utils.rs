@@ -25,6 +25,19 @@ pub fn gen_table_name_for_repo(repo_url: &str) -> Result<String> {
    }
 }
 
+/// Owner under which single files embedded from a URL are stored. GitHub owners cannot start
+/// with an underscore, so these never collide with a repository's collection.
+pub const FILE_URL_OWNER: &str = "_file";
+
+/// Builds the repository URL a file embedded from `file_url` is stored and queried under,
+/// whose shorthand form is `_file/<hash of the URL>`
+pub fn gen_repo_url_for_file(file_url: &str) -> String {
+   format!(
+      "https://github.com/{FILE_URL_OWNER}/{}",
+      content_hash(file_url)
+   )
+}
+
 /// Parses a collection name back to owner/repo format
 pub fn parse_collection_name_to_repo(collection_name: &str) -> String {
    // Simply replace double underscore back to slash
@@ -49,6 +62,17 @@ pub fn extract_repo_name_from_url(repo_url: &str) -> Result<String> {
    }
 }
 
+/// Whether a repository input is an alias given to a repository at embed time rather than a
+/// URL or `owner/repo` shorthand. Aliases consist of ASCII letters, digits, `-`, `_` and `.`,
+/// and may not contain `__`, which separates owner and repo in collection names.
+pub fn is_repo_alias(input: &str) -> bool {
+   !input.is_empty()
+      && !input.contains("__")
+      && input
+         .chars()
+         .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
 /// Normalizes various repository input formats into canonical GitHub URLs,
 /// supporting both shorthand and full URL inputs for user convenience
 pub fn parse_repository_input(input: &str) -> Result<String> {
@@ -75,6 +99,149 @@ pub fn parse_repository_input(input: &str) -> Result<String> {
    }
 }
 
+/// Builds a GitHub permalink to a repository file at `commit` so results can be cited
+/// directly, anchored to a line range when `lines` is given. Returns `None` for repositories
+/// not hosted on GitHub.
+pub fn gen_permalink(
+   repo_url: &str,
+   commit: &str,
+   file_path: &str,
+   lines: Option<(usize, usize)>,
+) -> Option<String> {
+   let url = Url::parse(repo_url).ok()?;
+   if url.host_str() != Some("github.com") {
+      return None;
+   }
+
+   let repo = url.path().trim_matches('/');
+   let anchor = match lines {
+      Some((start_line, end_line)) if start_line == end_line => format!("#L{start_line}"),
+      Some((start_line, end_line)) => format!("#L{start_line}-L{end_line}"),
+      None => String::new(),
+   };
+
+   Some(format!(
+      "https://github.com/{repo}/blob/{commit}/{file_path}{anchor}"
+   ))
+}
+
+/// Maps a file path to the name of the language its chunker handles
+pub fn language_name(file_path: &str) -> Option<&'static str> {
+   match file_path.rsplit('.').next() {
+      Some("rs") => Some("Rust"),
+      Some("ts") => Some("TypeScript"),
+      Some("md" | "mdx") => Some("Markdown"),
+      _ => None,
+   }
+}
+
+/// Checks whether an `owner/repo` name matches a repository pattern. Patterns may use `*` as
+/// a wildcard (e.g. `acme/*`); patterns without a wildcard match as a prefix.
+pub fn matches_repo_pattern(pattern: &str, repo_name: &str) -> bool {
+   if !pattern.contains('*') {
+      return repo_name.starts_with(pattern);
+   }
+
+   let parts: Vec<&str> = pattern.split('*').collect();
+   let (first, last) = (parts[0], parts[parts.len() - 1]);
+
+   if !repo_name.starts_with(first) || repo_name.len() < first.len() + last.len() {
+      return false;
+   }
+
+   // match the middle parts in order between the fixed prefix and suffix
+   let mut remaining = &repo_name[first.len()..repo_name.len() - last.len()];
+   for part in &parts[1..parts.len() - 1] {
+      match remaining.find(part) {
+         Some(idx) => remaining = &remaining[idx + part.len()..],
+         None => return false,
+      }
+   }
+
+   repo_name.ends_with(last)
+}
+
+/// Stable hash of content with whitespace runs collapsed, so chunks differing only in
+/// indentation or line endings hash the same. Uses 64-bit FNV-1a rendered as hex.
+pub fn content_hash(content: &str) -> String {
+   const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+   const FNV_PRIME: u64 = 0x100000001b3;
+
+   let mut hash = FNV_OFFSET_BASIS;
+   for (i, word) in content.split_whitespace().enumerate() {
+      if i > 0 {
+         hash = (hash ^ u64::from(b' ')).wrapping_mul(FNV_PRIME);
+      }
+      for byte in word.bytes() {
+         hash = (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME);
+      }
+   }
+
+   format!("{hash:016x}")
+}
+
+/// Normalizes whitespace that varies between checkouts without changing meaning: CRLF and CR
+/// line endings become LF and trailing whitespace is removed from every line. With
+/// `collapse_blank_lines`, runs of blank lines are reduced to a single blank line.
+pub fn normalize_content(content: &str, collapse_blank_lines: bool) -> String {
+   let content = content.replace("\r\n", "\n").replace('\r', "\n");
+   let mut normalized = String::with_capacity(content.len());
+   let mut previous_blank = false;
+
+   for (i, line) in content.split('\n').enumerate() {
+      let line = line.trim_end();
+      let blank = line.is_empty();
+      if collapse_blank_lines && blank && previous_blank {
+         continue;
+      }
+      if i > 0 {
+         normalized.push('\n');
+      }
+      normalized.push_str(line);
+      previous_blank = blank;
+   }
+
+   normalized
+}
+
+/// Reduces a chunk to code that can be pasted as-is: the bodies of its fenced code blocks when
+/// it has any, otherwise its lines without doc comments (`///`, `//!` and `/** */`)
+pub fn raw_code(content: &str) -> String {
+   let mut blocks: Vec<Vec<&str>> = Vec::new();
+   let mut in_fence = false;
+   for line in content.lines() {
+      if line.trim_start().starts_with("```") {
+         in_fence = !in_fence;
+         if in_fence {
+            blocks.push(Vec::new());
+         }
+      } else if in_fence && let Some(block) = blocks.last_mut() {
+         block.push(line);
+      }
+   }
+   if !blocks.is_empty() {
+      return blocks
+         .iter()
+         .map(|block| block.join("\n"))
+         .collect::<Vec<_>>()
+         .join("\n\n");
+   }
+
+   let mut in_doc_block = false;
+   content
+      .lines()
+      .filter(|line| {
+         let line = line.trim_start();
+         if in_doc_block || line.starts_with("/**") {
+            in_doc_block = !line.contains("*/");
+            return false;
+         }
+         !(line.starts_with("///") || line.starts_with("//!"))
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
    use super::*;
@@ -109,6 +276,16 @@ mod tests {
       Ok(())
    }
 
+   #[test]
+   fn test_is_repo_alias() {
+      assert!(is_repo_alias("tokio"));
+      assert!(is_repo_alias("serde_json-1.0"));
+      assert!(!is_repo_alias("tokio-rs/tokio"));
+      assert!(!is_repo_alias("https://github.com/tokio-rs/tokio"));
+      assert!(!is_repo_alias("tokio-rs__tokio"));
+      assert!(!is_repo_alias(""));
+   }
+
    #[test]
    fn test_parse_repository_input() -> Result<()> {
       // Test full URLs
@@ -157,4 +334,102 @@ mod tests {
 
       Ok(())
    }
+
+   #[test]
+   fn test_gen_permalink() {
+      assert_eq!(
+         gen_permalink(
+            "https://github.com/rust-lang/rust",
+            "0123abc",
+            "src/lib.rs",
+            Some((10, 20))
+         )
+         .as_deref(),
+         Some("https://github.com/rust-lang/rust/blob/0123abc/src/lib.rs#L10-L20")
+      );
+      assert_eq!(
+         gen_permalink(
+            "https://github.com/tokio-rs/tokio",
+            "0123abc",
+            "README.md",
+            Some((3, 3))
+         )
+         .as_deref(),
+         Some("https://github.com/tokio-rs/tokio/blob/0123abc/README.md#L3")
+      );
+      assert_eq!(
+         gen_permalink(
+            "https://gitlab.com/owner/repo",
+            "0123abc",
+            "src/lib.rs",
+            Some((1, 2))
+         ),
+         None
+      );
+   }
+
+   #[test]
+   fn test_matches_repo_pattern() {
+      let repos = ["acme/api", "acme/web", "other/api"];
+
+      let matched: Vec<_> = repos
+         .iter()
+         .filter(|repo| matches_repo_pattern("acme/*", repo))
+         .collect();
+      assert_eq!(matched, vec![&"acme/api", &"acme/web"]);
+
+      // prefix match without a wildcard
+      assert!(matches_repo_pattern("acme/", "acme/api"));
+      assert!(!matches_repo_pattern("acme/", "other/api"));
+
+      // wildcards in other positions
+      assert!(matches_repo_pattern("*/api", "acme/api"));
+      assert!(matches_repo_pattern("*/api", "other/api"));
+      assert!(!matches_repo_pattern("*/api", "acme/web"));
+      assert!(matches_repo_pattern("a*e/*i", "acme/api"));
+      assert!(!matches_repo_pattern("acme/*b*", "acme/api"));
+      assert!(matches_repo_pattern("*", "anything/at-all"));
+   }
+
+   #[test]
+   fn test_content_hash_ignores_whitespace_differences() {
+      assert_eq!(
+         content_hash("fn main() {\n    run();\n}"),
+         content_hash("fn main() {\r\n\trun();\r\n}\n")
+      );
+      assert_ne!(content_hash("fn main() {}"), content_hash("fn run() {}"));
+      // FNV-1a offset basis for empty input
+      assert_eq!(content_hash(""), "cbf29ce484222325");
+   }
+
+   #[test]
+   fn test_normalize_content() {
+      let content = "fn main() {  \r\n\tprintln!(\"hi\");\t\r\n\r\n\r\n\n}\r";
+
+      assert_eq!(
+         normalize_content(content, false),
+         "fn main() {\n\tprintln!(\"hi\");\n\n\n\n}\n"
+      );
+      assert_eq!(
+         normalize_content(content, true),
+         "fn main() {\n\tprintln!(\"hi\");\n\n}\n"
+      );
+   }
+
+   #[test]
+   fn test_raw_code_strips_fences_and_doc_comments() {
+      let doc_item =
+         "Parses the input.\n\n```rust\npub fn parse(input: &str) -> Widget {\n    Widget\n}\n```";
+      assert_eq!(
+         raw_code(doc_item),
+         "pub fn parse(input: &str) -> Widget {\n    Widget\n}"
+      );
+
+      let repo_chunk =
+         "/// Parses the input.\n///\n/// Never fails.\n#[must_use]\npub fn parse() {}";
+      assert_eq!(raw_code(repo_chunk), "#[must_use]\npub fn parse() {}");
+
+      let ts_chunk = "/**\n * Renders the widget.\n */\nexport function render() {}";
+      assert_eq!(raw_code(ts_chunk), "export function render() {}");
+   }
 }
@@ -0,0 +1,62 @@
+use mcp_rust_docs_embed::chunks::{ChunkKind, detect::Language};
+use std::path::Path;
+
+const MANIFEST: &str = r#"[package]
+name = "widgets"
+version = "0.3.1"
+edition = "2021"
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+tokio = "1.45.1"
+rmcp = { git = "https://github.com/modelcontextprotocol/rust-sdk", branch = "main" }
+widgets-core = { path = "../core", optional = true }
+
+[dev-dependencies]
+tempfile = "3"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+"#;
+
+#[test]
+fn test_cargo_manifest_becomes_dependency_chunk() {
+   let language = Language::from_extension(Path::new("crates/widgets/Cargo.toml")).unwrap();
+   assert_eq!(language, Language::CargoManifest);
+   assert_eq!(Language::from_extension(Path::new("rustfmt.toml")), None);
+
+   let chunks = language.chunk(MANIFEST).unwrap();
+
+   assert_eq!(chunks.len(), 1);
+   let chunk = &chunks[0];
+   assert_eq!(chunk.kind, ChunkKind::Dependencies);
+   assert_eq!(chunk.name.as_deref(), Some("widgets"));
+   assert_eq!((chunk.start_line, chunk.end_line), (1, 16));
+   assert_eq!(
+      chunk.content,
+      "Dependencies of crate widgets 0.3.1
+
+[dependencies]
+rmcp = git https://github.com/modelcontextprotocol/rust-sdk, \
+       branch main
+serde = 1.0, features: derive
+tokio = 1.45.1
+widgets-core = path ../core, optional
+
+[dev-dependencies]
+tempfile = 3
+
+[target.cfg(unix).dependencies]
+libc = 0.2"
+   );
+}
+
+#[test]
+fn test_invalid_cargo_manifest_is_chunked_as_text() {
+   let template = "[package]\nname = {{project-name}}\n";
+
+   let chunks = Language::CargoManifest.chunk(template).unwrap();
+
+   assert_eq!(chunks.len(), 1);
+   assert_eq!(chunks[0].kind, ChunkKind::TextBlock);
+}
@@ -0,0 +1,80 @@
+use mcp_rust_docs_embed::chunks::{
+   ChunkKind,
+   changelog::{normalize_version, version_header},
+   detect::Language,
+};
+use std::path::Path;
+
+const CHANGELOG: &str = "# Changelog
+
+All notable changes to this project are documented here.
+
+## [Unreleased]
+
+- Support for streaming responses
+
+## [1.2.3] - 2024-05-01
+
+### Fixed
+
+- Panic when parsing an empty header
+
+## v1.2.0
+
+- Added the `retry` option
+";
+
+#[test]
+fn test_changelog_chunks_are_tagged_with_their_version() {
+   let language = Language::from_extension(Path::new("docs/CHANGELOG.md")).unwrap();
+   assert_eq!(language, Language::Changelog);
+   assert_eq!(
+      Language::from_extension(Path::new("docs/guide.md")),
+      Some(Language::Markdown)
+   );
+
+   let chunks = language.chunk(CHANGELOG).unwrap();
+
+   let versions: Vec<Option<&str>> = chunks
+      .iter()
+      .map(|chunk| chunk.version.as_deref())
+      .collect();
+   assert_eq!(
+      versions,
+      vec![None, Some("Unreleased"), Some("1.2.3"), Some("1.2.0")]
+   );
+   assert!(
+      chunks
+         .iter()
+         .all(|chunk| chunk.kind == ChunkKind::MarkdownSection)
+   );
+
+   // line numbers refer to the whole changelog
+   let fixed = &chunks[2];
+   assert!(fixed.content.starts_with("## [1.2.3] - 2024-05-01"));
+   assert!(fixed.content.contains("Panic when parsing an empty header"));
+   assert_eq!((fixed.start_line, fixed.end_line), (9, 15));
+}
+
+#[test]
+fn test_version_headers() {
+   assert_eq!(
+      version_header("## [1.2.3] - 2024-05-01").as_deref(),
+      Some("1.2.3")
+   );
+   assert_eq!(
+      version_header("## [0.9.0-beta.1](https://example.com/compare) (2024-01-02)").as_deref(),
+      Some("0.9.0-beta.1")
+   );
+   assert_eq!(version_header("## v1.2.0").as_deref(), Some("1.2.0"));
+   assert_eq!(version_header("# Release 2.0").as_deref(), Some("2.0"));
+   assert_eq!(
+      version_header("## Unreleased").as_deref(),
+      Some("Unreleased")
+   );
+   assert_eq!(version_header("### Fixed"), None);
+   assert_eq!(version_header("Version 1.2.3 fixed a panic"), None);
+
+   assert_eq!(normalize_version("v1.2.3"), "1.2.3");
+   assert_eq!(normalize_version("unreleased"), "Unreleased");
+}
@@ -0,0 +1,53 @@
+use mcp_rust_docs_embed::chunks::{
+   Chunk, ChunkKind,
+   transform::{ChunkTransformer, StripLicenseHeader},
+};
+
+fn chunk(content: &str) -> Chunk {
+   Chunk {
+      kind: ChunkKind::Function,
+      name: Some("parse".to_string()),
+      start_line: 1,
+      end_line: content.lines().count(),
+      content: content.to_string(),
+      ..Default::default()
+   }
+}
+
+#[test]
+fn test_strip_license_header_removes_leading_spdx_block() {
+   let mut chunk = chunk(
+      "// SPDX-License-Identifier: MIT OR Apache-2.0\n// Copyright (c) The Widget Authors\n\n/// \
+       Parses a widget.\npub fn parse() {}",
+   );
+
+   StripLicenseHeader.transform(&mut chunk);
+
+   assert_eq!(chunk.content, "/// Parses a widget.\npub fn parse() {}");
+   assert_eq!(chunk.start_line, 4);
+   assert_eq!(chunk.end_line, 5);
+}
+
+#[test]
+fn test_strip_license_header_removes_block_comment_header() {
+   let mut chunk = chunk(
+      "/*\n * Copyright 2024 Widget Inc.\n * Licensed under the Apache License, Version 2.0\n \
+       */\nexport function parse() {}",
+   );
+
+   StripLicenseHeader.transform(&mut chunk);
+
+   assert_eq!(chunk.content, "export function parse() {}");
+   assert_eq!(chunk.start_line, 5);
+}
+
+#[test]
+fn test_strip_license_header_keeps_other_comments() {
+   let content = "// Parses widgets from their text form\npub fn parse() {}";
+   let mut chunk = chunk(content);
+
+   StripLicenseHeader.transform(&mut chunk);
+
+   assert_eq!(chunk.content, content);
+   assert_eq!(chunk.start_line, 1);
+}
@@ -0,0 +1,63 @@
+use mcp_rust_docs_embed::chunks::{
+   Chunk, rust::extract_rust_chunks, typescript::extract_typescript_chunks,
+};
+use std::{fs, path::PathBuf};
+
+/// Renders chunks in the format of the committed `.chunks` files: a header line with the
+/// kind, name and line range of each chunk, followed by its content
+fn render(chunks: &[Chunk]) -> String {
+   let mut rendered = String::new();
+   for chunk in chunks {
+      rendered.push_str(&format!(
+         "=== {:?} {} lines {}-{}\n{}\n",
+         chunk.kind,
+         chunk.name.as_deref().unwrap_or("-"),
+         chunk.start_line,
+         chunk.end_line,
+         chunk.content
+      ));
+   }
+   rendered
+}
+
+fn golden_dir() -> PathBuf {
+   PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// Compares the chunks of a fixture with its committed `<fixture>.chunks` file. Run with
+/// `UPDATE_GOLDEN=1` to rewrite the file after an intended change of the chunk boundaries.
+fn assert_golden(fixture: &str, chunks: &[Chunk]) {
+   let golden_path = golden_dir().join(format!("{fixture}.chunks"));
+   let actual = render(chunks);
+
+   if std::env::var_os("UPDATE_GOLDEN").is_some() {
+      fs::write(&golden_path, &actual).unwrap();
+      return;
+   }
+
+   let expected = fs::read_to_string(&golden_path).unwrap_or_else(|e| {
+      panic!("failed to read {}: {e}", golden_path.display());
+   });
+   assert_eq!(
+      actual,
+      expected,
+      "chunks of {fixture} differ from {}; rerun with UPDATE_GOLDEN=1 if the change is intended",
+      golden_path.display()
+   );
+}
+
+fn read_fixture(fixture: &str) -> String {
+   fs::read_to_string(golden_dir().join(fixture)).unwrap()
+}
+
+#[test]
+fn test_rust_chunks_match_golden_file() {
+   let chunks = extract_rust_chunks(&read_fixture("sample.rs")).unwrap();
+   assert_golden("sample.rs", &chunks);
+}
+
+#[test]
+fn test_typescript_chunks_match_golden_file() {
+   let chunks = extract_typescript_chunks(&read_fixture("sample.ts")).unwrap();
+   assert_golden("sample.ts", &chunks);
+}
@@ -0,0 +1,56 @@
+//! Geometry primitives used by the renderer
+
+use std::fmt;
+
+/// A point on the plane
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Shapes the renderer can draw
+pub enum Shape {
+    Circle { center: Point, radius: f64 },
+    Polygon(Vec<Point>),
+}
+
+impl Point {
+    /// Distance between two points
+    pub fn distance(&self, other: &Point) -> f64 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2)).sqrt()
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+// Area helpers follow.
+// They are kept free-standing so they can be used without a Shape.
+
+/// Area of a circle of the given radius
+#[inline]
+pub fn circle_area(radius: f64) -> f64 {
+    std::f64::consts::PI * radius * radius
+}
+
+#[must_use]
+
+pub fn origin() -> Point {
+    Point { x: 0.0, y: 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_is_symmetric() {
+        let a = Point { x: 1.0, y: 2.0 };
+        assert_eq!(a.distance(&origin()), origin().distance(&a));
+    }
+}
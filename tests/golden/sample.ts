@@ -0,0 +1,45 @@
+import { Injectable } from '@angular/core';
+
+/**
+ * Options accepted by the cache
+ */
+export interface CacheOptions {
+    ttlSeconds: number;
+    maxEntries?: number;
+}
+
+// Keys are namespaced by tenant
+export type CacheKey = `${string}:${string}`;
+
+export enum EvictionPolicy {
+    Lru = 'LRU',
+    Fifo = 'FIFO',
+}
+
+export const DEFAULT_OPTIONS: CacheOptions = { ttlSeconds: 60 };
+
+/**
+ * In-memory cache with time-based expiry
+ */
+@Injectable({ providedIn: 'root' })
+export class Cache<T> {
+    private entries = new Map<CacheKey, { value: T; expiresAt: number }>();
+
+    constructor(private options: CacheOptions = DEFAULT_OPTIONS) {}
+
+    get(key: CacheKey): T | undefined {
+        const entry = this.entries.get(key);
+        return entry && entry.expiresAt > Date.now() ? entry.value : undefined;
+    }
+}
+
+/** Builds a cache key for a tenant */
+export function cacheKey(tenant: string, id: string): CacheKey {
+    return `${tenant}:${id}`;
+}
+
+const isExpired = (expiresAt: number): boolean => expiresAt <= Date.now();
+
+function internalHelper() {
+    return isExpired(0);
+}
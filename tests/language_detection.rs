@@ -0,0 +1,87 @@
+use mcp_rust_docs_embed::chunks::{
+   ChunkKind,
+   detect::{Language, LanguageCounts},
+};
+use std::path::Path;
+
+#[test]
+fn test_extensionless_python_script_is_chunked_as_python() {
+   let source = r#"#!/usr/bin/env python3
+"""Release helper."""
+
+import sys
+
+
+def main(argv):
+    print("releasing", argv[1:])
+
+
+if __name__ == "__main__":
+    main(sys.argv)
+"#;
+   let path = Path::new("scripts/release");
+
+   assert_eq!(Language::from_extension(path), None);
+   let language = Language::detect(path, source).expect("language should be detected");
+   assert_eq!(language, Language::Python);
+
+   let chunks = language.chunk(source).unwrap();
+   assert!(!chunks.is_empty());
+   assert!(
+      chunks
+         .iter()
+         .all(|chunk| chunk.kind == ChunkKind::TextBlock)
+   );
+   assert_eq!(chunks[0].start_line, 1);
+   assert!(
+      chunks
+         .iter()
+         .any(|chunk| chunk.content.contains("def main(argv):"))
+   );
+}
+
+#[test]
+fn test_detect_language_from_file_name_and_shebang() {
+   assert_eq!(
+      Language::detect(Path::new("Dockerfile"), "FROM rust:1.88\n"),
+      Some(Language::Dockerfile)
+   );
+   assert_eq!(
+      Language::detect(Path::new("build/bindings.rs.in"), "pub fn f() {}\n"),
+      Some(Language::Rust)
+   );
+   assert_eq!(
+      Language::detect(Path::new("bin/setup"), "#!/bin/bash\nset -e\n"),
+      Some(Language::Shell)
+   );
+   assert_eq!(
+      Language::detect(
+         Path::new("bin/serve"),
+         "#!/usr/bin/env -S node --experimental\n"
+      ),
+      Some(Language::JavaScript)
+   );
+   assert_eq!(
+      Language::detect(Path::new("LICENSE"), "MIT License\n"),
+      None
+   );
+}
+
+#[test]
+fn test_primary_language_ignores_docs() {
+   let mut counts = LanguageCounts::default();
+   for path in [
+      "src/main.rs",
+      "src/lib.rs",
+      "web/app.ts",
+      "docs/intro.md",
+      "docs/guide.md",
+      "docs/faq.md",
+      "Cargo.toml",
+   ] {
+      counts.record(Path::new(path));
+   }
+   assert_eq!(counts.primary(), Some("Rust"));
+
+   assert_eq!(LanguageCounts::default().primary(), None);
+}
@@ -1,3 +1,4 @@
+use mcp_rust_docs_embed::chunks::markdown::{extract_markdown_chunks, reconstruct};
 use text_splitter::{ChunkConfig, MarkdownSplitter};
 
 const MARKDOWN_CONTENT: &str = r#"# Main Documentation Title
@@ -206,3 +207,25 @@ fn test_extract_markdown_chunks() {
       "Content length mismatch after chunking"
    );
 }
+
+#[test]
+fn test_markdown_chunks_reconstruct_source() {
+   let chunks = extract_markdown_chunks(MARKDOWN_CONTENT).unwrap();
+
+   assert!(chunks.len() > 1);
+   assert_eq!(reconstruct(&chunks), MARKDOWN_CONTENT);
+}
+
+#[test]
+fn test_markdown_chunks_keep_trailing_whitespace_lines() {
+   let source = format!(
+      "# Notes  \n\n{}\n   \n\t\n\n",
+      "A sentence that fills the section. ".repeat(60)
+   );
+
+   let chunks = extract_markdown_chunks(&source).unwrap();
+
+   assert!(chunks.len() > 1);
+   assert_eq!(reconstruct(&chunks), source);
+   assert!(chunks.last().unwrap().content.ends_with("   \n\t\n\n"));
+}
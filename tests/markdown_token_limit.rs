@@ -0,0 +1,80 @@
+use mcp_rust_docs_embed::chunks::{
+   Chunk, ChunkKind,
+   markdown::{extract_markdown_chunks, split_to_token_limit},
+   tokenizer::tokenizer,
+};
+
+const MAX_TOKENS: usize = 8192;
+
+fn huge_code_block_doc(lines: usize) -> String {
+   let mut doc = String::from("# Generated bindings\n\nThe full table of generated constants:\n\n");
+   doc.push_str("```rust\n");
+   for i in 0..lines {
+      doc.push_str(&format!(
+         "pub const GENERATED_CONSTANT_{i}: u64 = 0x{i:016x}; // entry {i} of the table\n"
+      ));
+   }
+   doc.push_str("```\n\nThat is all.\n");
+   doc
+}
+
+#[test]
+fn test_markdown_chunks_stay_within_token_limit() {
+   let bpe = tokenizer();
+   let doc = huge_code_block_doc(5_000);
+
+   let chunks = extract_markdown_chunks(&doc).unwrap();
+   assert!(!chunks.is_empty());
+
+   for chunk in &chunks {
+      let tokens = bpe.encode_with_special_tokens(&chunk.content).len();
+      assert!(
+         tokens <= MAX_TOKENS,
+         "chunk at lines {}-{} has {tokens} tokens",
+         chunk.start_line,
+         chunk.end_line
+      );
+   }
+}
+
+#[test]
+fn test_oversized_chunk_splits_inside_code_fence() {
+   let bpe = tokenizer();
+   let doc = huge_code_block_doc(200);
+   let chunk = Chunk {
+      kind: ChunkKind::MarkdownSection,
+      start_line: 1,
+      end_line: doc.lines().count(),
+      content: doc.clone(),
+      ..Default::default()
+   };
+
+   let pieces = split_to_token_limit(chunk, 500);
+   assert!(pieces.len() > 1, "expected the chunk to be split");
+
+   for piece in &pieces {
+      assert!(bpe.encode_with_special_tokens(&piece.content).len() <= 500);
+      // every piece opens and closes its fences, so each renders as valid markdown
+      let fence_lines = piece
+         .content
+         .lines()
+         .filter(|line| line.starts_with("```"))
+         .count();
+      assert_eq!(
+         fence_lines % 2,
+         0,
+         "unbalanced fence in:\n{}",
+         piece.content
+      );
+   }
+
+   // continuation pieces reopen the fence with its language
+   assert!(pieces[1].content.starts_with("```rust\n"));
+
+   // pieces cover the original lines without gaps
+   assert_eq!(pieces[0].start_line, 1);
+   for pair in pieces.windows(2) {
+      assert_eq!(pair[1].start_line, pair[0].end_line + 1);
+   }
+   assert_eq!(pieces.last().unwrap().end_line, doc.lines().count());
+}
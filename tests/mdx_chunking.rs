@@ -0,0 +1,118 @@
+use mcp_rust_docs_embed::chunks::{ChunkKind, detect::Language, markdown::extract_markdown_chunks};
+use std::path::Path;
+
+const MDX_CONTENT: &str = r#"---
+title: Installation
+---
+
+import { Tabs, TabItem } from '@site/src/components/Tabs';
+import Callout from '@site/src/components/Callout';
+
+# Installation
+
+Install the client with your package manager of choice. Every package manager installs the
+same build, so pick whichever one your project already uses.
+
+<Tabs groupId="package-manager">
+  <TabItem value="npm" label="npm">
+
+```bash
+npm install @acme/client
+```
+
+  </TabItem>
+  <TabItem value="yarn" label="yarn">
+
+```bash
+yarn add @acme/client
+```
+
+  </TabItem>
+</Tabs>
+
+<Callout type="warning">
+  The client requires Node 18 or later. Older versions are missing the `fetch` global and
+  fail at startup with a reference error.
+</Callout>
+
+## Configuration
+
+Create a client with your API key. The key is read from the `ACME_API_KEY` environment
+variable when it is not passed explicitly, which keeps secrets out of your source code.
+
+<Tabs groupId="language">
+  <TabItem value="ts" label="TypeScript">
+
+```ts
+import { Client } from '@acme/client';
+
+const client = new Client({ apiKey: process.env.ACME_API_KEY });
+```
+
+  </TabItem>
+  <TabItem value="js" label="JavaScript">
+
+```js
+const { Client } = require('@acme/client');
+
+const client = new Client({ apiKey: process.env.ACME_API_KEY });
+```
+
+  </TabItem>
+</Tabs>
+
+## Retries
+
+Requests that fail with a network error or a 5xx status are retried up to three times with
+exponential backoff. Set `maxRetries` to change the number of attempts, or to zero to disable
+retries entirely.
+
+<Callout type="info">
+  Retries only apply to idempotent requests. Creating resources is never retried automatically
+  so that a request that timed out after reaching the server cannot create duplicates.
+</Callout>
+
+## Next steps
+
+Read the <Link to="/docs/guides/auth">authentication guide</Link> to learn about scoped keys.
+"#;
+
+#[test]
+fn test_mdx_files_are_chunked_as_markdown() {
+   assert_eq!(
+      Language::from_extension(Path::new("docs/installation.mdx")),
+      Some(Language::Markdown)
+   );
+}
+
+#[test]
+fn test_mdx_chunk_line_numbers() {
+   let chunks = extract_markdown_chunks(MDX_CONTENT).unwrap();
+   assert!(chunks.len() > 1, "expected the document to be split");
+
+   let lines: Vec<&str> = MDX_CONTENT.lines().collect();
+   let mut previous_end = 0;
+
+   for chunk in &chunks {
+      assert_eq!(chunk.kind, ChunkKind::MarkdownSection);
+      assert!(
+         chunk.start_line >= previous_end,
+         "chunks should be in order"
+      );
+      previous_end = chunk.end_line;
+
+      // the chunk text must start on the line it is reported at
+      let first_line = chunk.content.lines().next().unwrap_or_default();
+      assert!(
+         lines[chunk.start_line - 1].ends_with(first_line),
+         "chunk at line {} starts with {:?} but the line is {:?}",
+         chunk.start_line,
+         first_line,
+         lines[chunk.start_line - 1]
+      );
+   }
+
+   // JSX components are kept as content rather than dropped
+   let all_content: String = chunks.iter().map(|chunk| chunk.content.as_str()).collect();
+   assert_eq!(all_content, MDX_CONTENT);
+}
@@ -0,0 +1,52 @@
+use mcp_rust_docs_embed::chunks::{ChunkKind, detect::Language};
+use std::{fs, path::Path};
+
+fn read_fixture(fixture: &str) -> String {
+   fs::read_to_string(
+      Path::new(env!("CARGO_MANIFEST_DIR"))
+         .join("tests/fixtures")
+         .join(fixture),
+   )
+   .unwrap()
+}
+
+#[test]
+fn test_notebook_markdown_and_code_cells_are_chunked() {
+   let path = Path::new("notebooks/analysis.ipynb");
+   let language = Language::from_extension(path).unwrap();
+   assert_eq!(language, Language::Notebook);
+
+   let chunks = language.chunk(&read_fixture("analysis.ipynb")).unwrap();
+
+   // the empty code cell is skipped, and outputs are not embedded
+   let cells: Vec<Option<usize>> = chunks.iter().map(|chunk| chunk.cell).collect();
+   assert_eq!(cells, vec![Some(0), Some(1), Some(3), Some(4)]);
+   assert!(
+      chunks
+         .iter()
+         .all(|chunk| !chunk.content.contains("rows loaded\n"))
+   );
+
+   assert_eq!(chunks[0].kind, ChunkKind::MarkdownSection);
+   assert!(chunks[0].content.starts_with("# Sales analysis"));
+   assert_eq!(chunks[2].kind, ChunkKind::MarkdownSection);
+
+   // code cells are chunked by the kernel language, with lines relative to the cell
+   assert_eq!(chunks[1].kind, ChunkKind::TextBlock);
+   assert!(chunks[1].content.contains("pd.read_csv(\"sales.csv\")"));
+   assert_eq!((chunks[1].start_line, chunks[1].end_line), (1, 4));
+   assert!(chunks[3].content.starts_with("def monthly_trend(frame):"));
+}
+
+#[test]
+fn test_unparsable_notebook_is_chunked_as_text() {
+   // a notebook stored with Git LFS, checked out without fetching it
+   let lfs_pointer =
+      "version https://git-lfs.github.com/spec/v1\noid sha256:4d7a2146\nsize 12345\n";
+
+   let chunks = Language::Notebook.chunk(lfs_pointer).unwrap();
+
+   assert_eq!(chunks.len(), 1);
+   assert_eq!(chunks[0].kind, ChunkKind::TextBlock);
+   assert_eq!(chunks[0].cell, None);
+}
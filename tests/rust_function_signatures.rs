@@ -0,0 +1,71 @@
+use mcp_rust_docs_embed::chunks::{ChunkKind, rust::extract_rust_chunks};
+
+const SOURCE: &str = r#"pub const unsafe fn from_raw<T>(ptr: *const T) -> Handle<T> {
+    Handle(ptr)
+}
+
+impl<T> Cache<T> {
+    /// Loads the value when missing
+    pub async fn load<F>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        todo!()
+    }
+
+    fn len(&self) -> usize {
+        0
+    }
+
+    #[test]
+    fn not_a_method() {}
+}
+"#;
+
+#[test]
+fn test_signatures_keep_qualifiers_generics_and_where_clauses() {
+   let chunks = extract_rust_chunks(SOURCE).unwrap();
+
+   let function = chunks
+      .iter()
+      .find(|chunk| chunk.kind == ChunkKind::Function)
+      .unwrap();
+   assert_eq!(
+      function.signature.as_ref().unwrap().to_string(),
+      "const unsafe fn from_raw<T>(ptr: *const T) -> Handle<T>"
+   );
+}
+
+#[test]
+fn test_impl_methods_have_signatures() {
+   let chunks = extract_rust_chunks(SOURCE).unwrap();
+
+   let impl_chunk = chunks
+      .iter()
+      .find(|chunk| chunk.kind == ChunkKind::Impl)
+      .unwrap();
+   assert!(impl_chunk.signature.is_none());
+
+   let methods: Vec<_> = impl_chunk
+      .methods
+      .iter()
+      .map(|method| {
+         (
+            method.signature.to_string(),
+            method.start_line,
+            method.end_line,
+         )
+      })
+      .collect();
+   assert_eq!(
+      methods,
+      vec![
+         (
+            "async fn load<F>(&self, f: F) -> T where F: FnOnce() -> T".to_string(),
+            6,
+            12
+         ),
+         ("fn len(&self) -> usize".to_string(), 14, 16),
+      ]
+   );
+}
@@ -0,0 +1,53 @@
+use mcp_rust_docs_embed::chunks::{ChunkKind, rust::extract_rust_chunks};
+
+const SOURCE: &str = r#"/// Adds two numbers
+pub fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[test]
+fn adds_top_level() {
+    assert_eq!(add(1, 2), 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn adds_async() {
+    assert_eq!(add(2, 2), 4);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds() {
+        assert_eq!(add(2, 3), 5);
+    }
+}
+
+mod helpers;
+"#;
+
+#[test]
+fn test_rust_test_functions_and_modules_are_classified() {
+   let chunks = extract_rust_chunks(SOURCE).unwrap();
+   let kinds: Vec<_> = chunks
+      .iter()
+      .map(|chunk| (chunk.kind, chunk.name.as_deref()))
+      .collect();
+
+   assert_eq!(
+      kinds,
+      vec![
+         (ChunkKind::Function, Some("add")),
+         (ChunkKind::Test, Some("adds_top_level")),
+         (ChunkKind::Test, Some("adds_async")),
+         (ChunkKind::Test, Some("tests")),
+      ]
+   );
+
+   // the test module chunk includes its gating attribute
+   let module = &chunks[3];
+   assert!(module.content.starts_with("#[cfg(test)]"));
+   assert_eq!((module.start_line, module.end_line), (16, 24));
+}
@@ -384,6 +384,7 @@ export class MyService {
 
    let chunk = &chunks[0];
    assert_eq!(chunk.kind, ChunkKind::Class);
+   assert_eq!(chunk.name.as_deref(), Some("MyService"));
    assert!(chunk.content.contains("@Injectable()"));
    assert!(chunk.content.contains("@Singleton()"));
    assert!(chunk.content.contains("* Service documentation"));
@@ -440,6 +441,8 @@ const privateConst = 'not exported';
    assert_eq!(const_chunks.len(), 2, "Should extract only exported consts");
    assert!(const_chunks[0].content.contains("API_VERSION"));
    assert!(const_chunks[1].content.contains("CONFIG"));
+   assert_eq!(const_chunks[0].name.as_deref(), Some("API_VERSION"));
+   assert_eq!(const_chunks[1].name.as_deref(), Some("CONFIG"));
 }
 
 #[test]